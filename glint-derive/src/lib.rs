@@ -0,0 +1,243 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Field, Fields, LitStr, Path};
+
+/// Derive `glint::state::StateValue` for a type
+///
+/// For a struct with named fields, the derive also implements
+/// `glint::state::PartialState` and generates a partial-update type named after
+/// the struct with an `Update` suffix. The update type has an `Option` of every
+/// field and a `with_<field>` setter for each, so nodes can return just the
+/// fields they changed.
+///
+/// Fields take the `#[reducer(...)]` attribute to declare how updates are
+/// combined with the current value:
+///
+/// - `#[reducer(append)]` extends the field with the update's items
+/// - `#[reducer(add)]` adds the update to the field
+/// - `#[reducer(custom = "path::to::fn")]` calls `fn(current, update) -> value`
+///
+/// Fields without a reducer are replaced by the update's value. When update
+/// nodes run as parallel branches, their updates are folded into the merged
+/// state in turn with `PartialState::merge_update`, so custom functions see
+/// every update once, and two updates setting a field without a reducer
+/// conflict. `PartialState::channel_reducer` merges branches that return whole
+/// states with the `append` and `add` reducers only.
+#[proc_macro_derive(StateValue, attributes(reducer))]
+pub fn derive_state_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// How updates to a field are combined with its current value
+enum FieldReducer {
+    LastValue,
+    Append,
+    Add,
+    Custom(Path),
+}
+
+impl FieldReducer {
+    /// Parse the `#[reducer(...)]` attribute of a field, if any
+    fn from_field(field: &Field) -> syn::Result<Self> {
+        let mut reducer = None;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("reducer")) {
+            attr.parse_nested_meta(|meta| {
+                if reducer.is_some() {
+                    return Err(meta.error("a field can only have one reducer"));
+                }
+                reducer = Some(if meta.path.is_ident("append") {
+                    FieldReducer::Append
+                } else if meta.path.is_ident("add") {
+                    FieldReducer::Add
+                } else if meta.path.is_ident("custom") {
+                    let path: LitStr = meta.value()?.parse()?;
+                    FieldReducer::Custom(path.parse()?)
+                } else {
+                    return Err(meta.error(
+                        "unsupported reducer, expected `append`, `add` or `custom = \"path\"`",
+                    ));
+                });
+                Ok(())
+            })?;
+        }
+        Ok(reducer.unwrap_or(FieldReducer::LastValue))
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let state_value = quote! {
+        impl #impl_generics ::glint::state::StateValue for #name #ty_generics #where_clause {}
+    };
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            fields => return without_reducers(fields.iter(), state_value),
+        },
+        Data::Enum(data) => {
+            let fields = data
+                .variants
+                .iter()
+                .flat_map(|variant| variant.fields.iter());
+            return without_reducers(fields, state_value);
+        }
+        Data::Union(data) => return without_reducers(data.fields.named.iter(), state_value),
+    };
+
+    let vis = &input.vis;
+    let generics = &input.generics;
+    let update = format_ident!("{}Update", name);
+    let update_doc = format!(
+        "Partial update of [`{}`], holding new values for the fields a node changed",
+        name
+    );
+
+    let mut update_fields = Vec::new();
+    let mut defaults = Vec::new();
+    let mut setters = Vec::new();
+    let mut applies = Vec::new();
+    let mut merges = Vec::new();
+    let mut channels = Vec::new();
+    for field in fields {
+        let field_vis = &field.vis;
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let field_name = ident.to_string();
+        let setter = format_ident!("with_{}", field_name.trim_start_matches("r#"));
+        let channel = field_name.trim_start_matches("r#");
+
+        update_fields.push(quote! { #field_vis #ident: ::core::option::Option<#ty> });
+        defaults.push(quote! { #ident: ::core::option::Option::None });
+        setters.push(quote! {
+            #[doc = concat!("Set the new value of `", #channel, "`")]
+            #field_vis fn #setter(mut self, value: #ty) -> Self {
+                self.#ident = ::core::option::Option::Some(value);
+                self
+            }
+        });
+
+        let reducer = FieldReducer::from_field(field)?;
+        let replaces = matches!(reducer, FieldReducer::LastValue);
+        let (apply, channel_reducer) = match reducer {
+            FieldReducer::LastValue => (quote! { self.#ident = value; }, None),
+            FieldReducer::Append => (
+                quote! { ::core::iter::Extend::extend(&mut self.#ident, value); },
+                Some(quote! { ::glint::state::Reducer::Append }),
+            ),
+            FieldReducer::Add => (
+                quote! { ::core::ops::AddAssign::add_assign(&mut self.#ident, value); },
+                Some(quote! { ::glint::state::Reducer::Add }),
+            ),
+            FieldReducer::Custom(path) => (
+                quote! { self.#ident = #path(::core::clone::Clone::clone(&self.#ident), value); },
+                None,
+            ),
+        };
+        applies.push(quote! {
+            if let ::core::option::Option::Some(value) = update.#ident {
+                #apply
+            }
+        });
+        let check_conflict = replaces.then(|| {
+            quote! {
+                if !written.insert(#channel) {
+                    return ::core::result::Result::Err(::glint::Error::StateConflict(
+                        ::std::format!("Parallel updates both set field '{}' without a reducer", #channel),
+                    ));
+                }
+            }
+        });
+        merges.push(quote! {
+            if let ::core::option::Option::Some(value) = update.#ident {
+                #check_conflict
+                #apply
+            }
+        });
+        if let Some(channel_reducer) = channel_reducer {
+            channels.push(quote! { .with_field(#channel, #channel_reducer) });
+        }
+    }
+
+    Ok(quote! {
+        #state_value
+
+        #[doc = #update_doc]
+        #[derive(Debug, Clone)]
+        #vis struct #update #generics #where_clause {
+            #(#update_fields,)*
+        }
+
+        // Implemented by hand so that type parameters need not be `Default`
+        impl #impl_generics ::core::default::Default for #update #ty_generics #where_clause {
+            fn default() -> Self {
+                Self {
+                    #(#defaults,)*
+                }
+            }
+        }
+
+        #[allow(dead_code)]
+        impl #impl_generics #update #ty_generics #where_clause {
+            #(#setters)*
+        }
+
+        impl #impl_generics ::glint::state::PartialState for #name #ty_generics #where_clause {
+            type Update = #update #ty_generics;
+
+            fn apply_update(&mut self, update: Self::Update) {
+                #(#applies)*
+            }
+
+            fn merge_update(
+                &mut self,
+                update: Self::Update,
+                written: &mut ::std::collections::HashSet<&'static str>,
+            ) -> ::glint::Result<()> {
+                #(#merges)*
+                ::core::result::Result::Ok(())
+            }
+
+            fn channel_reducer() -> ::glint::state::ChannelReducer<Self> {
+                ::glint::state::ChannelReducer::new() #(#channels)*
+            }
+        }
+
+        impl #impl_generics ::glint::state::StateUpdate<#name #ty_generics>
+            for #update #ty_generics #where_clause
+        {
+            fn apply(
+                &self,
+                mut state: ::glint::state::State<#name #ty_generics>,
+            ) -> ::glint::Result<::glint::state::State<#name #ty_generics>> {
+                ::glint::state::PartialState::apply_update(
+                    &mut state.data,
+                    ::core::clone::Clone::clone(self),
+                );
+                ::core::result::Result::Ok(state)
+            }
+        }
+    })
+}
+
+/// Implement only `StateValue` for types without named fields, rejecting reducers
+fn without_reducers<'a>(
+    fields: impl Iterator<Item = &'a Field>,
+    state_value: TokenStream2,
+) -> syn::Result<TokenStream2> {
+    for field in fields {
+        if let Some(attr) = field.attrs.iter().find(|a| a.path().is_ident("reducer")) {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "reducers are only supported on structs with named fields",
+            ));
+        }
+    }
+    Ok(state_value)
+}
@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+use crate::Result;
+
+/// A store of serialized node results, looked up by cache key
+///
+/// Entries past their expiry time must not be returned.
+pub trait NodeCache: Send + Sync {
+    /// Get the entry stored under `key`, unless it is missing or expired
+    fn get(&self, key: &str) -> Result<Option<Value>>;
+
+    /// Store an entry under `key`, expiring after `ttl` if one is given
+    fn set(&self, key: &str, value: Value, ttl: Option<Duration>) -> Result<()>;
+
+    /// Remove every entry
+    fn clear(&self) -> Result<()>;
+}
+
+/// A cached value with the time it expires at
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    value: Value,
+    /// Expiry time in milliseconds since the Unix epoch
+    expires_at_ms: Option<u64>,
+}
+
+impl CacheEntry {
+    fn new(value: Value, ttl: Option<Duration>) -> Self {
+        Self {
+            value,
+            expires_at_ms: ttl.map(|ttl| now_ms().saturating_add(ttl.as_millis() as u64)),
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_at_ms
+            .is_some_and(|expires_at| expires_at <= now_ms())
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// An in-memory node cache
+#[derive(Debug, Default)]
+pub struct MemoryCache {
+    entries: RwLock<HashMap<String, CacheEntry>>,
+}
+
+impl MemoryCache {
+    /// Create an empty in-memory cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the number of stored entries, including expired ones not yet evicted
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().len()
+    }
+
+    /// Check whether the cache has no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl NodeCache for MemoryCache {
+    fn get(&self, key: &str) -> Result<Option<Value>> {
+        let entries = self.entries.read().unwrap();
+        Ok(match entries.get(key) {
+            Some(entry) if !entry.is_expired() => Some(entry.value.clone()),
+            _ => None,
+        })
+    }
+
+    fn set(&self, key: &str, value: Value, ttl: Option<Duration>) -> Result<()> {
+        let mut entries = self.entries.write().unwrap();
+        entries.retain(|_, entry| !entry.is_expired());
+        entries.insert(key.to_string(), CacheEntry::new(value, ttl));
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.entries.write().unwrap().clear();
+        Ok(())
+    }
+}
+
+/// A node cache that keeps every entry in a JSON file in a directory
+///
+/// The cache survives restarts, so re-running an evaluation suite reuses the
+/// results of earlier runs. Keys are used as file names, and clearing the cache
+/// removes only its entry files, so the directory can be shared with other files.
+#[derive(Debug, Clone)]
+pub struct FileCache {
+    directory: PathBuf,
+}
+
+impl FileCache {
+    /// Create a cache in the given directory, which is created on first write
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    /// Get the path of the file holding the entry of a key
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{}.json", key))
+    }
+}
+
+impl NodeCache for FileCache {
+    fn get(&self, key: &str) -> Result<Option<Value>> {
+        let path = self.entry_path(key);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::Io(e)),
+        };
+
+        let entry: CacheEntry = serde_json::from_str(&content)?;
+        if entry.is_expired() {
+            fs::remove_file(&path)?;
+            return Ok(None);
+        }
+        Ok(Some(entry.value))
+    }
+
+    fn set(&self, key: &str, value: Value, ttl: Option<Duration>) -> Result<()> {
+        fs::create_dir_all(&self.directory)?;
+        let json = serde_json::to_string(&CacheEntry::new(value, ttl))?;
+        fs::write(self.entry_path(key), json)?;
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(Error::Io(e)),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            if !path.is_file() || path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            // Leave JSON files the cache did not write
+            let is_entry = fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str::<CacheEntry>(&content).ok())
+                .is_some();
+            if is_entry {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_memory_cache_ttl() {
+        let cache = MemoryCache::new();
+        cache.set("forever", json!(1), None).unwrap();
+        cache
+            .set("expired", json!(2), Some(Duration::ZERO))
+            .unwrap();
+
+        assert_eq!(cache.get("forever").unwrap(), Some(json!(1)));
+        assert_eq!(cache.get("expired").unwrap(), None);
+        assert_eq!(cache.get("missing").unwrap(), None);
+
+        // Expired entries are evicted on the next write
+        cache.set("other", json!(3), None).unwrap();
+        assert_eq!(cache.len(), 2);
+        cache.clear().unwrap();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_file_cache() {
+        let directory = std::env::temp_dir().join(format!("glint-cache-{}", uuid::Uuid::new_v4()));
+        let cache = FileCache::new(&directory);
+        assert_eq!(cache.get("key").unwrap(), None);
+
+        cache.set("key", json!({"answer": 42}), None).unwrap();
+        cache
+            .set("expired", json!("stale"), Some(Duration::ZERO))
+            .unwrap();
+
+        // A new cache over the same directory sees the stored entries
+        let reopened = FileCache::new(&directory);
+        assert_eq!(reopened.get("key").unwrap(), Some(json!({"answer": 42})));
+        assert_eq!(reopened.get("expired").unwrap(), None);
+        assert!(!directory.join("expired.json").exists());
+
+        reopened.clear().unwrap();
+        assert_eq!(reopened.get("key").unwrap(), None);
+        assert!(directory.exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_file_cache_clear_keeps_other_files() {
+        let directory = std::env::temp_dir().join(format!("glint-cache-{}", uuid::Uuid::new_v4()));
+        let cache = FileCache::new(&directory);
+        cache.set("key", json!(1), None).unwrap();
+        fs::write(directory.join("results.json"), r#"{"score": 0.9}"#).unwrap();
+        fs::write(directory.join("notes.txt"), "keep me").unwrap();
+
+        cache.clear().unwrap();
+        assert!(!directory.join("key.json").exists());
+        assert!(directory.join("results.json").exists());
+        assert!(directory.join("notes.txt").exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,443 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::schema::{Document, Message};
+
+/// Number of tokens a model call consumed, as reported by the provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    /// Tokens of the prompt
+    pub prompt_tokens: u32,
+    /// Tokens of the generated output
+    pub completion_tokens: u32,
+    /// Total tokens of the call
+    pub total_tokens: u32,
+}
+
+impl TokenUsage {
+    /// Record the usage on the token fields of a tracing span
+    pub(crate) fn record(&self, span: &tracing::Span) {
+        span.record("prompt_tokens", self.prompt_tokens);
+        span.record("completion_tokens", self.completion_tokens);
+        span.record("total_tokens", self.total_tokens);
+    }
+}
+
+/// Hooks called as graphs, models and retrievers run
+///
+/// Every hook does nothing by default, so handlers only implement the ones they
+/// need. Hooks are called inline and should return quickly; handlers that do slow
+/// work, such as writing to a database, should hand events off to a task.
+///
+/// Nodes are identified by their path and models by their name. Tools have no
+/// built-in type; nodes that call them report through
+/// [`NodeContext::callbacks`](crate::graph::NodeContext::callbacks). Hooks called
+/// during a graph run can find the run and node they belong to with
+/// [`current_scope`](crate::trace::current_scope).
+///
+/// # Examples
+///
+/// ```
+/// use glint::callbacks::{CallbackHandler, TokenUsage};
+/// use std::sync::atomic::{AtomicU32, Ordering};
+///
+/// #[derive(Default)]
+/// struct CostTracker {
+///     tokens: AtomicU32,
+/// }
+///
+/// impl CallbackHandler for CostTracker {
+///     fn on_llm_end(&self, _model: &str, _output: &str, usage: Option<&TokenUsage>) {
+///         if let Some(usage) = usage {
+///             self.tokens.fetch_add(usage.total_tokens, Ordering::Relaxed);
+///         }
+///     }
+/// }
+/// ```
+#[allow(unused_variables)]
+pub trait CallbackHandler: Send + Sync {
+    /// A top-level graph run started
+    fn on_run_start(&self, run_id: &str, thread_id: Option<&str>) {}
+
+    /// A top-level graph run finished, failed or stopped at an interrupt
+    fn on_run_end(&self, run_id: &str, elapsed: Duration, error: Option<&Error>) {}
+
+    /// A graph node started running with the given input state
+    fn on_node_start(&self, node: &str, step: usize, input: &dyn fmt::Debug) {}
+
+    /// A graph node finished running with the given output state
+    fn on_node_end(&self, node: &str, output: &dyn fmt::Debug, elapsed: Duration) {}
+
+    /// A graph node failed, after any retries
+    fn on_node_error(&self, node: &str, error: &Error) {}
+
+    /// A language model was called with the given prompts
+    fn on_llm_start(&self, model: &str, prompts: &[String]) {}
+
+    /// A chat model was called with the given messages
+    ///
+    /// Calls [`on_llm_start`](Self::on_llm_start) with the message contents by default.
+    fn on_chat_model_start(&self, model: &str, messages: &[Message]) {
+        let prompts: Vec<String> = messages
+            .iter()
+            .map(|message| message.content.clone())
+            .collect();
+        self.on_llm_start(model, &prompts);
+    }
+
+    /// A model, or a node on its behalf, produced a chunk of output
+    fn on_llm_token(&self, source: &str, token: &str) {}
+
+    /// A model call returned its output
+    fn on_llm_end(&self, model: &str, output: &str, usage: Option<&TokenUsage>) {}
+
+    /// A model call failed
+    fn on_llm_error(&self, model: &str, error: &Error) {}
+
+    /// An embedding model was called with the given texts
+    fn on_embedding_start(&self, model: &str, texts: &[String]) {}
+
+    /// An embedding model call returned
+    fn on_embedding_end(&self, model: &str, usage: Option<&TokenUsage>) {}
+
+    /// An embedding model call failed
+    fn on_embedding_error(&self, model: &str, error: &Error) {}
+
+    /// A retriever was queried
+    fn on_retriever_start(&self, query: &str) {}
+
+    /// A retriever returned documents with their scores
+    fn on_retriever_end(&self, documents: &[(Document, f32)]) {}
+
+    /// A retriever query failed
+    fn on_retriever_error(&self, error: &Error) {}
+
+    /// A tool was called with the given input
+    fn on_tool_start(&self, tool: &str, input: &str) {}
+
+    /// A tool returned its output
+    fn on_tool_end(&self, tool: &str, output: &str) {}
+
+    /// A tool call failed
+    fn on_tool_error(&self, tool: &str, error: &Error) {}
+}
+
+/// The callback handlers registered on a component
+///
+/// Forwards every hook to each handler in registration order.
+#[derive(Clone, Default)]
+pub struct Callbacks {
+    handlers: Vec<Arc<dyn CallbackHandler>>,
+}
+
+impl fmt::Debug for Callbacks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Callbacks")
+            .field("handlers", &self.handlers.len())
+            .finish()
+    }
+}
+
+impl Callbacks {
+    /// Create an empty set of handlers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a handler
+    pub fn add(&mut self, handler: Arc<dyn CallbackHandler>) {
+        self.handlers.push(handler);
+    }
+
+    /// Check whether no handlers are registered
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    /// Create the handlers of both `self` and `other`, those of `self` first
+    pub fn merged(&self, other: &Callbacks) -> Callbacks {
+        if other.is_empty() {
+            return self.clone();
+        }
+
+        let mut handlers = self.handlers.clone();
+        handlers.extend(other.handlers.iter().cloned());
+        Callbacks { handlers }
+    }
+}
+
+impl CallbackHandler for Callbacks {
+    fn on_run_start(&self, run_id: &str, thread_id: Option<&str>) {
+        for handler in &self.handlers {
+            handler.on_run_start(run_id, thread_id);
+        }
+    }
+
+    fn on_run_end(&self, run_id: &str, elapsed: Duration, error: Option<&Error>) {
+        for handler in &self.handlers {
+            handler.on_run_end(run_id, elapsed, error);
+        }
+    }
+
+    fn on_node_start(&self, node: &str, step: usize, input: &dyn fmt::Debug) {
+        for handler in &self.handlers {
+            handler.on_node_start(node, step, input);
+        }
+    }
+
+    fn on_node_end(&self, node: &str, output: &dyn fmt::Debug, elapsed: Duration) {
+        for handler in &self.handlers {
+            handler.on_node_end(node, output, elapsed);
+        }
+    }
+
+    fn on_node_error(&self, node: &str, error: &Error) {
+        for handler in &self.handlers {
+            handler.on_node_error(node, error);
+        }
+    }
+
+    fn on_llm_start(&self, model: &str, prompts: &[String]) {
+        for handler in &self.handlers {
+            handler.on_llm_start(model, prompts);
+        }
+    }
+
+    fn on_chat_model_start(&self, model: &str, messages: &[Message]) {
+        for handler in &self.handlers {
+            handler.on_chat_model_start(model, messages);
+        }
+    }
+
+    fn on_llm_token(&self, source: &str, token: &str) {
+        for handler in &self.handlers {
+            handler.on_llm_token(source, token);
+        }
+    }
+
+    fn on_llm_end(&self, model: &str, output: &str, usage: Option<&TokenUsage>) {
+        for handler in &self.handlers {
+            handler.on_llm_end(model, output, usage);
+        }
+    }
+
+    fn on_llm_error(&self, model: &str, error: &Error) {
+        for handler in &self.handlers {
+            handler.on_llm_error(model, error);
+        }
+    }
+
+    fn on_embedding_start(&self, model: &str, texts: &[String]) {
+        for handler in &self.handlers {
+            handler.on_embedding_start(model, texts);
+        }
+    }
+
+    fn on_embedding_end(&self, model: &str, usage: Option<&TokenUsage>) {
+        for handler in &self.handlers {
+            handler.on_embedding_end(model, usage);
+        }
+    }
+
+    fn on_embedding_error(&self, model: &str, error: &Error) {
+        for handler in &self.handlers {
+            handler.on_embedding_error(model, error);
+        }
+    }
+
+    fn on_retriever_start(&self, query: &str) {
+        for handler in &self.handlers {
+            handler.on_retriever_start(query);
+        }
+    }
+
+    fn on_retriever_end(&self, documents: &[(Document, f32)]) {
+        for handler in &self.handlers {
+            handler.on_retriever_end(documents);
+        }
+    }
+
+    fn on_retriever_error(&self, error: &Error) {
+        for handler in &self.handlers {
+            handler.on_retriever_error(error);
+        }
+    }
+
+    fn on_tool_start(&self, tool: &str, input: &str) {
+        for handler in &self.handlers {
+            handler.on_tool_start(tool, input);
+        }
+    }
+
+    fn on_tool_end(&self, tool: &str, output: &str) {
+        for handler in &self.handlers {
+            handler.on_tool_end(tool, output);
+        }
+    }
+
+    fn on_tool_error(&self, tool: &str, error: &Error) {
+        for handler in &self.handlers {
+            handler.on_tool_error(tool, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::MockEmbeddings;
+    use crate::graph::{Command, GraphBuilder, NodeContext, NodeProcessor, RunConfig};
+    use crate::state::State;
+    use crate::traits::VectorStore;
+    use crate::vectorstores::MemoryVectorStore;
+    use crate::Result;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// Records the hooks it sees in a compact form
+    #[derive(Default)]
+    struct Recorder {
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl Recorder {
+        fn record(&self, call: String) {
+            self.calls.lock().unwrap().push(call);
+        }
+
+        fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+    }
+
+    impl CallbackHandler for Recorder {
+        fn on_node_start(&self, node: &str, step: usize, _input: &dyn fmt::Debug) {
+            self.record(format!("start {} {}", node, step));
+        }
+
+        fn on_node_end(&self, node: &str, _output: &dyn fmt::Debug, _elapsed: Duration) {
+            self.record(format!("end {}", node));
+        }
+
+        fn on_node_error(&self, node: &str, error: &Error) {
+            self.record(format!("error {} {}", node, error));
+        }
+
+        fn on_llm_token(&self, source: &str, token: &str) {
+            self.record(format!("token {} {}", source, token));
+        }
+
+        fn on_retriever_start(&self, query: &str) {
+            self.record(format!("retrieve {}", query));
+        }
+
+        fn on_retriever_end(&self, documents: &[(Document, f32)]) {
+            let contents: Vec<&str> = documents
+                .iter()
+                .map(|(document, _)| document.page_content.as_str())
+                .collect();
+            self.record(format!("retrieved {}", contents.join(",")));
+        }
+
+        fn on_tool_start(&self, tool: &str, input: &str) {
+            self.record(format!("tool {} {}", tool, input));
+        }
+
+        fn on_tool_end(&self, tool: &str, output: &str) {
+            self.record(format!("tool {} -> {}", tool, output));
+        }
+    }
+
+    /// Streams a reply and calls a search tool
+    struct Agent;
+
+    #[async_trait]
+    impl NodeProcessor<String> for Agent {
+        async fn process(&self, state: State<String>) -> Result<State<String>> {
+            Ok(state)
+        }
+
+        async fn process_command(
+            &self,
+            mut state: State<String>,
+            ctx: &NodeContext<String>,
+        ) -> Result<Command<String>> {
+            ctx.emit_token("Hi");
+            ctx.callbacks().on_tool_start("search", "rust");
+            ctx.callbacks().on_tool_end("search", "found");
+            state.data.push_str("Hi");
+            Ok(Command::new(state))
+        }
+    }
+
+    struct Fail;
+
+    #[async_trait]
+    impl NodeProcessor<String> for Fail {
+        async fn process(&self, _state: State<String>) -> Result<State<String>> {
+            Err(Error::LLM("overloaded".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_graph_callbacks() {
+        let graph_recorder = Arc::new(Recorder::default());
+        let run_recorder = Arc::new(Recorder::default());
+        let graph = GraphBuilder::new()
+            .with_callback(graph_recorder.clone())
+            .with_node("agent", Agent)
+            .unwrap()
+            .with_node("review", Fail)
+            .unwrap()
+            .with_start_edge("agent")
+            .unwrap()
+            .with_edge("agent", "review", None)
+            .unwrap()
+            .with_end_edge("review")
+            .unwrap()
+            .build();
+
+        let config = RunConfig::new().with_callback(run_recorder.clone());
+        let result = graph
+            .execute_with_config(State::new(String::new()), &config)
+            .await;
+        assert!(result.is_err());
+
+        let expected = vec![
+            "start agent 1",
+            "token agent Hi",
+            "tool search rust",
+            "tool search -> found",
+            "end agent",
+            "start review 2",
+            "error review LLM error: overloaded",
+        ];
+        assert_eq!(graph_recorder.calls(), expected);
+        assert_eq!(run_recorder.calls(), expected);
+
+        // Handlers registered on a run only see that run
+        graph.execute(State::new(String::new())).await.unwrap_err();
+        assert_eq!(graph_recorder.calls().len(), expected.len() * 2);
+        assert_eq!(run_recorder.calls().len(), expected.len());
+    }
+
+    #[tokio::test]
+    async fn test_retriever_callbacks() {
+        let recorder = Arc::new(Recorder::default());
+        let embeddings = MockEmbeddings::new(2)
+            .with_embedding("rust", vec![1.0, 0.0])
+            .with_embedding("python", vec![0.0, 1.0])
+            .with_embedding("crab", vec![0.9, 0.1]);
+        let mut store = MemoryVectorStore::new(embeddings).with_callback(recorder.clone());
+        store
+            .add_documents(vec![Document::new("rust"), Document::new("python")])
+            .await
+            .unwrap();
+
+        store.search("crab", 1).await.unwrap();
+        assert_eq!(recorder.calls(), vec!["retrieve crab", "retrieved rust"]);
+    }
+}
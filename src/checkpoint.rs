@@ -2,11 +2,10 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::Path;
 use std::sync::{Arc, RwLock};
-use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::fs;
-use uuid::Uuid;
 
 use crate::error::Error;
+use crate::replay;
 use crate::state::{State, StateValue};
 use crate::Result;
 
@@ -19,6 +18,18 @@ pub struct CheckpointMetadata {
     pub created_at: u64,
     /// Name of the node that produced this state
     pub node_name: String,
+    /// Thread of graph runs the checkpoint belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<String>,
+    /// Position of the checkpoint within its thread
+    #[serde(default)]
+    pub step: usize,
+    /// Nodes scheduled to run after this checkpoint; empty once a run has finished
+    #[serde(default)]
+    pub next: Vec<String>,
+    /// ID of the checkpoint this one follows, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
     /// Additional metadata
     #[serde(default)]
     pub metadata: HashMap<String, serde_json::Value>,
@@ -35,19 +46,23 @@ pub struct Checkpoint<S: StateValue> {
 
 impl<S: StateValue> Checkpoint<S> {
     /// Create a new checkpoint
-    pub fn new(node_name: impl Into<String>, state: State<S>) -> Self {
-        Self {
+    ///
+    /// Fails with [`Error::Replay`] when replaying a run whose recording does not
+    /// have the checkpoint's ID or timestamp.
+    pub fn new(node_name: impl Into<String>, state: State<S>) -> Result<Self> {
+        Ok(Self {
             metadata: CheckpointMetadata {
-                id: Uuid::new_v4().to_string(),
-                created_at: SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs(),
+                id: replay::new_id()?,
+                created_at: replay::unix_time()?.as_secs(),
                 node_name: node_name.into(),
+                thread_id: None,
+                step: 0,
+                next: Vec::new(),
+                parent_id: None,
                 metadata: HashMap::new(),
             },
             state,
-        }
+        })
     }
 
     /// Add metadata to the checkpoint
@@ -97,6 +112,17 @@ pub trait CheckpointStore<S: StateValue>: Send + Sync {
         }
         Ok(())
     }
+
+    /// List the checkpoints of a thread, ordered by step
+    fn list_thread(&self, thread_id: &str) -> Result<Vec<CheckpointMetadata>> {
+        let mut checkpoints: Vec<CheckpointMetadata> = self
+            .list()?
+            .into_iter()
+            .filter(|metadata| metadata.thread_id.as_deref() == Some(thread_id))
+            .collect();
+        checkpoints.sort_by_key(|metadata| metadata.step);
+        Ok(checkpoints)
+    }
 }
 
 /// An in-memory checkpoint store
@@ -1,6 +1,9 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::Instrument;
 
+use crate::callbacks::{CallbackHandler, Callbacks, TokenUsage};
 use crate::error::Error;
 use crate::traits::{EmbeddingModel, Runnable};
 use crate::Result;
@@ -32,12 +35,23 @@ struct OpenAIUsage {
     total_tokens: u32,
 }
 
+impl From<OpenAIUsage> for TokenUsage {
+    fn from(usage: OpenAIUsage) -> Self {
+        Self {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: 0,
+            total_tokens: usage.total_tokens,
+        }
+    }
+}
+
 /// OpenAI embeddings model implementation
 pub struct OpenAIEmbeddings {
     api_key: String,
     model: String,
     client: reqwest::Client,
     dimension: usize,
+    callbacks: Callbacks,
 }
 
 impl OpenAIEmbeddings {
@@ -58,13 +72,18 @@ impl OpenAIEmbeddings {
             model: model_name,
             client: reqwest::Client::new(),
             dimension,
+            callbacks: Callbacks::new(),
         }
     }
-}
 
-#[async_trait]
-impl Runnable<String, Vec<f32>> for OpenAIEmbeddings {
-    async fn invoke(&self, input: String) -> Result<Vec<f32>> {
+    /// Register a handler called on every request to the model
+    pub fn with_callback(mut self, handler: Arc<dyn CallbackHandler>) -> Self {
+        self.callbacks.add(handler);
+        self
+    }
+
+    /// Request the embedding of a text, returning it and the tokens it used
+    async fn embed(&self, input: String) -> Result<(Vec<f32>, TokenUsage)> {
         let request = OpenAIEmbeddingRequest {
             model: self.model.clone(),
             input,
@@ -97,7 +116,35 @@ impl Runnable<String, Vec<f32>> for OpenAIEmbeddings {
             return Err(Error::LLM("No embeddings returned".to_string()));
         }
 
-        Ok(response.data[0].embedding.clone())
+        let usage = TokenUsage::from(response.usage);
+        Ok((response.data[0].embedding.clone(), usage))
+    }
+}
+
+#[async_trait]
+impl Runnable<String, Vec<f32>> for OpenAIEmbeddings {
+    async fn invoke(&self, input: String) -> Result<Vec<f32>> {
+        let span = tracing::info_span!(
+            "embedding",
+            model = %self.model,
+            prompt_tokens = tracing::field::Empty,
+            completion_tokens = tracing::field::Empty,
+            total_tokens = tracing::field::Empty
+        );
+        self.callbacks
+            .on_embedding_start(&self.model, std::slice::from_ref(&input));
+        match self.embed(input).instrument(span.clone()).await {
+            Ok((embedding, usage)) => {
+                usage.record(&span);
+                self.callbacks.on_embedding_end(&self.model, Some(&usage));
+                Ok(embedding)
+            }
+            Err(e) => {
+                tracing::warn!(parent: &span, error = %e, "Embedding request failed");
+                self.callbacks.on_embedding_error(&self.model, &e);
+                Err(e)
+            }
+        }
     }
 }
 
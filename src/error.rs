@@ -12,6 +12,18 @@ pub enum Error {
     #[error("Node execution error: {0}")]
     NodeExecution(String),
 
+    /// Error raised by a node, tagged with the path of the node
+    ///
+    /// Graph runs return every error a node raises wrapped in this variant. Use
+    /// [`Error::root_cause`] to match on the error the node returned.
+    #[error("Node {node} failed: {source}")]
+    NodeFailed {
+        /// Path of the failed node, e.g. `research/search` inside a subgraph
+        node: String,
+        /// The error the node returned
+        source: Box<Error>,
+    },
+
     /// Error related to invalid node
     #[error("Invalid node: {0}")]
     InvalidNode(String),
@@ -20,14 +32,43 @@ pub enum Error {
     #[error("Invalid edge: {0}")]
     InvalidEdge(String),
 
+    /// Error listing every structural problem found when validating a graph
+    #[error("Graph validation failed: {}", .0.join("; "))]
+    Validation(Vec<String>),
+
     /// Error with cycle detection
     #[error("Cycle detected: {0}")]
     CycleDetected(String),
 
+    /// Error when a graph run exceeds its maximum number of steps
+    #[error("Recursion limit of {limit} steps exceeded; node trail: {}", trail.join(" -> "))]
+    RecursionLimit {
+        /// The configured step limit
+        limit: usize,
+        /// The nodes executed before the limit was hit, in order
+        trail: Vec<String>,
+    },
+
+    /// A run stopped at an interrupt to wait for human input
+    #[error("Run interrupted at node: {}", .0.node)]
+    Interrupted(Box<crate::graph::Interrupt>),
+
+    /// A node attempt or a whole run took longer than its timeout
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
+    /// A run was cancelled through its cancellation token
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+
     /// Error related to state
     #[error("State error: {0}")]
     State(String),
 
+    /// Error when parallel branches write the same state field without a reducer
+    #[error("State conflict: {0}")]
+    StateConflict(String),
+
     /// Error related to edge conditions
     #[error("Edge condition error: {0}")]
     EdgeCondition(String),
@@ -44,6 +85,10 @@ pub enum Error {
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 
+    /// YAML serialization or deserialization error
+    #[error("YAML error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+
     /// LLM error
     #[error("LLM error: {0}")]
     LLM(String),
@@ -64,7 +109,57 @@ pub enum Error {
     #[error("Pregel error: {0}")]
     Pregel(String),
 
+    /// Error replaying a recorded run, e.g. a call the recording does not have
+    #[error("Replay error: {0}")]
+    Replay(String),
+
+    /// A failure recorded in a cassette, returned again when the run is replayed
+    #[error("Replayed error: {message}")]
+    Replayed {
+        /// Message of the recorded error
+        message: String,
+        /// Whether the recorded error was retryable
+        retryable: bool,
+    },
+
     /// Other general errors
     #[error("Other error: {0}")]
     Other(String),
 }
+
+impl Error {
+    /// Get the error a node originally returned, looking through
+    /// [`Error::NodeFailed`]
+    ///
+    /// Other errors are returned as they are.
+    pub fn root_cause(&self) -> &Error {
+        match self {
+            Error::NodeFailed { source, .. } => source.root_cause(),
+            error => error,
+        }
+    }
+
+    /// Get the path of the node that failed, if the error came from a node
+    pub fn failed_node(&self) -> Option<&str> {
+        match self {
+            Error::NodeFailed { node, .. } => Some(node),
+            _ => None,
+        }
+    }
+
+    /// Tag an error returned by a node with the node's path.
+    ///
+    /// Errors that already identify where they happened are returned unchanged.
+    pub(crate) fn at_node(self, node: &str) -> Self {
+        match self {
+            Error::NodeFailed { .. }
+            | Error::RecursionLimit { .. }
+            | Error::Interrupted(_)
+            | Error::Cancelled(_) => self,
+            source => Error::NodeFailed {
+                node: node.to_string(),
+                source: Box::new(source),
+            },
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use futures::stream::{self, Stream, StreamExt};
+
+use super::{Graph, RunConfig};
+use crate::error::Error;
+use crate::state::{State, StateValue};
+use crate::Result;
+
+impl<S: StateValue> RunConfig<S> {
+    /// Derive the configuration a batch run uses for the input at `index`
+    ///
+    /// Each input runs as its own thread, `<thread_id>/<index>`, so a failed input
+    /// can be resumed with `graph.resume(&config.for_item(index))`. A run ID becomes
+    /// `<run_id>-<index>`, which is also safe as a file name for traces.
+    pub fn for_item(&self, index: usize) -> Self {
+        let mut config = self.clone();
+        config.thread_id = self
+            .thread_id
+            .as_ref()
+            .map(|thread_id| format!("{}/{}", thread_id, index));
+        config.run_id = self
+            .run_id
+            .as_ref()
+            .map(|run_id| format!("{}-{}", run_id, index));
+        config
+    }
+}
+
+impl<S: StateValue> Graph<S> {
+    /// Execute the graph over many initial states, at most `max_concurrency` at a time
+    ///
+    /// Returns the result of every input in input order; a failing input does not
+    /// stop the others. With a `max_concurrency` of 0 every input fails without
+    /// running. Every input runs with [`RunConfig::for_item`], so with a
+    /// checkpointer each input is checkpointed as its own thread. The timeout of
+    /// `config` applies to every input separately; its cancellation token stops them
+    /// all.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use glint::graph::{Graph, RunConfig};
+    /// # use glint::state::State;
+    /// # async fn evaluate(graph: Graph<String>, questions: Vec<String>) {
+    /// let inputs = questions.into_iter().map(State::new).collect();
+    /// let results = graph.batch(inputs, &RunConfig::new(), 8).await;
+    ///
+    /// for (question, result) in results.iter().enumerate() {
+    ///     if let Err(e) = result {
+    ///         eprintln!("question {} failed: {}", question, e);
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub async fn batch(
+        &self,
+        inputs: Vec<State<S>>,
+        config: &RunConfig<S>,
+        max_concurrency: usize,
+    ) -> Vec<Result<State<S>>> {
+        let mut results: Vec<(usize, Result<State<S>>)> = self
+            .batch_stream(inputs, config, max_concurrency)
+            .collect()
+            .await;
+
+        // Return the results in input order, not completion order
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+
+    /// Execute the graph over many initial states, at most `max_concurrency` at a
+    /// time, yielding the index of every input with its result as soon as it finishes
+    ///
+    /// See [`Graph::batch`] for how inputs are configured.
+    pub fn batch_stream<'a>(
+        &'a self,
+        inputs: Vec<State<S>>,
+        config: &RunConfig<S>,
+        max_concurrency: usize,
+    ) -> impl Stream<Item = (usize, Result<State<S>>)> + 'a {
+        let config = config.clone();
+        stream::iter(inputs.into_iter().enumerate())
+            .map(move |(index, state)| {
+                let config = config.for_item(index);
+                async move {
+                    if max_concurrency == 0 {
+                        let error =
+                            Error::Graph("Batch concurrency must be at least 1".to_string());
+                        return (index, Err(error));
+                    }
+                    let result = self.execute_with_config(state, &config).await;
+                    (index, result)
+                }
+            })
+            .buffer_unordered(max_concurrency.max(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::{CheckpointStore, MemoryCheckpointStore};
+    use crate::error::Error;
+    use crate::graph::{GraphBuilder, NodeProcessor};
+    use crate::trace::{RunTree, RunTreeRecorder};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Doubles its input, failing on negative numbers; later inputs finish first
+    struct Double {
+        running: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<i32> for Double {
+        async fn process(&self, mut state: State<i32>) -> Result<State<i32>> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            let delay = 20 - state.data.unsigned_abs().min(5) as u64 * 3;
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+
+            if state.data < 0 {
+                return Err(Error::Other(format!("negative input {}", state.data)));
+            }
+            state.data *= 2;
+            Ok(state)
+        }
+    }
+
+    fn double_graph(peak: &Arc<AtomicUsize>) -> Graph<i32> {
+        let node = Double {
+            running: Arc::new(AtomicUsize::new(0)),
+            peak: peak.clone(),
+        };
+        GraphBuilder::new()
+            .with_node("double", node)
+            .unwrap()
+            .with_start_edge("double")
+            .unwrap()
+            .with_end_edge("double")
+            .unwrap()
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_batch_results_in_input_order() {
+        let peak = Arc::new(AtomicUsize::new(0));
+        let graph = double_graph(&peak);
+        let inputs = [1, 2, -3, 4, 5].into_iter().map(State::new).collect();
+
+        let results = graph.batch(inputs, &RunConfig::new(), 2).await;
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+
+        let outputs: Vec<std::result::Result<i32, String>> = results
+            .into_iter()
+            .map(|result| result.map(|state| state.data).map_err(|e| e.to_string()))
+            .collect();
+        assert_eq!(
+            outputs,
+            vec![
+                Ok(2),
+                Ok(4),
+                Err("Node double failed: Other error: negative input -3".to_string()),
+                Ok(8),
+                Ok(10),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_rejects_zero_concurrency() {
+        let peak = Arc::new(AtomicUsize::new(0));
+        let graph = double_graph(&peak);
+        let inputs = [1, 2].into_iter().map(State::new).collect();
+
+        let results = graph.batch(inputs, &RunConfig::new(), 0).await;
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .all(|result| matches!(result, Err(Error::Graph(_)))));
+        assert_eq!(peak.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_batch_writes_a_trace_per_item() {
+        let directory = std::env::temp_dir().join(format!("glint-traces-{}", uuid::Uuid::new_v4()));
+        let recorder = Arc::new(RunTreeRecorder::new().with_directory(&directory));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let graph = double_graph(&peak);
+        let config = RunConfig::new()
+            .with_run_id("eval")
+            .with_callback(recorder.clone());
+        let inputs = [1, 2].into_iter().map(State::new).collect();
+
+        graph.batch(inputs, &config, 2).await;
+        for index in 0..2 {
+            let json = std::fs::read_to_string(directory.join(format!("eval-{}.json", index)));
+            let tree: RunTree = serde_json::from_str(&json.unwrap()).unwrap();
+            assert_eq!(tree.run_id, format!("eval-{}", index));
+        }
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_batch_checkpoints_per_item() {
+        let peak = Arc::new(AtomicUsize::new(0));
+        let graph = double_graph(&peak);
+        let store = Arc::new(MemoryCheckpointStore::new());
+        let config = RunConfig::new()
+            .with_thread_id("eval")
+            .with_checkpointer(store.clone());
+        let inputs = [1, -2, 3].into_iter().map(State::new).collect();
+
+        let mut finished: Vec<usize> = graph
+            .batch_stream(inputs, &config, 3)
+            .map(|(index, _)| index)
+            .collect()
+            .await;
+        finished.sort();
+        assert_eq!(finished, vec![0, 1, 2]);
+
+        for (index, checkpoints) in [(0, 2), (1, 1), (2, 2)] {
+            let thread = store.list_thread(&format!("eval/{}", index)).unwrap();
+            assert_eq!(thread.len(), checkpoints);
+        }
+
+        // The failed input resumes from its own thread
+        let result = graph.resume(&config.for_item(1)).await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,300 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{Command, Graph, NodeContext, NodeProcessor};
+use crate::cache::NodeCache;
+use crate::error::Error;
+use crate::state::{State, StateValue};
+use crate::Result;
+
+/// Type alias for functions that convert states to JSON
+type EncodeFn<S> = Arc<dyn Fn(&State<S>) -> Result<Value> + Send + Sync>;
+
+/// Type alias for functions that convert JSON back to states
+type DecodeFn<S> = Arc<dyn Fn(Value) -> Result<State<S>> + Send + Sync>;
+
+/// How the results of a node are cached
+///
+/// A result is stored under a hash of the node's path, the policy's version and
+/// the serialized input state, data and metadata alike. The entry keeps all three,
+/// and is only used for an input they match, so inputs whose hashes collide never
+/// share results. Bump the version when the node's behaviour changes so that
+/// older results are no longer used. Only successful results are cached.
+///
+/// # Examples
+///
+/// ```
+/// use glint::cache::FileCache;
+/// use glint::graph::CachePolicy;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// let policy = CachePolicy::<String>::new(Arc::new(FileCache::new(".cache/embed")))
+///     .with_version("text-embedding-3-small")
+///     .with_ttl(Duration::from_secs(7 * 24 * 3600));
+/// ```
+#[derive(Clone)]
+pub struct CachePolicy<S: StateValue> {
+    /// Store the results are kept in
+    cache: Arc<dyn NodeCache>,
+    /// Version of the node's behaviour, part of every key
+    pub version: String,
+    /// How long results are kept, forever if unset
+    pub ttl: Option<Duration>,
+    encode: EncodeFn<S>,
+    decode: DecodeFn<S>,
+}
+
+impl<S: StateValue> fmt::Debug for CachePolicy<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachePolicy")
+            .field("version", &self.version)
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+impl<S: StateValue + Serialize + DeserializeOwned> CachePolicy<S> {
+    /// Create a policy that keeps results in `cache` forever
+    pub fn new(cache: Arc<dyn NodeCache>) -> Self {
+        Self {
+            cache,
+            version: String::new(),
+            ttl: None,
+            encode: Arc::new(|state| Ok(serde_json::to_value(state)?)),
+            decode: Arc::new(|value| Ok(serde_json::from_value(value)?)),
+        }
+    }
+}
+
+impl<S: StateValue> CachePolicy<S> {
+    /// Set the version of the node's behaviour
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Set how long results are kept
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Compute the key of the result of the node at `node` for an input state
+    pub fn key(&self, node: &str, state: &State<S>) -> Result<String> {
+        Ok(self.keyed_input(node, state)?.0)
+    }
+
+    /// Compute the key of an input, along with the input the entry must match
+    fn keyed_input(&self, node: &str, state: &State<S>) -> Result<(String, Value)> {
+        // JSON objects have sorted keys, so equal states serialize the same way
+        let input = (self.encode)(state)?;
+        let serialized = input.to_string();
+
+        // FNV-1a, which unlike the std hasher is stable across releases
+        let hash = [node, "\0", &self.version, "\0", &serialized]
+            .iter()
+            .flat_map(|part| part.bytes())
+            .fold(0xcbf29ce484222325u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            });
+
+        let input = json!({ "node": node, "version": self.version, "state": input });
+        Ok((format!("{:016x}", hash), input))
+    }
+
+    /// Get the cached command stored under `key` for `input`, if any
+    fn lookup(&self, key: &str, input: &Value) -> Result<Option<Command<S>>> {
+        let mut entry = match self.cache.get(key)? {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        // Another input whose key collides with this one
+        if &entry["input"] != input {
+            return Ok(None);
+        }
+
+        let goto: Vec<String> = serde_json::from_value(entry["goto"].take())?;
+        let state = (self.decode)(entry["state"].take())?;
+        Ok(Some(Command::new(state).goto_all(goto)))
+    }
+
+    /// Store the command computed for `input` under `key`
+    fn store(&self, key: &str, input: Value, command: &Command<S>) -> Result<()> {
+        let entry = json!({
+            "input": input,
+            "state": (self.encode)(&command.state)?,
+            "goto": command.goto,
+        });
+        self.cache.set(key, entry, self.ttl)
+    }
+}
+
+impl<S: StateValue> Graph<S> {
+    /// Cache the results of a node according to the given policy
+    ///
+    /// A cached result is returned without running the node, its retries or its
+    /// timeout. Suited to expensive deterministic nodes such as embedding,
+    /// retrieval and classification.
+    pub fn add_cache_policy(
+        &mut self,
+        node: impl Into<String>,
+        policy: CachePolicy<S>,
+    ) -> Result<&mut Self> {
+        let node = node.into();
+        if !self.processors.contains_key(&node) {
+            return Err(Error::InvalidNode(format!("Node not found: {}", node)));
+        }
+
+        self.cache_policies.insert(node, policy);
+        Ok(self)
+    }
+
+    /// Run a node's processor with retries, unless its cache holds a result for the
+    /// input state
+    ///
+    /// Cache failures are logged and do not fail the node; the node then runs as if
+    /// it had no cache.
+    pub(crate) async fn process_cached(
+        &self,
+        node_name: &str,
+        processor: &dyn NodeProcessor<S>,
+        state: State<S>,
+        ctx: &NodeContext<S>,
+    ) -> Result<Command<S>> {
+        let policy = match self.cache_policies.get(node_name) {
+            Some(policy) => policy,
+            None => {
+                return self
+                    .process_with_retry(node_name, processor, state, ctx)
+                    .await
+            }
+        };
+
+        let (key, input) = match policy.keyed_input(ctx.path(), &state) {
+            Ok(keyed_input) => keyed_input,
+            Err(e) => {
+                tracing::warn!(node = ctx.path(), error = %e, "cannot compute cache key");
+                return self
+                    .process_with_retry(node_name, processor, state, ctx)
+                    .await;
+            }
+        };
+
+        match policy.lookup(&key, &input) {
+            Ok(Some(command)) => {
+                tracing::debug!(node = ctx.path(), key, "cache hit");
+                return Ok(command);
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!(node = ctx.path(), error = %e, "cannot read cache"),
+        }
+
+        let command = self
+            .process_with_retry(node_name, processor, state, ctx)
+            .await?;
+        if let Err(e) = policy.store(&key, input, &command) {
+            tracing::warn!(node = ctx.path(), error = %e, "cannot write cache");
+        }
+        Ok(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::MemoryCache;
+    use crate::graph::GraphBuilder;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts its calls and appends the length of the input
+    struct Embed {
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<Vec<i64>> for Embed {
+        async fn process(&self, mut state: State<Vec<i64>>) -> Result<State<Vec<i64>>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            state.data.push(state.data.len() as i64);
+            Ok(state)
+        }
+    }
+
+    fn embed_graph(calls: &Arc<AtomicUsize>, policy: CachePolicy<Vec<i64>>) -> Graph<Vec<i64>> {
+        GraphBuilder::new()
+            .with_node(
+                "embed",
+                Embed {
+                    calls: calls.clone(),
+                },
+            )
+            .unwrap()
+            .with_cache_policy("embed", policy)
+            .unwrap()
+            .with_start_edge("embed")
+            .unwrap()
+            .with_end_edge("embed")
+            .unwrap()
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_cache_policy_skips_cached_inputs() {
+        let cache = Arc::new(MemoryCache::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let graph = embed_graph(&calls, CachePolicy::new(cache.clone()));
+
+        let first = graph.execute(State::new(vec![7])).await.unwrap();
+        let second = graph.execute(State::new(vec![7])).await.unwrap();
+        assert_eq!(first.data, vec![7, 1]);
+        assert_eq!(second.data, vec![7, 1]);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Different data or metadata is a different input
+        graph.execute(State::new(vec![8])).await.unwrap();
+        let tagged = State::new(vec![7]).with_metadata("user", "ada");
+        graph.execute(tagged).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(cache.len(), 3);
+
+        // A new version does not see results of the old one
+        let graph = embed_graph(&calls, CachePolicy::new(cache).with_version("v2"));
+        graph.execute(State::new(vec![7])).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_cache_policy_ttl() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let policy = CachePolicy::new(Arc::new(MemoryCache::new())).with_ttl(Duration::ZERO);
+        let graph = embed_graph(&calls, policy);
+
+        graph.execute(State::new(vec![1])).await.unwrap();
+        graph.execute(State::new(vec![1])).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cache_policy_ignores_colliding_entries() {
+        let cache = Arc::new(MemoryCache::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let policy = CachePolicy::new(cache.clone());
+        let graph = embed_graph(&calls, policy.clone());
+        graph.execute(State::new(vec![7])).await.unwrap();
+
+        // Store the result for [7] under the key of [8], as if their hashes collided
+        let key = |data| policy.key("embed", &State::new(data)).unwrap();
+        let entry = cache.get(&key(vec![7])).unwrap().unwrap();
+        cache.set(&key(vec![8]), entry, None).unwrap();
+
+        let result = graph.execute(State::new(vec![8])).await.unwrap();
+        assert_eq!(result.data, vec![8, 1]);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
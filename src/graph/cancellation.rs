@@ -0,0 +1,177 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// A token that cancels the graph runs it is passed to
+///
+/// Clones share the same cancellation state, so a request handler can keep one clone
+/// and cancel the run it started with another. Cancelled runs stop at once, dropping
+/// the nodes in flight, and fail with [`Error::Cancelled`](crate::Error::Cancelled).
+/// Nodes that need to clean up can watch the token through
+/// [`NodeContext::cancelled`](super::NodeContext::cancelled).
+///
+/// # Examples
+///
+/// ```
+/// use glint::graph::{CancellationToken, RunConfig};
+///
+/// let token = CancellationToken::new();
+/// let config = RunConfig::<String>::new().with_cancellation_token(token.clone());
+///
+/// // Later, when the client disconnects
+/// token.cancel();
+/// assert!(token.is_cancelled());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancellationToken {
+    /// Create a token that is not cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel every run using this token
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    /// Check whether the token was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the token is cancelled
+    pub async fn cancelled(&self) {
+        loop {
+            // Register before checking so a concurrent cancel is not missed
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::graph::{ExecutionStrategy, Graph, GraphBuilder, NodeProcessor, RunConfig};
+    use crate::state::State;
+    use crate::Result;
+    use async_trait::async_trait;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    /// Sleeps before counting how many times it finished
+    struct Slow {
+        delay: Duration,
+        finished: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<i32> for Slow {
+        async fn process(&self, state: State<i32>) -> Result<State<i32>> {
+            tokio::time::sleep(self.delay).await;
+            self.finished.fetch_add(1, Ordering::SeqCst);
+            Ok(state)
+        }
+    }
+
+    /// Two slow branches that run in parallel
+    fn parallel_graph(delay: Duration, finished: &Arc<AtomicUsize>) -> Graph<i32> {
+        let slow = || Slow {
+            delay,
+            finished: finished.clone(),
+        };
+        GraphBuilder::new()
+            .with_execution_strategy(ExecutionStrategy::Parallel)
+            .with_node("a", slow())
+            .unwrap()
+            .with_node("b", slow())
+            .unwrap()
+            .with_start_edge("a")
+            .unwrap()
+            .with_start_edge("b")
+            .unwrap()
+            .with_end_edge("a")
+            .unwrap()
+            .with_end_edge("b")
+            .unwrap()
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_node_timeout() {
+        let finished = Arc::new(AtomicUsize::new(0));
+        let graph = GraphBuilder::new()
+            .with_node(
+                "slow",
+                Slow {
+                    delay: Duration::from_secs(10),
+                    finished: finished.clone(),
+                },
+            )
+            .unwrap()
+            .with_node_timeout("slow", Duration::from_millis(10))
+            .unwrap()
+            .with_start_edge("slow")
+            .unwrap()
+            .with_end_edge("slow")
+            .unwrap()
+            .build();
+
+        match graph.execute(State::new(0)).await.unwrap_err() {
+            Error::NodeFailed { node, source } => {
+                assert_eq!(node, "slow");
+                assert!(matches!(*source, Error::Timeout(_)));
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+        assert_eq!(finished.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_run_timeout() {
+        let finished = Arc::new(AtomicUsize::new(0));
+        let graph = parallel_graph(Duration::from_secs(10), &finished);
+        let config = RunConfig::new().with_timeout(Duration::from_millis(10));
+
+        let result = graph.execute_with_config(State::new(0), &config).await;
+        assert!(matches!(result, Err(Error::Timeout(_))));
+        assert_eq!(finished.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_parallel_branches() {
+        let finished = Arc::new(AtomicUsize::new(0));
+        let graph = parallel_graph(Duration::from_secs(10), &finished);
+        let token = CancellationToken::new();
+        let config = RunConfig::new().with_cancellation_token(token.clone());
+
+        let canceller = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            token.cancel();
+        };
+        let (result, _) =
+            tokio::join!(graph.execute_with_config(State::new(0), &config), canceller);
+
+        assert!(matches!(result, Err(Error::Cancelled(_))));
+        assert_eq!(finished.load(Ordering::SeqCst), 0);
+
+        // A cancelled token stops later runs as well
+        let result = graph.execute_with_config(State::new(0), &config).await;
+        assert!(matches!(result, Err(Error::Cancelled(_))));
+    }
+}
@@ -0,0 +1,185 @@
+use async_trait::async_trait;
+
+use super::fallback::NodeError;
+use super::update::NodeUpdate;
+use super::{NodeContext, NodeProcessor, END};
+use crate::state::{State, StateValue};
+use crate::Result;
+
+/// The result of a node that decides where execution goes next.
+///
+/// A command carries the updated state and, optionally, the nodes to run next.
+/// When `goto` is empty the node's edges are followed as usual.
+#[derive(Debug, Clone)]
+pub struct Command<S: StateValue> {
+    /// The updated state
+    pub state: State<S>,
+    /// Names of the nodes to run next
+    pub goto: Vec<String>,
+    /// The partial update an update node returned, folded in again when parallel
+    /// branches are merged
+    pub(crate) update: Option<NodeUpdate<S>>,
+    /// The handler a failure was routed to, with the error it gets
+    pub(crate) routed_error: Option<(String, NodeError)>,
+}
+
+impl<S: StateValue> Command<S> {
+    /// Create a command that updates the state and follows the node's edges
+    pub fn new(state: State<S>) -> Self {
+        Self {
+            state,
+            goto: Vec::new(),
+            update: None,
+            routed_error: None,
+        }
+    }
+
+    /// Jump to the given node
+    pub fn goto(mut self, node: impl Into<String>) -> Self {
+        self.goto.push(node.into());
+        self
+    }
+
+    /// Jump to several nodes
+    pub fn goto_all(mut self, nodes: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.goto.extend(nodes.into_iter().map(Into::into));
+        self
+    }
+
+    /// Finish the run after this node
+    pub fn end(self) -> Self {
+        self.goto(END)
+    }
+}
+
+impl<S: StateValue> From<State<S>> for Command<S> {
+    fn from(state: State<S>) -> Self {
+        Self::new(state)
+    }
+}
+
+/// Trait for node processors that return a [`Command`] instead of a plain state.
+///
+/// Register them with [`Graph::add_command_node`](super::Graph::add_command_node),
+/// declaring every node the command may jump to.
+///
+/// # Examples
+///
+/// ```
+/// use glint::graph::{Command, CommandProcessor, END};
+/// use glint::state::State;
+/// use glint::Result;
+/// use async_trait::async_trait;
+///
+/// struct Review;
+///
+/// #[async_trait]
+/// impl CommandProcessor<i32> for Review {
+///     async fn process(&self, state: State<i32>) -> Result<Command<i32>> {
+///         if state.data > 10 {
+///             Ok(Command::new(state).end())
+///         } else {
+///             Ok(Command::new(state).goto("revise"))
+///         }
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait CommandProcessor<S: StateValue>: Send + Sync {
+    /// Process the state and return the updated state with routing instructions
+    async fn process(&self, state: State<S>) -> Result<Command<S>>;
+}
+
+/// Adapter that lets a [`CommandProcessor`] be stored as a [`NodeProcessor`]
+pub(crate) struct CommandNode<P>(pub(crate) P);
+
+#[async_trait]
+impl<S, P> NodeProcessor<S> for CommandNode<P>
+where
+    S: StateValue,
+    P: CommandProcessor<S>,
+{
+    async fn process(&self, state: State<S>) -> Result<State<S>> {
+        Ok(self.0.process(state).await?.state)
+    }
+
+    async fn process_command(&self, state: State<S>, _ctx: &NodeContext<S>) -> Result<Command<S>> {
+        self.0.process(state).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::graph::{Graph, GraphBuilder, START};
+
+    struct Review;
+
+    #[async_trait]
+    impl CommandProcessor<i32> for Review {
+        async fn process(&self, state: State<i32>) -> Result<Command<i32>> {
+            if state.data > 10 {
+                Ok(Command::new(state).end())
+            } else {
+                Ok(Command::new(state).goto("revise"))
+            }
+        }
+    }
+
+    struct Revise {
+        amount: i32,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<i32> for Revise {
+        async fn process(&self, mut state: State<i32>) -> Result<State<i32>> {
+            state.data += self.amount;
+            Ok(state)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_command_goto() {
+        // "revise" is declared as a destination before it is added
+        let graph = GraphBuilder::new()
+            .with_command_node("review", Review, ["revise", END])
+            .unwrap()
+            .with_node("revise", Revise { amount: 4 })
+            .unwrap()
+            .with_start_edge("review")
+            .unwrap()
+            .with_edge("revise", "review", None)
+            .unwrap()
+            .build();
+
+        let final_state = graph.execute(State::new(0)).await.unwrap();
+        assert_eq!(final_state.data, 12);
+    }
+
+    #[tokio::test]
+    async fn test_command_undeclared_destination() {
+        let graph = GraphBuilder::new()
+            .with_command_node("review", Review, [END])
+            .unwrap()
+            .with_node("revise", Revise { amount: 4 })
+            .unwrap()
+            .with_start_edge("review")
+            .unwrap()
+            .build();
+
+        let result = graph.execute(State::new(0)).await;
+        assert!(matches!(result.unwrap_err(), Error::InvalidEdge(_)));
+    }
+
+    #[test]
+    fn test_command_node_rejects_start_without_changes() {
+        let mut graph = Graph::new();
+        let result = graph.add_command_node("review", Review, ["revise", START]);
+        assert!(matches!(result, Err(Error::InvalidEdge(_))));
+
+        // The node was not added, so it can be added again
+        assert!(graph.add_command_node("review", Review, [END]).is_ok());
+        assert_eq!(graph.destinations["review"], vec![END]);
+    }
+}
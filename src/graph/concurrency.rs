@@ -0,0 +1,405 @@
+use futures::stream::{FuturesUnordered, StreamExt};
+use petgraph::graph::NodeIndex;
+use std::sync::Arc;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use super::context::RunContext;
+use super::{Command, Graph};
+use crate::error::Error;
+use crate::state::{State, StateValue};
+use crate::Result;
+
+impl<S: StateValue> Graph<S> {
+    /// Set the maximum number of nodes that run at once in a parallel superstep or
+    /// a fan-out
+    ///
+    /// Results are still merged in scheduling order.
+    pub fn set_max_concurrency(&mut self, max_concurrency: usize) -> Result<&mut Self> {
+        if max_concurrency == 0 {
+            return Err(Error::Graph(
+                "Maximum concurrency must be at least 1".to_string(),
+            ));
+        }
+
+        self.max_concurrency = Some(max_concurrency);
+        Ok(self)
+    }
+
+    /// Limit how many nodes tagged with `tag` run at once
+    ///
+    /// The limit is shared by all runs of the graph, so concurrent requests served
+    /// by one graph together stay within a provider's rate limit.
+    pub fn set_concurrency_limit(
+        &mut self,
+        tag: impl Into<String>,
+        limit: usize,
+    ) -> Result<&mut Self> {
+        let tag = tag.into();
+        if limit == 0 {
+            return Err(Error::Graph(format!(
+                "Concurrency limit of tag {} must be at least 1",
+                tag
+            )));
+        }
+
+        self.concurrency_limits
+            .insert(tag, Arc::new(Semaphore::new(limit)));
+        Ok(self)
+    }
+
+    /// Tag a node so that it counts against the concurrency limit of `tag`
+    ///
+    /// The limit may be set before or after the tag; [`Graph::validate`] reports
+    /// tags that never get one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glint::graph::GraphBuilder;
+    /// # use glint::graph::NodeProcessor;
+    /// # use glint::state::State;
+    /// # struct Summarize;
+    /// # #[async_trait::async_trait]
+    /// # impl NodeProcessor<String> for Summarize {
+    /// #     async fn process(&self, state: State<String>) -> glint::Result<State<String>> {
+    /// #         Ok(state)
+    /// #     }
+    /// # }
+    ///
+    /// let builder = GraphBuilder::<String>::new()
+    ///     .with_max_concurrency(16)?
+    ///     .with_concurrency_limit("openai", 4)?
+    ///     .with_node("summarize", Summarize)?
+    ///     .with_concurrency_tag("summarize", "openai")?;
+    /// # Ok::<(), glint::Error>(())
+    /// ```
+    pub fn set_concurrency_tag(
+        &mut self,
+        node: impl Into<String>,
+        tag: impl Into<String>,
+    ) -> Result<&mut Self> {
+        let node = node.into();
+        if !self.processors.contains_key(&node) {
+            return Err(Error::InvalidNode(format!("Node not found: {}", node)));
+        }
+
+        self.concurrency_tags.insert(node, tag.into());
+        Ok(self)
+    }
+
+    /// Run nodes with their input states concurrently, at most `limit` and the
+    /// graph's maximum concurrency at a time
+    ///
+    /// Commands are returned in the order of `nodes`, whatever order the nodes finish
+    /// in. The first error stops the nodes still running.
+    pub(crate) async fn run_nodes(
+        &self,
+        nodes: Vec<(NodeIndex, State<S>)>,
+        run: &RunContext<S>,
+        step: usize,
+        limit: Option<usize>,
+    ) -> Result<Vec<(NodeIndex, Command<S>)>> {
+        let limit = [limit, self.max_concurrency]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(nodes.len())
+            .max(1);
+
+        let start = |(position, (node_idx, state)): (usize, (NodeIndex, State<S>))| async move {
+            let command = self.run_node(node_idx, state, run, step).await?;
+            Ok::<_, Error>((position, node_idx, command))
+        };
+
+        let mut queued = nodes.into_iter().enumerate();
+        let mut running: FuturesUnordered<_> = queued.by_ref().take(limit).map(start).collect();
+        let mut results = Vec::with_capacity(running.len());
+        while let Some(result) = running.next().await {
+            results.push(result?);
+            if let Some(next) = queued.next() {
+                running.push(start(next));
+            }
+        }
+
+        // Return the results in scheduling order, not completion order
+        results.sort_by_key(|(position, _, _)| *position);
+        Ok(results
+            .into_iter()
+            .map(|(_, node_idx, command)| (node_idx, command))
+            .collect())
+    }
+
+    /// Wait for a slot under the concurrency limit of a node's tag, if it has one
+    pub(crate) async fn acquire_slot(
+        &self,
+        node_name: &str,
+    ) -> Result<Option<SemaphorePermit<'_>>> {
+        let semaphore = match self
+            .concurrency_tags
+            .get(node_name)
+            .and_then(|tag| self.concurrency_limits.get(tag))
+        {
+            Some(semaphore) => semaphore,
+            None => return Ok(None),
+        };
+
+        semaphore
+            .acquire()
+            .await
+            .map(Some)
+            .map_err(|e| Error::Graph(format!("Concurrency limit closed: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{
+        Branch, ExecutionStrategy, FanOutFn, GraphBuilder, NodeProcessor, RetryPolicy, END, START,
+    };
+    use crate::state::{LastValueReducer, State};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Appends its name to the state, tracking how many tracked nodes run at once
+    struct Tracked {
+        name: &'static str,
+        running: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<Vec<String>> for Tracked {
+        async fn process(&self, mut state: State<Vec<String>>) -> Result<State<Vec<String>>> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            // Later branches finish first
+            let delay = 20 - state.data.len() as u64 * 4;
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+
+            state.data.push(self.name.to_string());
+            Ok(state)
+        }
+    }
+
+    struct Noop;
+
+    #[async_trait]
+    impl NodeProcessor<Vec<String>> for Noop {
+        async fn process(&self, state: State<Vec<String>>) -> Result<State<Vec<String>>> {
+            Ok(state)
+        }
+    }
+
+    /// Fans out into four branches, each starting with a different number of items
+    fn fan_out_graph(
+        builder: GraphBuilder<Vec<String>>,
+        peak: &Arc<AtomicUsize>,
+    ) -> Graph<Vec<String>> {
+        let tracked = Tracked {
+            name: "call",
+            running: Arc::new(AtomicUsize::new(0)),
+            peak: peak.clone(),
+        };
+        let fan_out: FanOutFn<Vec<String>> = Arc::new(|_state: &State<Vec<String>>| {
+            Ok((0..4)
+                .map(|i| Branch::new("call", State::new(vec![i.to_string(); i])))
+                .collect())
+        });
+
+        builder
+            .with_node("plan", Noop)
+            .unwrap()
+            .with_node("call", tracked)
+            .unwrap()
+            .with_start_edge("plan")
+            .unwrap()
+            .with_fan_out("plan", fan_out, ["call"], None)
+            .unwrap()
+            .with_end_edge("call")
+            .unwrap()
+            .with_reducer(
+                |_base: &State<Vec<String>>, branches: Vec<State<Vec<String>>>| {
+                    Ok(State::new(
+                        branches
+                            .into_iter()
+                            .flat_map(|branch| branch.data)
+                            .collect(),
+                    ))
+                },
+            )
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrency_keeps_merge_order() {
+        let peak = Arc::new(AtomicUsize::new(0));
+        let graph = fan_out_graph(GraphBuilder::new().with_max_concurrency(2).unwrap(), &peak);
+
+        let final_state = graph.execute(State::new(Vec::new())).await.unwrap();
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            final_state.data,
+            vec!["call", "1", "call", "2", "2", "call", "3", "3", "3", "call"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_tag_limit() {
+        let peak = Arc::new(AtomicUsize::new(0));
+        let running = Arc::new(AtomicUsize::new(0));
+        let tracked = |name| Tracked {
+            name,
+            running: running.clone(),
+            peak: peak.clone(),
+        };
+
+        let graph = GraphBuilder::new()
+            .with_execution_strategy(ExecutionStrategy::Parallel)
+            .with_reducer(LastValueReducer)
+            .with_concurrency_limit("openai", 1)
+            .unwrap()
+            .with_node("a", tracked("a"))
+            .unwrap()
+            .with_node("b", tracked("b"))
+            .unwrap()
+            .with_node("c", tracked("c"))
+            .unwrap()
+            .with_concurrency_tag("a", "openai")
+            .unwrap()
+            .with_concurrency_tag("b", "openai")
+            .unwrap()
+            .with_concurrency_tag("c", "openai")
+            .unwrap()
+            .with_start_edge("a")
+            .unwrap()
+            .with_start_edge("b")
+            .unwrap()
+            .with_start_edge("c")
+            .unwrap()
+            .with_end_edge("a")
+            .unwrap()
+            .with_end_edge("b")
+            .unwrap()
+            .with_end_edge("c")
+            .unwrap()
+            .build();
+
+        graph.execute(State::new(Vec::new())).await.unwrap();
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+    }
+
+    /// Logs its attempts, failing with a rate limit error on its first attempt if
+    /// it is flaky
+    struct Logged {
+        name: &'static str,
+        flaky: bool,
+        log: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<Vec<String>> for Logged {
+        async fn process(&self, state: State<Vec<String>>) -> Result<State<Vec<String>>> {
+            let mut log = self.log.lock().unwrap();
+            let attempts = log
+                .iter()
+                .filter(|entry| entry.starts_with(self.name))
+                .count();
+            if self.flaky && attempts == 0 {
+                log.push(format!("{} failed", self.name));
+                return Err(Error::LLM("rate limit exceeded".to_string()));
+            }
+            log.push(format!("{} done", self.name));
+            Ok(state)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_backoff_releases_concurrency_slot() {
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let logged = |name, flaky| Logged {
+            name,
+            flaky,
+            log: log.clone(),
+        };
+        let policy = RetryPolicy::new(2)
+            .with_initial_interval(Duration::from_millis(100))
+            .with_jitter(false);
+
+        let graph = GraphBuilder::new()
+            .with_execution_strategy(ExecutionStrategy::Parallel)
+            .with_reducer(LastValueReducer)
+            .with_concurrency_limit("openai", 1)
+            .unwrap()
+            .with_node("flaky", logged("flaky", true))
+            .unwrap()
+            .with_node("steady", logged("steady", false))
+            .unwrap()
+            .with_concurrency_tag("flaky", "openai")
+            .unwrap()
+            .with_concurrency_tag("steady", "openai")
+            .unwrap()
+            .with_retry_policy("flaky", policy)
+            .unwrap()
+            .with_start_edge("steady")
+            .unwrap()
+            .with_start_edge("flaky")
+            .unwrap()
+            .with_end_edge("flaky")
+            .unwrap()
+            .with_end_edge("steady")
+            .unwrap()
+            .build();
+
+        graph.execute(State::new(Vec::new())).await.unwrap();
+        // The steady node runs while the flaky one waits to retry
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["flaky failed", "steady done", "flaky done"]
+        );
+    }
+
+    #[test]
+    fn test_zero_concurrency_limits_are_rejected() {
+        let mut graph = Graph::<Vec<String>>::new();
+        graph.add_node("plan", Noop).unwrap();
+
+        assert!(matches!(graph.set_max_concurrency(0), Err(Error::Graph(_))));
+        assert!(matches!(
+            graph.set_concurrency_limit("openai", 0),
+            Err(Error::Graph(_))
+        ));
+        let fan_out: FanOutFn<Vec<String>> = Arc::new(|_state: &State<Vec<String>>| Ok(vec![]));
+        assert!(matches!(
+            graph.add_fan_out("plan", fan_out, ["plan"], Some(0)),
+            Err(Error::InvalidEdge(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_reports_tags_without_limit() {
+        let mut graph = Graph::<Vec<String>>::new();
+        graph
+            .add_node("a", Noop)
+            .unwrap()
+            .add_edge(START, "a", None)
+            .unwrap()
+            .add_edge("a", END, None)
+            .unwrap()
+            .set_concurrency_tag("a", "openai")
+            .unwrap();
+
+        match graph.validate() {
+            Err(Error::Validation(problems)) => assert_eq!(
+                problems,
+                vec!["Concurrency tag of node a has no limit: openai"]
+            ),
+            _ => panic!("expected validation error"),
+        }
+
+        graph.set_concurrency_limit("openai", 2).unwrap();
+        assert!(graph.validate().is_ok());
+    }
+}
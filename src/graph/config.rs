@@ -0,0 +1,132 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::context::{Checkpointing, RunContext};
+use super::CancellationToken;
+use crate::callbacks::{CallbackHandler, Callbacks};
+use crate::checkpoint::CheckpointStore;
+use crate::error::Error;
+use crate::state::StateValue;
+use crate::Result;
+
+/// Configuration for a single graph run
+///
+/// # Examples
+///
+/// ```
+/// use glint::checkpoint::MemoryCheckpointStore;
+/// use glint::graph::RunConfig;
+/// use std::sync::Arc;
+///
+/// let config = RunConfig::<String>::new()
+///     .with_thread_id("conversation-42")
+///     .with_checkpointer(Arc::new(MemoryCheckpointStore::new()));
+/// ```
+#[derive(Clone)]
+pub struct RunConfig<S: StateValue> {
+    /// Identifier of the thread of runs, e.g. a conversation, the run belongs to
+    pub thread_id: Option<String>,
+    /// Identifier of the run in traces; a random one is generated if unset
+    pub run_id: Option<String>,
+    /// Store that receives a checkpoint after every node
+    pub checkpointer: Option<Arc<dyn CheckpointStore<S>>>,
+    /// Longest time the run may take
+    pub timeout: Option<Duration>,
+    /// Token that cancels the run
+    pub cancellation_token: Option<CancellationToken>,
+    /// Handlers called as the nodes of the run execute
+    pub callbacks: Callbacks,
+}
+
+impl<S: StateValue> fmt::Debug for RunConfig<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RunConfig")
+            .field("thread_id", &self.thread_id)
+            .field("run_id", &self.run_id)
+            .field("checkpointer", &self.checkpointer.is_some())
+            .field("timeout", &self.timeout)
+            .field("cancellation_token", &self.cancellation_token)
+            .field("callbacks", &self.callbacks)
+            .finish()
+    }
+}
+
+impl<S: StateValue> Default for RunConfig<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: StateValue> RunConfig<S> {
+    /// Create a configuration without checkpointing
+    pub fn new() -> Self {
+        Self {
+            thread_id: None,
+            run_id: None,
+            checkpointer: None,
+            timeout: None,
+            cancellation_token: None,
+            callbacks: Callbacks::new(),
+        }
+    }
+
+    /// Set the thread the run belongs to
+    pub fn with_thread_id(mut self, thread_id: impl Into<String>) -> Self {
+        self.thread_id = Some(thread_id.into());
+        self
+    }
+
+    /// Set the ID the run is reported with to tracing spans and callback handlers
+    pub fn with_run_id(mut self, run_id: impl Into<String>) -> Self {
+        self.run_id = Some(run_id.into());
+        self
+    }
+
+    /// Set the store that receives a checkpoint after every node
+    pub fn with_checkpointer(mut self, checkpointer: Arc<dyn CheckpointStore<S>>) -> Self {
+        self.checkpointer = Some(checkpointer);
+        self
+    }
+
+    /// Fail the run with [`Error::Timeout`] once it has taken longer than `timeout`
+    ///
+    /// A resumed run gets the full timeout again.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Cancel the run when the given token is cancelled
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
+    /// Register a handler called as the nodes of this run execute
+    ///
+    /// Unlike handlers registered on the graph, it sees only this run, e.g. to
+    /// stream the progress of one request to its client.
+    pub fn with_callback(mut self, handler: Arc<dyn CallbackHandler>) -> Self {
+        self.callbacks.add(handler);
+        self
+    }
+
+    /// Create the context of a top-level run with this configuration
+    pub(crate) fn run_context(&self) -> Result<RunContext<S>> {
+        Ok(RunContext::root(self.checkpointing()?).with_config(self))
+    }
+
+    /// Set up checkpointing for a run with this configuration
+    pub(crate) fn checkpointing(&self) -> Result<Option<Checkpointing<S>>> {
+        match (&self.checkpointer, &self.thread_id) {
+            (Some(store), Some(thread_id)) => {
+                Ok(Some(Checkpointing::new(store.clone(), thread_id.clone())?))
+            }
+            (Some(_), None) => Err(Error::Checkpoint(
+                "A thread_id is required to save checkpoints".to_string(),
+            )),
+            (None, _) => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,425 @@
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::interrupt::{Interrupt, InterruptKind, Release, INTERRUPT_KEY};
+use super::stream::{EventSink, GraphEvent};
+use super::{CancellationToken, RunConfig};
+use crate::callbacks::{CallbackHandler, Callbacks};
+use crate::checkpoint::{Checkpoint, CheckpointStore};
+use crate::error::Error;
+use crate::state::{State, StateValue};
+use crate::Result;
+
+/// Separator between the names of nested nodes in a node path
+pub const PATH_SEPARATOR: &str = "/";
+
+/// Information about the node execution a processor is running in.
+///
+/// Passed to [`NodeProcessor::process_command`](super::NodeProcessor::process_command).
+#[derive(Debug, Clone)]
+pub struct NodeContext<S: StateValue> {
+    /// Path of the node, including the names of enclosing subgraph nodes
+    path: String,
+    /// Step of the enclosing run at which the node executes
+    step: usize,
+    /// Checkpointing of the enclosing run, shared with nested graphs
+    checkpointing: Option<Arc<Checkpointing<S>>>,
+    /// The interrupt the enclosing run was resumed from, if any
+    release: Option<Arc<Release>>,
+    /// Where the enclosing run streams its events, if anywhere
+    events: Option<EventSink<S>>,
+    /// Token that cancels the enclosing run
+    cancellation_token: CancellationToken,
+    /// Handlers of the enclosing run and graph
+    callbacks: Callbacks,
+    /// ID of the top-level run
+    run_id: String,
+}
+
+impl<S: StateValue> NodeContext<S> {
+    /// Create a context for a node running outside of any graph
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            step: 0,
+            checkpointing: None,
+            release: None,
+            events: None,
+            cancellation_token: CancellationToken::new(),
+            callbacks: Callbacks::new(),
+            run_id: Uuid::new_v4().to_string(),
+        }
+    }
+
+    /// Get the path of the node, e.g. `research/search` for a node inside a subgraph
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Get the step of the enclosing run at which the node executes
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    /// Get the ID of the top-level run the node executes in
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Get the thread the enclosing run belongs to, if it saves checkpoints
+    pub fn thread_id(&self) -> Option<&str> {
+        self.checkpointing
+            .as_ref()
+            .map(|checkpointing| checkpointing.thread_id.as_str())
+    }
+
+    /// Check whether the enclosing run was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation_token.is_cancelled()
+    }
+
+    /// Wait until the enclosing run is cancelled
+    ///
+    /// A cancelled run drops the nodes in flight at their next await point; nodes
+    /// that must clean up first, such as ones that stream from a model, can select
+    /// on this instead.
+    pub async fn cancelled(&self) {
+        self.cancellation_token.cancelled().await
+    }
+
+    /// Get the callback handlers of the enclosing run and graph
+    ///
+    /// Nodes report the tools they call through these.
+    pub fn callbacks(&self) -> &Callbacks {
+        &self.callbacks
+    }
+
+    /// Create the equivalent context for a subgraph with a different state type
+    ///
+    /// Checkpoints and events are typed by state, so runs of the subgraph do not
+    /// save or stream any.
+    pub fn for_state<T: StateValue>(&self) -> NodeContext<T> {
+        NodeContext {
+            path: self.path.clone(),
+            step: self.step,
+            checkpointing: None,
+            release: self.release.clone(),
+            events: None,
+            cancellation_token: self.cancellation_token.clone(),
+            callbacks: self.callbacks.clone(),
+            run_id: self.run_id.clone(),
+        }
+    }
+
+    /// Send an event to the stream of the enclosing run, if it has one
+    pub(crate) fn emit(&self, event: impl FnOnce() -> GraphEvent<S>) {
+        if let Some(events) = &self.events {
+            let _ = events.unbounded_send(event());
+        }
+    }
+
+    /// Report a chunk of LLM output to the handlers of the enclosing run
+    pub(crate) fn report_token(&self, chunk: &str) {
+        self.callbacks.on_llm_token(&self.path, chunk);
+    }
+
+    /// Pass an interrupt of this node if the run was resumed from it
+    pub(crate) fn release(&self, kind: InterruptKind) -> Option<Value> {
+        self.release
+            .as_ref()
+            .and_then(|release| release.take(&self.path, kind))
+    }
+}
+
+/// Saves the checkpoints of a run to a store under a thread
+pub(crate) struct Checkpointing<S: StateValue> {
+    store: Arc<dyn CheckpointStore<S>>,
+    thread_id: String,
+    /// Step to give the next checkpoint
+    next_step: AtomicUsize,
+    /// ID of the checkpoint the next one follows
+    parent_id: Mutex<Option<String>>,
+}
+
+impl<S: StateValue> fmt::Debug for Checkpointing<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Checkpointing")
+            .field("thread_id", &self.thread_id)
+            .field("next_step", &self.next_step)
+            .field("parent_id", &self.parent_id)
+            .finish()
+    }
+}
+
+impl<S: StateValue> Checkpointing<S> {
+    /// Start checkpointing a thread, continuing after its latest checkpoint
+    pub(crate) fn new(store: Arc<dyn CheckpointStore<S>>, thread_id: String) -> Result<Self> {
+        let latest = store.list_thread(&thread_id)?.pop();
+
+        Ok(Self {
+            store,
+            thread_id,
+            next_step: AtomicUsize::new(latest.as_ref().map_or(0, |metadata| metadata.step + 1)),
+            parent_id: Mutex::new(latest.map(|metadata| metadata.id)),
+        })
+    }
+
+    /// Make the next checkpoint follow the given checkpoint
+    pub(crate) fn set_parent(&self, parent_id: impl Into<String>) {
+        *self.parent_id.lock().unwrap() = Some(parent_id.into());
+    }
+
+    /// Get the store checkpoints are saved to
+    pub(crate) fn store(&self) -> &dyn CheckpointStore<S> {
+        self.store.as_ref()
+    }
+
+    /// Get the thread checkpoints are saved under
+    pub(crate) fn thread_id(&self) -> &str {
+        &self.thread_id
+    }
+
+    /// Get the ID of the checkpoint saved last
+    pub(crate) fn latest_id(&self) -> Option<String> {
+        self.parent_id.lock().unwrap().clone()
+    }
+
+    /// Save the state produced by a node and the nodes scheduled to run next,
+    /// recording the interrupt that stopped the run if any
+    pub(crate) fn save(
+        &self,
+        node_path: String,
+        state: &State<S>,
+        next: Vec<String>,
+        interrupt: Option<&Interrupt>,
+    ) -> Result<String> {
+        let mut checkpoint = Checkpoint::new(node_path, state.clone())?;
+        checkpoint.metadata.thread_id = Some(self.thread_id.clone());
+        checkpoint.metadata.step = self.next_step.fetch_add(1, Ordering::SeqCst);
+        checkpoint.metadata.next = next;
+        if let Some(interrupt) = interrupt {
+            checkpoint
+                .metadata
+                .metadata
+                .insert(INTERRUPT_KEY.to_string(), serde_json::to_value(interrupt)?);
+        }
+
+        let mut parent_id = self.parent_id.lock().unwrap();
+        checkpoint.metadata.parent_id = parent_id.replace(checkpoint.metadata.id.clone());
+        self.store.save(checkpoint)
+    }
+}
+
+/// Settings shared by every node of a single graph run
+pub(crate) struct RunContext<S: StateValue> {
+    /// Path of the subgraph node this run executes for, if any
+    prefix: Option<String>,
+    /// Where the run saves its checkpoints, if anywhere
+    checkpointing: Option<Arc<Checkpointing<S>>>,
+    /// The interrupt the run was resumed from, if any
+    release: Option<Arc<Release>>,
+    /// Where the run streams its events, if anywhere
+    events: Option<EventSink<S>>,
+    /// Token that cancels the run
+    cancellation_token: CancellationToken,
+    /// When the run times out, if it has a timeout
+    deadline: Option<(Instant, Duration)>,
+    /// Handlers of the run and of the graphs enclosing it
+    callbacks: Callbacks,
+    /// ID of the top-level run
+    run_id: String,
+}
+
+impl<S: StateValue> RunContext<S> {
+    /// Create the context for a top-level run
+    pub(crate) fn root(checkpointing: Option<Checkpointing<S>>) -> Self {
+        Self {
+            prefix: None,
+            checkpointing: checkpointing.map(Arc::new),
+            release: None,
+            events: None,
+            cancellation_token: CancellationToken::new(),
+            deadline: None,
+            callbacks: Callbacks::new(),
+            run_id: Uuid::new_v4().to_string(),
+        }
+    }
+
+    /// Apply the run ID, timeout, cancellation token and callbacks of a run
+    /// configuration
+    pub(crate) fn with_config(mut self, config: &RunConfig<S>) -> Self {
+        if let Some(run_id) = &config.run_id {
+            self.run_id = run_id.clone();
+        }
+        self.callbacks = config.callbacks.clone();
+        if let Some(token) = &config.cancellation_token {
+            self.cancellation_token = token.clone();
+        }
+        self.deadline = config
+            .timeout
+            .map(|timeout| (Instant::now() + timeout, timeout));
+        self
+    }
+
+    /// Stream the events of the run to the given sink
+    pub(crate) fn with_events(mut self, events: EventSink<S>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Let the run pass the interrupt it was resumed from
+    pub(crate) fn with_release(mut self, release: Release) -> Self {
+        self.release = Some(Arc::new(release));
+        self
+    }
+
+    /// Create the context for a run nested inside the node described by `parent`
+    pub(crate) fn nested(parent: &NodeContext<S>) -> Self {
+        Self {
+            prefix: Some(parent.path.clone()),
+            checkpointing: parent.checkpointing.clone(),
+            release: parent.release.clone(),
+            events: parent.events.clone(),
+            cancellation_token: parent.cancellation_token.clone(),
+            // The top-level run enforces the deadline of the whole run
+            deadline: None,
+            callbacks: parent.callbacks.clone(),
+            run_id: parent.run_id.clone(),
+        }
+    }
+
+    /// Run the execution of this run, stopping it when the run times out or is
+    /// cancelled
+    ///
+    /// Only top-level runs are stopped here; stopping them drops every node in
+    /// flight, including nested runs and parallel branches.
+    pub(crate) async fn limit<T>(&self, execution: impl Future<Output = Result<T>>) -> Result<T> {
+        if self.is_nested() {
+            return execution.await;
+        }
+
+        let timeout = async {
+            match self.deadline {
+                Some((deadline, _)) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        // Check for cancellation first so a cancelled run never starts another node
+        tokio::select! {
+            biased;
+            _ = self.cancellation_token.cancelled() => {
+                Err(Error::Cancelled("Run was cancelled".to_string()))
+            }
+            _ = timeout => Err(Error::Timeout(format!(
+                "Run exceeded its timeout of {:?}",
+                self.deadline.map(|(_, timeout)| timeout).unwrap_or_default()
+            ))),
+            result = execution => result,
+        }
+    }
+
+    /// Check whether this run executes inside a node of another run
+    pub(crate) fn is_nested(&self) -> bool {
+        self.prefix.is_some()
+    }
+
+    /// Get the ID of the top-level run
+    pub(crate) fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Get the handlers of the run and of the graphs enclosing it
+    pub(crate) fn callbacks(&self) -> &Callbacks {
+        &self.callbacks
+    }
+
+    /// Get the checkpointing of the run, if it saves checkpoints
+    pub(crate) fn checkpointing(&self) -> Option<&Checkpointing<S>> {
+        self.checkpointing.as_deref()
+    }
+
+    /// Check whether interrupts raised in this run are saved so it can be resumed
+    ///
+    /// Only top-level runs save them; nested runs restart with their node.
+    pub(crate) fn saves_interrupts(&self) -> bool {
+        self.prefix.is_none() && self.checkpointing.is_some()
+    }
+
+    /// Pass an interrupt at the given node if the run was resumed from it
+    pub(crate) fn release(&self, node_path: &str, kind: InterruptKind) -> Option<Value> {
+        self.release
+            .as_ref()
+            .and_then(|release| release.take(node_path, kind))
+    }
+
+    /// Get the full path of a node of this run
+    pub(crate) fn node_path(&self, name: &str) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}{}{}", prefix, PATH_SEPARATOR, name),
+            None => name.to_string(),
+        }
+    }
+
+    /// Create the context passed to a node of this run, adding the handlers of the
+    /// node's graph to those of the run
+    pub(crate) fn node_context(
+        &self,
+        name: &str,
+        step: usize,
+        callbacks: &Callbacks,
+    ) -> NodeContext<S> {
+        NodeContext {
+            path: self.node_path(name),
+            step,
+            checkpointing: self.checkpointing.clone(),
+            release: self.release.clone(),
+            events: self.events.clone(),
+            cancellation_token: self.cancellation_token.clone(),
+            callbacks: self.callbacks.merged(callbacks),
+            run_id: self.run_id.clone(),
+        }
+    }
+
+    /// Send an event to the stream of the run, if it has one
+    pub(crate) fn emit(&self, event: impl FnOnce() -> GraphEvent<S>) {
+        if let Some(events) = &self.events {
+            let _ = events.unbounded_send(event());
+        }
+    }
+
+    /// Save a checkpoint if the run has a checkpoint store
+    ///
+    /// `nodes` are the nodes of this run that produced the state and `next` the nodes
+    /// scheduled after them; both are saved as full paths. `interrupt` is the
+    /// interrupt that stops the run at this checkpoint, if any.
+    pub(crate) fn checkpoint(
+        &self,
+        nodes: &[&str],
+        state: &State<S>,
+        next: &[&str],
+        interrupt: Option<&Interrupt>,
+    ) -> Result<()> {
+        if let Some(checkpointing) = &self.checkpointing {
+            let node_name = nodes
+                .iter()
+                .map(|name| self.node_path(name))
+                .collect::<Vec<_>>()
+                .join(",");
+            let next = next.iter().map(|name| self.node_path(name)).collect();
+            let checkpoint_id = checkpointing.save(node_name.clone(), state, next, interrupt)?;
+            self.emit(|| GraphEvent::CheckpointSaved {
+                checkpoint_id,
+                node: node_name,
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use super::{EdgeKind, Graph, END, START};
+use crate::serialization::{SerializableEdge, SerializableGraph};
+use crate::state::StateValue;
+
+impl<S: StateValue> Graph<S> {
+    /// Export a serializable representation of the graph
+    ///
+    /// Nodes are listed in the order they were added. Edges include those from
+    /// [`START`] and to [`END`], and carry the labels given with
+    /// [`Graph::add_labelled_edge`]. Edges to the possible targets of routers and
+    /// commands, and error edges, count as conditional.
+    ///
+    /// Nodes and conditional edges loaded by a [`NodeRegistry`](super::NodeRegistry)
+    /// keep their types, configs and condition names, so graphs loaded from a
+    /// definition can be loaded again from their export. Routers, commands and conditions
+    /// added in code have no name, and a registry rejects their edges.
+    pub fn export_serializable(&self) -> SerializableGraph {
+        let nodes: Vec<String> = self
+            .graph
+            .node_indices()
+            .map(|node_idx| self.graph[node_idx].clone())
+            .filter(|name| name != START && name != END)
+            .collect();
+        let node_definitions = nodes
+            .iter()
+            .filter_map(|name| self.node_definitions.get(name).cloned())
+            .collect();
+
+        let edges = self
+            .graph
+            .edge_indices()
+            .map(|edge_idx| {
+                let (from_idx, to_idx) = self.graph.edge_endpoints(edge_idx).unwrap();
+                let kind = &self.graph[edge_idx];
+                SerializableEdge {
+                    from: self.graph[from_idx].clone(),
+                    to: self.graph[to_idx].clone(),
+                    has_condition: !matches!(kind, EdgeKind::Direct),
+                    condition: self.edge_conditions.get(&edge_idx).cloned(),
+                    condition_description: self.edge_labels.get(&edge_idx).cloned(),
+                    on_error: matches!(kind, EdgeKind::Error),
+                }
+            })
+            .collect();
+
+        SerializableGraph {
+            nodes,
+            node_definitions,
+            edges,
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// Render the graph in the DOT language of Graphviz
+    pub fn to_dot(&self) -> String {
+        self.export_serializable().to_dot()
+    }
+
+    /// Render the graph as a Mermaid flowchart
+    pub fn to_mermaid(&self) -> String {
+        self.export_serializable().to_mermaid()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{GraphBuilder, NodeProcessor};
+    use crate::state::State;
+    use crate::Result;
+    use async_trait::async_trait;
+    use std::sync::Arc;
+
+    struct Noop;
+
+    #[async_trait]
+    impl NodeProcessor<i32> for Noop {
+        async fn process(&self, state: State<i32>) -> Result<State<i32>> {
+            Ok(state)
+        }
+    }
+
+    fn review_graph() -> Graph<i32> {
+        GraphBuilder::new()
+            .with_node("draft", Noop)
+            .unwrap()
+            .with_node("review \"final\"", Noop)
+            .unwrap()
+            .with_node("fallback", Noop)
+            .unwrap()
+            .with_start_edge("draft")
+            .unwrap()
+            .with_labelled_edge(
+                "draft",
+                "review \"final\"",
+                Some(Arc::new(|state: &State<i32>| Ok(state.data > 0))),
+                "has content",
+            )
+            .unwrap()
+            .with_edge("draft", END, None)
+            .unwrap()
+            .with_error_edge("draft", "fallback")
+            .unwrap()
+            .with_end_edge("review \"final\"")
+            .unwrap()
+            .with_end_edge("fallback")
+            .unwrap()
+            .build()
+    }
+
+    #[test]
+    fn test_export_serializable() {
+        let exported = review_graph().export_serializable();
+
+        let nodes: Vec<&str> = exported.nodes.iter().map(String::as_str).collect();
+        assert_eq!(nodes, vec!["draft", "review \"final\"", "fallback"]);
+        assert!(exported.node_definitions.is_empty());
+
+        // Nodes without a type are written by name alone
+        let json: serde_json::Value = serde_json::from_str(&exported.to_json().unwrap()).unwrap();
+        assert_eq!(
+            json["nodes"],
+            serde_json::json!(["draft", "review \"final\"", "fallback"])
+        );
+
+        let edges: Vec<(&str, &str, bool, Option<&str>)> = exported
+            .edges
+            .iter()
+            .map(|edge| {
+                (
+                    edge.from.as_str(),
+                    edge.to.as_str(),
+                    edge.has_condition,
+                    edge.label(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                (START, "draft", false, None),
+                ("draft", "review \"final\"", true, Some("has content")),
+                ("draft", END, false, None),
+                ("draft", "fallback", true, Some("error")),
+                ("review \"final\"", END, false, None),
+                ("fallback", END, false, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_mermaid() {
+        let expected = "\
+flowchart TD
+    __start__([\"start\"])
+    __end__([\"end\"])
+    n0[\"draft\"]
+    n1[\"review #quot;final#quot;\"]
+    n2[\"fallback\"]
+    __start__ --> n0
+    n0 -.->|\"has content\"| n1
+    n0 --> __end__
+    n0 -.->|\"error\"| n2
+    n1 --> __end__
+    n2 --> __end__
+";
+        assert_eq!(review_graph().to_mermaid(), expected);
+    }
+
+    #[test]
+    fn test_to_dot_escapes_names() {
+        let dot = review_graph().to_dot();
+        assert!(dot.contains("    \"review \\\"final\\\"\";\n"));
+        assert!(dot.contains(
+            "    \"draft\" -> \"review \\\"final\\\"\" [label=\"has content\", style=dashed];\n"
+        ));
+        assert!(dot.contains("    \"__start__\" -> \"draft\";\n"));
+    }
+}
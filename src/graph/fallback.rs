@@ -0,0 +1,428 @@
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::{is_retryable, Command, EdgeKind, Graph, NodeContext, END, START};
+use crate::error::Error;
+use crate::state::{State, StateValue};
+use crate::Result;
+
+/// Key of the state metadata entry holding the [`NodeError`] a handler node was
+/// routed to with
+///
+/// The entry is only in the handler's input. Other nodes, including those
+/// running alongside the handler, do not see it, and it is removed from the
+/// handler's output, so it does not reach later nodes or the final state.
+pub const ERROR_KEY: &str = "__glint_error__";
+
+/// Key of the state metadata entry holding the errors routed to handlers that
+/// have not run yet, so that checkpoints taken in between keep them
+const ROUTED_ERRORS_KEY: &str = "__glint_routed_errors__";
+
+/// Errors routed to handlers that have not run yet, by handler name
+pub(crate) type RoutedErrors = BTreeMap<String, NodeError>;
+
+/// The failure of a node, as seen by the node its error was routed to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeError {
+    /// Path of the node that failed, e.g. `research/search` inside a subgraph
+    pub node: String,
+    /// The message of the error the node returned
+    pub message: String,
+    /// Whether the error is retryable according to [`is_retryable`]
+    pub retryable: bool,
+}
+
+impl NodeError {
+    /// Describe the error returned by the node at `node`
+    fn new(node: &str, error: &Error) -> Self {
+        match error {
+            Error::NodeFailed { node, source } => Self {
+                node: node.clone(),
+                message: source.to_string(),
+                retryable: is_retryable(source),
+            },
+            error => Self {
+                node: node.to_string(),
+                message: error.to_string(),
+                retryable: is_retryable(error),
+            },
+        }
+    }
+
+    /// Get the error stored in a state's metadata under [`ERROR_KEY`], if any
+    pub fn from_state<S: StateValue>(state: &State<S>) -> Option<Self> {
+        state
+            .metadata
+            .get(ERROR_KEY)
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+    }
+}
+
+impl<S: StateValue> Graph<S> {
+    /// Route the failures of a node to another node
+    ///
+    /// When `from` fails after its retries, `to` runs next with the input state of
+    /// `from`, whose metadata holds a [`NodeError`] under [`ERROR_KEY`] for `to`
+    /// only. Interrupts, cancellation and the recursion limit are not routed. A
+    /// node has at most one error edge.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glint::graph::GraphBuilder;
+    /// # use glint::graph::NodeProcessor;
+    /// # use glint::state::State;
+    /// # struct Model(&'static str);
+    /// # #[async_trait::async_trait]
+    /// # impl NodeProcessor<String> for Model {
+    /// #     async fn process(&self, state: State<String>) -> glint::Result<State<String>> {
+    /// #         Ok(state)
+    /// #     }
+    /// # }
+    ///
+    /// let graph = GraphBuilder::<String>::new()
+    ///     .with_node("answer", Model("gpt-4o"))?
+    ///     .with_node("answer_cheaply", Model("gpt-4o-mini"))?
+    ///     .with_start_edge("answer")?
+    ///     .with_error_edge("answer", "answer_cheaply")?
+    ///     .with_end_edge("answer")?
+    ///     .with_end_edge("answer_cheaply")?
+    ///     .compile()?;
+    /// # Ok::<(), glint::Error>(())
+    /// ```
+    pub fn add_error_edge(
+        &mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+    ) -> Result<&mut Self> {
+        let from = from.into();
+        let to = to.into();
+
+        if !self.processors.contains_key(&from) {
+            return Err(Error::InvalidNode(format!(
+                "Source node not found: {}",
+                from
+            )));
+        }
+        if to == START || to == END || !self.processors.contains_key(&to) {
+            return Err(Error::InvalidNode(format!("Target node not found: {}", to)));
+        }
+
+        let from_idx = self.node_map[&from];
+        if self
+            .graph
+            .edges(from_idx)
+            .any(|edge| matches!(edge.weight(), EdgeKind::Error))
+        {
+            return Err(Error::InvalidEdge(format!(
+                "Node already has an error edge: {}",
+                from
+            )));
+        }
+
+        self.graph
+            .add_edge(from_idx, self.node_map[&to], EdgeKind::Error);
+        Ok(self)
+    }
+
+    /// Route the failures of every node without an error edge to a handler node
+    ///
+    /// The handler gets the failed node's input state like the target of an error
+    /// edge does. Failures of the handler itself are not routed.
+    pub fn set_error_handler(&mut self, node: impl Into<String>) -> Result<&mut Self> {
+        let node = node.into();
+        if !self.processors.contains_key(&node) {
+            return Err(Error::InvalidNode(format!("Node not found: {}", node)));
+        }
+
+        self.error_handler = Some(node);
+        Ok(self)
+    }
+
+    /// Get the node that failures of a node are routed to, if any
+    pub(crate) fn error_target(&self, node_idx: NodeIndex) -> Option<&str> {
+        let edge_target = self
+            .graph
+            .edges(node_idx)
+            .find(|edge| matches!(edge.weight(), EdgeKind::Error))
+            .map(|edge| self.graph[edge.target()].as_str());
+
+        edge_target.or_else(|| {
+            self.error_handler
+                .as_deref()
+                .filter(|handler| *handler != self.graph[node_idx])
+        })
+    }
+
+    /// Get the node routed to as the graph-wide error handler, if any
+    pub(crate) fn error_handler(&self) -> Option<&str> {
+        self.error_handler.as_deref()
+    }
+
+    /// Turn the failure of a node into a jump to its error handler, carrying the
+    /// error for the handler
+    pub(crate) fn recover(
+        mut input: State<S>,
+        error: &Error,
+        target: &str,
+        ctx: &NodeContext<S>,
+    ) -> Command<S> {
+        let node_error = NodeError::new(ctx.path(), error);
+        tracing::warn!(
+            node = %node_error.node,
+            error = %node_error.message,
+            handler = target,
+            "routing node failure to error handler"
+        );
+
+        // A handler that failed does not pass on the error it was handling
+        input.metadata.remove(ERROR_KEY);
+        let mut command = Command::new(input).goto(target);
+        command.routed_error = Some((target.to_string(), node_error));
+        command
+    }
+
+    /// Get the errors routed to handlers that have not run yet
+    pub(crate) fn routed_errors(state: &State<S>) -> RoutedErrors {
+        state
+            .metadata
+            .get(ROUTED_ERRORS_KEY)
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Take the errors routed to handlers that have not run yet out of a state
+    pub(crate) fn take_routed_errors(state: &mut State<S>) -> RoutedErrors {
+        let routed = Self::routed_errors(state);
+        state.metadata.remove(ROUTED_ERRORS_KEY);
+        routed
+    }
+
+    /// Keep the errors routed to handlers that have not run yet in a state
+    pub(crate) fn keep_routed_errors(state: &mut State<S>, routed: RoutedErrors) {
+        if routed.is_empty() {
+            state.metadata.remove(ROUTED_ERRORS_KEY);
+        // NodeError holds only strings and a bool, so it always serializes
+        } else if let Ok(value) = serde_json::to_value(routed) {
+            state.metadata.insert(ROUTED_ERRORS_KEY.to_string(), value);
+        }
+    }
+
+    /// Give a node the error routed to it, if any, and hide the errors routed to
+    /// other nodes
+    pub(crate) fn handler_input(mut state: State<S>, node_name: &str) -> State<S> {
+        let error = Self::take_routed_errors(&mut state).remove(node_name);
+        match error.and_then(|error| serde_json::to_value(error).ok()) {
+            Some(value) => {
+                state.metadata.insert(ERROR_KEY.to_string(), value);
+            }
+            None => {
+                state.metadata.remove(ERROR_KEY);
+            }
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{ExecutionStrategy, GraphBuilder, NodeProcessor};
+    use crate::state::{ChannelReducer, Reducer};
+    use async_trait::async_trait;
+
+    /// Appends its name to the state, failing if it is a failing node
+    struct Step {
+        name: &'static str,
+        fails: bool,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<Vec<String>> for Step {
+        async fn process(&self, mut state: State<Vec<String>>) -> Result<State<Vec<String>>> {
+            if self.fails {
+                return Err(Error::LLM(format!("{} is overloaded", self.name)));
+            }
+            state.data.push(self.name.to_string());
+            Ok(state)
+        }
+    }
+
+    /// Appends the failed node recorded in the state
+    struct Apologize;
+
+    #[async_trait]
+    impl NodeProcessor<Vec<String>> for Apologize {
+        async fn process(&self, mut state: State<Vec<String>>) -> Result<State<Vec<String>>> {
+            let error = NodeError::from_state(&state)
+                .ok_or_else(|| Error::State("No error in state".to_string()))?;
+            let retry = if error.retryable { "retry" } else { "give up" };
+            state.data.push(format!(
+                "sorry, {} failed: {}, {}",
+                error.node, error.message, retry
+            ));
+            Ok(state)
+        }
+    }
+
+    fn step(name: &'static str, fails: bool) -> Step {
+        Step { name, fails }
+    }
+
+    #[tokio::test]
+    async fn test_error_edge_routes_to_fallback() {
+        let mut graph = GraphBuilder::new()
+            .with_node("answer", step("answer", true))
+            .unwrap()
+            .with_node("answer_cheaply", Apologize)
+            .unwrap()
+            .with_node("format", step("format", false))
+            .unwrap()
+            .with_start_edge("answer")
+            .unwrap()
+            .with_error_edge("answer", "answer_cheaply")
+            .unwrap()
+            .with_edge("answer", "format", None)
+            .unwrap()
+            .with_edge("answer_cheaply", "format", None)
+            .unwrap()
+            .with_end_edge("format")
+            .unwrap()
+            .compile()
+            .unwrap();
+
+        let final_state = graph.execute(State::new(Vec::new())).await.unwrap();
+        assert_eq!(
+            final_state.data,
+            vec![
+                "sorry, answer failed: LLM error: answer is overloaded, retry",
+                "format"
+            ]
+        );
+        // The error does not leak past the node it was routed to
+        assert_eq!(NodeError::from_state(&final_state), None);
+        assert!(final_state.metadata.is_empty());
+
+        let result = graph.add_error_edge("answer", "format").map(|_| ());
+        assert!(matches!(result, Err(Error::InvalidEdge(_))));
+    }
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    struct Log {
+        entries: Vec<String>,
+    }
+
+    impl StateValue for Log {}
+
+    /// Logs its name and the error routed to it, if any
+    struct Record {
+        name: &'static str,
+        fails: bool,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<Log> for Record {
+        async fn process(&self, mut state: State<Log>) -> Result<State<Log>> {
+            if self.fails {
+                return Err(Error::Other("boom".to_string()));
+            }
+            let entry = match NodeError::from_state(&state) {
+                Some(error) => format!("{} saw {}", self.name, error.node),
+                None => self.name.to_string(),
+            };
+            state.data.entries.push(entry);
+            Ok(state)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_error_edge_in_parallel_branches() {
+        let record = |name, fails| Record { name, fails };
+        let graph = GraphBuilder::new()
+            .with_execution_strategy(ExecutionStrategy::Parallel)
+            .with_reducer(ChannelReducer::new().with_field("entries", Reducer::Append))
+            .with_node("ok", record("ok", false))
+            .unwrap()
+            .with_node("fail", record("fail", true))
+            .unwrap()
+            .with_node("after_ok", record("after_ok", false))
+            .unwrap()
+            .with_node("handler", record("handler", false))
+            .unwrap()
+            .with_start_edge("ok")
+            .unwrap()
+            .with_start_edge("fail")
+            .unwrap()
+            .with_edge("ok", "after_ok", None)
+            .unwrap()
+            .with_error_edge("fail", "handler")
+            .unwrap()
+            .with_end_edge("fail")
+            .unwrap()
+            .with_end_edge("after_ok")
+            .unwrap()
+            .with_end_edge("handler")
+            .unwrap()
+            .compile()
+            .unwrap();
+
+        let final_state = graph.execute(State::new(Log::default())).await.unwrap();
+        // The node running alongside the handler does not see the error
+        assert_eq!(
+            final_state.data.entries,
+            vec!["ok", "handler saw fail", "after_ok"]
+        );
+        assert!(final_state.metadata.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_error_handler_catches_nested_failures() {
+        let research = GraphBuilder::new()
+            .with_node("search", step("search", true))
+            .unwrap()
+            .with_start_edge("search")
+            .unwrap()
+            .with_end_edge("search")
+            .unwrap()
+            .build();
+
+        let graph = GraphBuilder::new()
+            .with_node("research", research)
+            .unwrap()
+            .with_node("apologize", Apologize)
+            .unwrap()
+            .with_start_edge("research")
+            .unwrap()
+            .with_end_edge("research")
+            .unwrap()
+            .with_end_edge("apologize")
+            .unwrap()
+            .with_error_handler("apologize")
+            .unwrap()
+            .compile()
+            .unwrap();
+
+        let final_state = graph.execute(State::new(Vec::new())).await.unwrap();
+        assert_eq!(
+            final_state.data,
+            vec!["sorry, research/search failed: LLM error: search is overloaded, retry"]
+        );
+        assert!(!final_state.has_metadata(ERROR_KEY));
+
+        // Failures of the handler itself are not routed back to it
+        let failing = GraphBuilder::new()
+            .with_node("apologize", step("apologize", true))
+            .unwrap()
+            .with_start_edge("apologize")
+            .unwrap()
+            .with_end_edge("apologize")
+            .unwrap()
+            .with_error_handler("apologize")
+            .unwrap()
+            .build();
+        let error = failing.execute(State::new(Vec::new())).await.unwrap_err();
+        assert!(matches!(error, Error::NodeFailed { node, .. } if node == "apologize"));
+    }
+}
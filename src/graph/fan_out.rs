@@ -0,0 +1,369 @@
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use std::sync::Arc;
+
+use super::context::RunContext;
+use super::{EdgeKind, Graph, GraphEvent};
+use crate::error::Error;
+use crate::state::{State, StateValue};
+use crate::Result;
+
+/// A branch of a dynamic fan-out: a node to run and the state to run it with
+#[derive(Debug, Clone)]
+pub struct Branch<S: StateValue> {
+    /// Name of the node to run
+    pub node: String,
+    /// The input state of the branch
+    pub state: State<S>,
+}
+
+impl<S: StateValue> Branch<S> {
+    /// Create a branch that runs `node` with the given input state
+    pub fn new(node: impl Into<String>, state: State<S>) -> Self {
+        Self {
+            node: node.into(),
+            state,
+        }
+    }
+}
+
+/// Type alias for functions that split a state into branches run in parallel
+pub type FanOutFn<S> = Arc<dyn Fn(&State<S>) -> Result<Vec<Branch<S>>> + Send + Sync>;
+
+/// A dynamic fan-out from a node
+pub(crate) struct FanOut<S: StateValue> {
+    /// Splits the node's output into branches
+    branches: FanOutFn<S>,
+    /// Maximum number of branches running at once
+    max_concurrency: Option<usize>,
+}
+
+impl<S: StateValue> Graph<S> {
+    /// Fan out from a node into a number of branches decided at runtime
+    ///
+    /// After `from` runs, `fan_out` maps its state to branches, each naming a node
+    /// and the state to run it with. The branches run concurrently, at most
+    /// `max_concurrency` at a time, and their states are merged into the state of
+    /// `from` with the graph's reducer, in the order the branches were returned.
+    /// Execution then follows the edges of the branch nodes. When `fan_out` returns
+    /// no branches, the edges of every target are followed with the unchanged state.
+    ///
+    /// `targets` declares every node a branch may run. A node that fans out can
+    /// have no other outgoing edges, and the whole fan-out counts as a single step.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glint::graph::{Branch, Graph};
+    /// use glint::state::{ChannelReducer, Reducer, State};
+    /// use serde::{Deserialize, Serialize};
+    /// use std::sync::Arc;
+    /// # use glint::graph::NodeProcessor;
+    /// # struct Noop;
+    /// # #[async_trait::async_trait]
+    /// # impl NodeProcessor<Docs> for Noop {
+    /// #     async fn process(&self, state: State<Docs>) -> glint::Result<State<Docs>> {
+    /// #         Ok(state)
+    /// #     }
+    /// # }
+    ///
+    /// #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    /// struct Docs {
+    ///     documents: Vec<String>,
+    ///     current: String,
+    ///     summaries: Vec<String>,
+    /// }
+    ///
+    /// impl glint::state::StateValue for Docs {}
+    ///
+    /// let mut graph = Graph::new().with_reducer(
+    ///     ChannelReducer::new()
+    ///         .with_field("current", Reducer::LastValue)
+    ///         .with_field("summaries", Reducer::Append),
+    /// );
+    /// graph.add_node("retrieve", Noop)?;
+    /// graph.add_node("summarize", Noop)?;
+    /// graph.add_start_edge("retrieve")?;
+    /// graph.add_fan_out(
+    ///     "retrieve",
+    ///     Arc::new(|state: &State<Docs>| {
+    ///         Ok(state
+    ///             .data
+    ///             .documents
+    ///             .iter()
+    ///             .map(|doc| {
+    ///                 let mut branch = state.clone();
+    ///                 branch.data.current = doc.clone();
+    ///                 Branch::new("summarize", branch)
+    ///             })
+    ///             .collect())
+    ///     }),
+    ///     ["summarize"],
+    ///     Some(4),
+    /// )?;
+    /// graph.add_end_edge("summarize")?;
+    /// # Ok::<(), glint::Error>(())
+    /// ```
+    pub fn add_fan_out(
+        &mut self,
+        from: impl Into<String>,
+        fan_out: FanOutFn<S>,
+        targets: impl IntoIterator<Item = impl Into<String>>,
+        max_concurrency: Option<usize>,
+    ) -> Result<&mut Self> {
+        let from = from.into();
+        let from_idx = self.source_index(&from)?;
+
+        if self.graph.edges(from_idx).next().is_some() {
+            return Err(Error::InvalidEdge(format!(
+                "Node already has outgoing edges and cannot also fan out: {}",
+                from
+            )));
+        }
+        if max_concurrency == Some(0) {
+            return Err(Error::InvalidEdge(format!(
+                "Fan-out concurrency limit of node {} must be at least 1",
+                from
+            )));
+        }
+
+        let mut target_indices = Vec::new();
+        for target in targets {
+            let to_idx = self.target_index(&target.into())?;
+            if !target_indices.contains(&to_idx) {
+                target_indices.push(to_idx);
+            }
+        }
+
+        for to_idx in target_indices {
+            self.graph.add_edge(from_idx, to_idx, EdgeKind::Routed);
+        }
+        self.fan_outs.insert(
+            from,
+            FanOut {
+                branches: fan_out,
+                max_concurrency,
+            },
+        );
+        Ok(self)
+    }
+
+    /// Determine the nodes to run after `node_idx`, first running the node's
+    /// fan-out if it has one
+    ///
+    /// Returns the state to continue with and the next nodes.
+    pub(crate) async fn advance(
+        &self,
+        node_idx: NodeIndex,
+        state: State<S>,
+        goto: &[String],
+        run: &RunContext<S>,
+        step: usize,
+    ) -> Result<(State<S>, Vec<NodeIndex>)> {
+        let node_name = &self.graph[node_idx];
+        let fan_out = match self.fan_outs.get(node_name) {
+            Some(fan_out) if goto.is_empty() => fan_out,
+            _ => {
+                let next = self.next_nodes(node_idx, &state, goto)?;
+                self.emit_edges(run, node_idx, &next);
+                return Ok((state, next));
+            }
+        };
+
+        let branches = (fan_out.branches)(&state)?
+            .into_iter()
+            .map(|branch| {
+                let target_idx = self
+                    .graph
+                    .edges(node_idx)
+                    .map(|edge| edge.target())
+                    .find(|&target_idx| self.graph[target_idx] == branch.node)
+                    .ok_or_else(|| {
+                        Error::InvalidEdge(format!(
+                            "Fan-out of node {} returned undeclared target: {}",
+                            node_name, branch.node
+                        ))
+                    })?;
+                Ok((target_idx, branch.state))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut state = state;
+        let mut jumps = Vec::with_capacity(branches.len());
+        if branches.is_empty() {
+            jumps.extend(
+                self.graph
+                    .edges(node_idx)
+                    .map(|edge| (edge.target(), Vec::new())),
+            );
+        } else {
+            let targets: Vec<NodeIndex> =
+                branches.iter().map(|(target_idx, _)| *target_idx).collect();
+            self.emit_edges(run, node_idx, &targets);
+
+            let results = self
+                .run_nodes(branches, run, step, fan_out.max_concurrency)
+                .await?;
+
+            let mut routed = Self::take_routed_errors(&mut state);
+            let mut branches = Vec::with_capacity(results.len());
+            for (target_idx, mut command) in results {
+                routed.extend(command.routed_error.take());
+                jumps.push((target_idx, std::mem::take(&mut command.goto)));
+                branches.push(command);
+            }
+            state = self.merge_states(&state, branches)?;
+            Self::keep_routed_errors(&mut state, routed);
+        }
+
+        // Continue along the edges of the branch nodes, visiting each node once
+        let mut next = Vec::new();
+        for (target_idx, goto) in &jumps {
+            let target_next = self.next_nodes(*target_idx, &state, goto)?;
+            for next_idx in target_next {
+                if !next.contains(&next_idx) {
+                    run.emit(|| GraphEvent::EdgeTaken {
+                        from: run.node_path(&self.graph[*target_idx]),
+                        to: run.node_path(&self.graph[next_idx]),
+                    });
+                    next.push(next_idx);
+                }
+            }
+        }
+        Ok((state, next))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{GraphBuilder, NodeProcessor};
+    use crate::state::{ChannelReducer, Reducer, StateValue};
+    use async_trait::async_trait;
+    use serde::{Deserialize, Serialize};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    struct Docs {
+        documents: Vec<String>,
+        current: String,
+        summaries: Vec<String>,
+        report: String,
+    }
+
+    impl StateValue for Docs {}
+
+    /// Summarizes the current document, tracking how many run at once
+    struct Summarize {
+        running: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<Docs> for Summarize {
+        async fn process(&self, mut state: State<Docs>) -> Result<State<Docs>> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+
+            let summary = format!("summary of {}", state.data.current);
+            state.data.summaries.push(summary);
+            Ok(state)
+        }
+    }
+
+    struct Report;
+
+    #[async_trait]
+    impl NodeProcessor<Docs> for Report {
+        async fn process(&self, mut state: State<Docs>) -> Result<State<Docs>> {
+            state.data.report = state.data.summaries.join(", ");
+            Ok(state)
+        }
+    }
+
+    struct Noop;
+
+    #[async_trait]
+    impl NodeProcessor<Docs> for Noop {
+        async fn process(&self, state: State<Docs>) -> Result<State<Docs>> {
+            Ok(state)
+        }
+    }
+
+    fn summarize_graph(target: &'static str, peak: &Arc<AtomicUsize>) -> Graph<Docs> {
+        let summarize = Summarize {
+            running: Arc::new(AtomicUsize::new(0)),
+            peak: peak.clone(),
+        };
+        let fan_out: FanOutFn<Docs> = Arc::new(move |state: &State<Docs>| {
+            Ok(state
+                .data
+                .documents
+                .iter()
+                .map(|doc| {
+                    let mut branch = state.clone();
+                    branch.data.current = doc.clone();
+                    Branch::new(target, branch)
+                })
+                .collect())
+        });
+
+        GraphBuilder::new()
+            .with_reducer(
+                ChannelReducer::new()
+                    .with_field("current", Reducer::LastValue)
+                    .with_field("summaries", Reducer::Append),
+            )
+            .with_node("retrieve", Noop)
+            .unwrap()
+            .with_node("summarize", summarize)
+            .unwrap()
+            .with_node("report", Report)
+            .unwrap()
+            .with_start_edge("retrieve")
+            .unwrap()
+            .with_fan_out("retrieve", fan_out, ["summarize"], Some(2))
+            .unwrap()
+            .with_edge("summarize", "report", None)
+            .unwrap()
+            .with_end_edge("report")
+            .unwrap()
+            .build()
+    }
+
+    fn documents(count: usize) -> State<Docs> {
+        State::new(Docs {
+            documents: (1..=count).map(|i| format!("doc{}", i)).collect(),
+            ..Docs::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_map_reduce() {
+        let peak = Arc::new(AtomicUsize::new(0));
+        let graph = summarize_graph("summarize", &peak);
+
+        let final_state = graph.execute(documents(5)).await.unwrap();
+        assert_eq!(
+            final_state.data.report,
+            "summary of doc1, summary of doc2, summary of doc3, summary of doc4, summary of doc5"
+        );
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+
+        // No documents: the report runs on the unchanged state
+        let final_state = graph.execute(documents(0)).await.unwrap();
+        assert_eq!(final_state.data.report, "");
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_undeclared_target() {
+        let peak = Arc::new(AtomicUsize::new(0));
+        let graph = summarize_graph("report", &peak);
+
+        let result = graph.execute(documents(1)).await;
+        assert!(matches!(result, Err(Error::InvalidEdge(_))));
+    }
+}
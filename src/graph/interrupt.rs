@@ -0,0 +1,534 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::context::RunContext;
+use super::{Graph, NodeContext, RunConfig, PATH_SEPARATOR};
+use crate::error::Error;
+use crate::state::{State, StateValue};
+use crate::Result;
+
+/// Key of the state metadata entry holding the value a run was resumed with
+///
+/// The entry is in the input of the nodes that run first after resuming and is
+/// removed from their output, so later interrupts do not see an earlier answer.
+pub const RESUME_KEY: &str = "__glint_resume__";
+
+/// Key of the checkpoint metadata entry holding the interrupt that stopped a run
+pub(crate) const INTERRUPT_KEY: &str = "interrupt";
+
+/// Where an interrupt stopped a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InterruptKind {
+    /// Before a node listed in `interrupt_before` ran
+    Before,
+    /// After a node listed in `interrupt_after` ran
+    After,
+    /// Raised by a node with [`NodeContext::interrupt`]
+    Dynamic,
+}
+
+/// An interrupt that stopped a run to wait for human input
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interrupt {
+    /// Path of the node the interrupt happened at
+    pub node: String,
+    /// Where the interrupt happened
+    pub kind: InterruptKind,
+    /// Payload of a dynamic interrupt, e.g. the tool call awaiting approval
+    pub value: Value,
+}
+
+impl Interrupt {
+    /// Create an interrupt at the given node
+    pub fn new(node: impl Into<String>, kind: InterruptKind, value: Value) -> Self {
+        Self {
+            node: node.into(),
+            kind,
+            value,
+        }
+    }
+}
+
+impl From<Interrupt> for Error {
+    fn from(interrupt: Interrupt) -> Self {
+        Error::Interrupted(Box::new(interrupt))
+    }
+}
+
+/// The outcome of a run that may be interrupted
+#[derive(Debug, Clone)]
+pub enum RunOutcome<S: StateValue> {
+    /// The run reached END
+    Completed(State<S>),
+    /// The run stopped at an interrupt and can be continued with
+    /// [`Graph::resume_with`]
+    Interrupted {
+        /// The state saved with the interrupt
+        state: State<S>,
+        /// The interrupt that stopped the run
+        interrupt: Interrupt,
+        /// ID of the checkpoint saved with the interrupt
+        checkpoint_id: String,
+    },
+}
+
+impl<S: StateValue> RunOutcome<S> {
+    /// Check whether the run stopped at an interrupt
+    pub fn is_interrupted(&self) -> bool {
+        matches!(self, RunOutcome::Interrupted { .. })
+    }
+
+    /// Get the state the run finished or stopped with
+    pub fn state(&self) -> &State<S> {
+        match self {
+            RunOutcome::Completed(state) | RunOutcome::Interrupted { state, .. } => state,
+        }
+    }
+}
+
+/// Lets a resumed run pass the interrupt it stopped at once
+#[derive(Debug)]
+pub(crate) struct Release {
+    interrupt: Interrupt,
+    value: Option<Value>,
+    used: AtomicBool,
+}
+
+impl Release {
+    pub(crate) fn new(interrupt: Interrupt, value: Option<Value>) -> Self {
+        Self {
+            interrupt,
+            value,
+            used: AtomicBool::new(false),
+        }
+    }
+
+    /// Pass the interrupt if it is the one the run was resumed from, returning the
+    /// value supplied on resume
+    pub(crate) fn take(&self, node: &str, kind: InterruptKind) -> Option<Value> {
+        if self.interrupt.node != node || self.interrupt.kind != kind {
+            return None;
+        }
+        if self.used.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+        Some(self.value.clone().unwrap_or(Value::Null))
+    }
+}
+
+impl<S: StateValue> NodeContext<S> {
+    /// Stop the run to wait for human input, or get the input once it is resumed
+    ///
+    /// The first call returns an [`Error::Interrupted`] error that the node should
+    /// return, carrying `value` for whoever handles the interrupt. When the run is
+    /// resumed with [`Graph::resume_with`] the node runs again, and this call returns
+    /// the supplied value instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glint::graph::{NodeContext, NodeProcessor, Command};
+    /// use glint::state::State;
+    /// use glint::Result;
+    /// use async_trait::async_trait;
+    /// use serde_json::json;
+    ///
+    /// struct Tools;
+    ///
+    /// #[async_trait]
+    /// impl NodeProcessor<String> for Tools {
+    ///     async fn process(&self, state: State<String>) -> Result<State<String>> {
+    ///         Ok(state)
+    ///     }
+    ///
+    ///     async fn process_command(
+    ///         &self,
+    ///         mut state: State<String>,
+    ///         ctx: &NodeContext<String>,
+    ///     ) -> Result<Command<String>> {
+    ///         let approval = ctx.interrupt(json!({ "tool": "delete_file" }))?;
+    ///         if approval == json!("approve") {
+    ///             state.data.push_str("deleted");
+    ///         }
+    ///         Ok(Command::new(state))
+    ///     }
+    /// }
+    /// ```
+    pub fn interrupt(&self, value: impl Serialize) -> Result<Value> {
+        if let Some(resumed) = self.release(InterruptKind::Dynamic) {
+            return Ok(resumed);
+        }
+
+        Err(Interrupt::new(
+            self.path(),
+            InterruptKind::Dynamic,
+            serde_json::to_value(value)?,
+        )
+        .into())
+    }
+}
+
+impl<S: StateValue> Graph<S> {
+    /// Stop runs before any of the given nodes run
+    pub fn with_interrupt_before(
+        mut self,
+        nodes: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.interrupt_before
+            .extend(nodes.into_iter().map(Into::into));
+        self
+    }
+
+    /// Stop runs after any of the given nodes ran
+    pub fn with_interrupt_after(
+        mut self,
+        nodes: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.interrupt_after
+            .extend(nodes.into_iter().map(Into::into));
+        self
+    }
+
+    /// Execute the graph with the given initial state, stopping at interrupts
+    ///
+    /// When the configuration has a checkpointer, a run that hits an interrupt saves
+    /// a checkpoint and returns [`RunOutcome::Interrupted`]; continue it with
+    /// [`Graph::resume_with`]. Without a checkpointer the interrupt is returned as an
+    /// [`Error::Interrupted`] error.
+    pub async fn invoke(
+        &self,
+        initial_state: State<S>,
+        config: &RunConfig<S>,
+    ) -> Result<RunOutcome<S>> {
+        let run = config.run_context()?;
+        let result = self.execute_in(initial_state, &run).await;
+        Self::outcome(result, &run)
+    }
+
+    /// Continue an interrupted run of a thread with a human-supplied value
+    ///
+    /// The value is stored in the state's metadata under [`RESUME_KEY`], and a node
+    /// that raised a dynamic interrupt gets it from [`NodeContext::interrupt`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use glint::graph::{Graph, RunConfig, RunOutcome};
+    /// # use glint::state::State;
+    /// # async fn approve(graph: Graph<String>, config: RunConfig<String>) -> glint::Result<()> {
+    /// let outcome = graph.invoke(State::new("delete tmp/".to_string()), &config).await?;
+    ///
+    /// if let RunOutcome::Interrupted { interrupt, .. } = outcome {
+    ///     println!("approval needed at {}", interrupt.node);
+    ///     graph.resume_with(&config, serde_json::json!("approve")).await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn resume_with(&self, config: &RunConfig<S>, value: Value) -> Result<RunOutcome<S>> {
+        let checkpointing = Self::require_checkpointing(config)?;
+        let mut checkpoint = Self::latest_checkpoint(&checkpointing)?;
+        checkpoint
+            .state
+            .metadata
+            .insert(RESUME_KEY.to_string(), value.clone());
+
+        let run = Self::resumed_run(&checkpoint, checkpointing, config, Some(value))?;
+        let result = self.continue_from(checkpoint, &run).await;
+        Self::outcome(result, &run)
+    }
+
+    /// Get the interrupt configured for a node, unless the run was resumed from it
+    pub(crate) fn check_interrupt(
+        &self,
+        node_name: &str,
+        kind: InterruptKind,
+        run: &RunContext<S>,
+    ) -> Option<Interrupt> {
+        let configured = match kind {
+            InterruptKind::Before => &self.interrupt_before,
+            InterruptKind::After => &self.interrupt_after,
+            InterruptKind::Dynamic => return None,
+        };
+        if !configured.contains(node_name) {
+            return None;
+        }
+
+        let node_path = run.node_path(node_name);
+        if run.release(&node_path, kind).is_some() {
+            return None;
+        }
+        Some(Interrupt::new(node_path, kind, Value::Null))
+    }
+
+    /// Save the state that interrupted nodes started from, so that resuming the run
+    /// runs them again
+    ///
+    /// Errors other than interrupts, and interrupts of nested runs, are returned
+    /// unchanged.
+    pub(crate) fn save_interrupt(
+        &self,
+        run: &RunContext<S>,
+        error: Error,
+        nodes: &[&str],
+        input: Option<&State<S>>,
+        next: &[&str],
+    ) -> Error {
+        match (error, input) {
+            (Error::Interrupted(interrupt), Some(input)) => {
+                match run.checkpoint(nodes, input, next, Some(&interrupt)) {
+                    Ok(()) => Error::Interrupted(interrupt),
+                    Err(e) => e,
+                }
+            }
+            (error, _) => error,
+        }
+    }
+
+    /// Turn the result of a run into an outcome, loading the checkpoint saved with
+    /// an interrupt
+    fn outcome(result: Result<State<S>>, run: &RunContext<S>) -> Result<RunOutcome<S>> {
+        match (result, run.checkpointing()) {
+            (Ok(state), _) => Ok(RunOutcome::Completed(state)),
+            (Err(Error::Interrupted(interrupt)), Some(checkpointing)) => {
+                let checkpoint_id = checkpointing.latest_id().ok_or_else(|| {
+                    Error::Checkpoint("No checkpoint saved with the interrupt".to_string())
+                })?;
+                let checkpoint = checkpointing.store().load(&checkpoint_id)?;
+                Ok(RunOutcome::Interrupted {
+                    state: checkpoint.state,
+                    interrupt: *interrupt,
+                    checkpoint_id,
+                })
+            }
+            (Err(e), _) => Err(e),
+        }
+    }
+}
+
+/// Decide whether resuming from an interrupt runs its node again
+///
+/// A node interrupted after it ran at the top level does not run again; every other
+/// interrupt does, inside the node the run stopped at.
+pub(crate) fn reruns_node(interrupt: &Interrupt) -> bool {
+    interrupt.kind != InterruptKind::After || interrupt.node.contains(PATH_SEPARATOR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::MemoryCheckpointStore;
+    use crate::graph::{Command, GraphBuilder, NodeProcessor};
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    struct Append {
+        text: &'static str,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<String> for Append {
+        async fn process(&self, mut state: State<String>) -> Result<State<String>> {
+            state.data.push_str(self.text);
+            Ok(state)
+        }
+    }
+
+    /// Asks for approval before running a tool
+    struct Approve;
+
+    #[async_trait]
+    impl NodeProcessor<String> for Approve {
+        async fn process(&self, state: State<String>) -> Result<State<String>> {
+            Ok(state)
+        }
+
+        async fn process_command(
+            &self,
+            mut state: State<String>,
+            ctx: &NodeContext<String>,
+        ) -> Result<Command<String>> {
+            let answer = ctx.interrupt(json!({ "tool": "search" }))?;
+            state.data.push_str(answer.as_str().unwrap_or("none"));
+            state.data.push(';');
+            Ok(Command::new(state))
+        }
+    }
+
+    fn agent_graph(tools: impl NodeProcessor<String> + 'static) -> GraphBuilder<String> {
+        GraphBuilder::new()
+            .with_node("model", Append { text: "model;" })
+            .unwrap()
+            .with_node("tools", tools)
+            .unwrap()
+            .with_start_edge("model")
+            .unwrap()
+            .with_edge("model", "tools", None)
+            .unwrap()
+            .with_end_edge("tools")
+            .unwrap()
+    }
+
+    fn config() -> RunConfig<String> {
+        RunConfig::new()
+            .with_thread_id("thread-1")
+            .with_checkpointer(Arc::new(MemoryCheckpointStore::new()))
+    }
+
+    #[tokio::test]
+    async fn test_interrupt_before() {
+        let graph = agent_graph(Append { text: "tools;" })
+            .interrupt_before(["tools"])
+            .compile()
+            .unwrap();
+        let config = config();
+
+        let outcome = graph
+            .invoke(State::new(String::new()), &config)
+            .await
+            .unwrap();
+        match &outcome {
+            RunOutcome::Interrupted {
+                interrupt, state, ..
+            } => {
+                assert_eq!(interrupt.node, "tools");
+                assert_eq!(interrupt.kind, InterruptKind::Before);
+                assert_eq!(state.data, "model;");
+            }
+            RunOutcome::Completed(_) => panic!("expected an interrupt"),
+        }
+
+        let outcome = graph.resume_with(&config, json!("ok")).await.unwrap();
+        assert!(!outcome.is_interrupted());
+        assert_eq!(outcome.state().data, "model;tools;");
+        assert!(!outcome.state().has_metadata(RESUME_KEY));
+    }
+
+    /// Appends the value the run was resumed with
+    struct Answer;
+
+    #[async_trait]
+    impl NodeProcessor<String> for Answer {
+        async fn process(&self, mut state: State<String>) -> Result<State<String>> {
+            let answer: Option<String> = state.get_metadata(RESUME_KEY)?;
+            state.data.push_str(answer.as_deref().unwrap_or("none"));
+            state.data.push(';');
+            Ok(state)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_value_is_not_seen_by_later_interrupts() {
+        let graph = GraphBuilder::new()
+            .with_node("first", Answer)
+            .unwrap()
+            .with_node("second", Answer)
+            .unwrap()
+            .with_start_edge("first")
+            .unwrap()
+            .with_edge("first", "second", None)
+            .unwrap()
+            .with_end_edge("second")
+            .unwrap()
+            .interrupt_before(["first", "second"])
+            .compile()
+            .unwrap();
+        let config = config();
+
+        let outcome = graph
+            .invoke(State::new(String::new()), &config)
+            .await
+            .unwrap();
+        assert!(outcome.is_interrupted());
+
+        let outcome = graph.resume_with(&config, json!("approve")).await.unwrap();
+        assert!(outcome.is_interrupted());
+        assert_eq!(outcome.state().data, "approve;");
+        assert!(!outcome.state().has_metadata(RESUME_KEY));
+
+        // The second prompt was not answered
+        let final_state = graph.resume(&config).await.unwrap();
+        assert_eq!(final_state.data, "approve;none;");
+        assert!(final_state.metadata.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_interrupt_after() {
+        let graph = agent_graph(Append { text: "tools;" })
+            .interrupt_after(["model"])
+            .compile()
+            .unwrap();
+        let config = config();
+
+        let outcome = graph
+            .invoke(State::new(String::new()), &config)
+            .await
+            .unwrap();
+        assert!(outcome.is_interrupted());
+        assert_eq!(outcome.state().data, "model;");
+
+        // The model does not run again
+        let final_state = graph.resume(&config).await.unwrap();
+        assert_eq!(final_state.data, "model;tools;");
+    }
+
+    #[tokio::test]
+    async fn test_dynamic_interrupt() {
+        let graph = agent_graph(Approve).compile().unwrap();
+        let config = config();
+
+        let outcome = graph
+            .invoke(State::new(String::new()), &config)
+            .await
+            .unwrap();
+        match outcome {
+            RunOutcome::Interrupted { interrupt, .. } => {
+                assert_eq!(interrupt.kind, InterruptKind::Dynamic);
+                assert_eq!(interrupt.value, json!({ "tool": "search" }));
+            }
+            RunOutcome::Completed(_) => panic!("expected an interrupt"),
+        }
+
+        let outcome = graph.resume_with(&config, json!("approved")).await.unwrap();
+        assert_eq!(outcome.state().data, "model;approved;");
+
+        // Without a checkpointer the interrupt is an error
+        let result = graph.execute(State::new(String::new())).await;
+        assert!(matches!(result, Err(Error::Interrupted(_))));
+    }
+
+    #[tokio::test]
+    async fn test_interrupt_inside_subgraph() {
+        let inner = GraphBuilder::new()
+            .with_node("tools", Approve)
+            .unwrap()
+            .with_start_edge("tools")
+            .unwrap()
+            .with_end_edge("tools")
+            .unwrap()
+            .build();
+        let graph = GraphBuilder::new()
+            .with_node("agent", inner)
+            .unwrap()
+            .with_start_edge("agent")
+            .unwrap()
+            .with_end_edge("agent")
+            .unwrap()
+            .build();
+        let config = config();
+
+        let outcome = graph
+            .invoke(State::new(String::new()), &config)
+            .await
+            .unwrap();
+        match outcome {
+            RunOutcome::Interrupted { interrupt, .. } => assert_eq!(interrupt.node, "agent/tools"),
+            RunOutcome::Completed(_) => panic!("expected an interrupt"),
+        }
+
+        let outcome = graph.resume_with(&config, json!("yes")).await.unwrap();
+        assert_eq!(outcome.state().data, "yes;");
+    }
+}
@@ -0,0 +1,1981 @@
+use async_trait::async_trait;
+use petgraph::algo::has_path_connecting;
+use petgraph::graph::{DiGraph, EdgeIndex, NodeIndex};
+use petgraph::visit::EdgeRef;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+use tracing::Instrument;
+
+use crate::callbacks::{CallbackHandler, Callbacks};
+use crate::error::Error;
+use crate::serialization::SerializableNode;
+use crate::state::{LastValueReducer, State, StateReducer, StateValue};
+use crate::trace::{self, TraceScope};
+use crate::Result;
+
+mod batch;
+mod cache;
+mod cancellation;
+mod command;
+mod concurrency;
+mod config;
+mod context;
+mod export;
+mod fallback;
+mod fan_out;
+mod interrupt;
+mod registry;
+mod replay;
+mod resume;
+mod retry;
+mod stream;
+mod subgraph;
+mod update;
+mod validation;
+
+pub use cache::CachePolicy;
+pub use cancellation::CancellationToken;
+use command::CommandNode;
+pub use command::{Command, CommandProcessor};
+pub use config::RunConfig;
+use context::RunContext;
+pub use context::{NodeContext, PATH_SEPARATOR};
+pub use fallback::{NodeError, ERROR_KEY};
+use fan_out::FanOut;
+pub use fan_out::{Branch, FanOutFn};
+pub use interrupt::{Interrupt, InterruptKind, RunOutcome, RESUME_KEY};
+pub use registry::{NodeFactoryFn, NodeRegistry};
+use retry::is_control_flow;
+pub use retry::{is_retryable, RetryPolicy, RetryPredicateFn};
+pub use stream::GraphEvent;
+pub use subgraph::{InputMapFn, OutputMapFn, Subgraph};
+pub use update::UpdateProcessor;
+
+/// Special node name for the graph entry point
+pub const START: &str = "__start__";
+/// Special node name for the graph exit point
+pub const END: &str = "__end__";
+
+/// Trait for node processors that operate on state.
+///
+/// # Examples
+///
+/// ```
+/// use glint::graph::NodeProcessor;
+/// use glint::state::{State, StateValue};
+/// use glint::Result;
+/// use async_trait::async_trait;
+///
+/// #[derive(Debug, Clone)]
+/// struct MyState {
+///     value: i32,
+/// }
+///
+/// impl StateValue for MyState {}
+///
+/// struct MyProcessor;
+///
+/// #[async_trait]
+/// impl NodeProcessor<MyState> for MyProcessor {
+///     async fn process(&self, state: State<MyState>) -> Result<State<MyState>> {
+///         // Process state here
+///         Ok(state)
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait NodeProcessor<S: StateValue>: Send + Sync {
+    /// Process the state and return an updated state
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The current state to process
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either:
+    /// * Ok(State<S>) - The updated state after processing
+    /// * Err(Error) - An error that occurred during processing
+    async fn process(&self, state: State<S>) -> Result<State<S>>;
+
+    /// Process the state within a graph run and return a [`Command`] that may also
+    /// choose the next nodes
+    ///
+    /// The graph always calls this method. The default implementation wraps
+    /// [`process`](NodeProcessor::process) and follows the node's edges.
+    async fn process_command(&self, state: State<S>, _ctx: &NodeContext<S>) -> Result<Command<S>> {
+        Ok(Command::new(self.process(state).await?))
+    }
+}
+
+/// Type alias for edge condition functions
+pub type EdgeConditionFn<S> = Arc<dyn Fn(&State<S>) -> Result<bool> + Send + Sync>;
+
+/// Type alias for router functions that map a state to the names of the next nodes
+pub type RouterFn<S> = Arc<dyn Fn(&State<S>) -> Result<Vec<String>> + Send + Sync>;
+
+/// The kind of an edge stored in the graph
+enum EdgeKind<S: StateValue> {
+    /// Always taken
+    Direct,
+    /// Taken when the condition holds
+    Conditional(EdgeConditionFn<S>),
+    /// A possible target of the source node's router
+    Routed,
+    /// Taken instead of the other edges when the source node fails
+    Error,
+}
+
+/// Execution strategy for the graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionStrategy {
+    /// Execute nodes sequentially (default)
+    Sequential,
+    /// Execute the scheduled nodes in parallel supersteps
+    Parallel,
+}
+
+/// When a node scheduled by several branches runs during parallel execution
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JoinMode {
+    /// Wait until no other scheduled node can still reach this one (default)
+    #[default]
+    All,
+    /// Run in the first superstep the node is scheduled for
+    Any,
+}
+
+/// A graph of nodes that process state
+pub struct Graph<S: StateValue> {
+    /// The underlying directed graph
+    graph: DiGraph<String, EdgeKind<S>>,
+    /// Map of node names to node indices
+    node_map: HashMap<String, NodeIndex>,
+    /// Map of edges to the labels describing them
+    edge_labels: HashMap<EdgeIndex, String>,
+    /// Map of conditional edges to the names their conditions are registered under
+    edge_conditions: HashMap<EdgeIndex, String>,
+    /// Map of node names to the definitions they were loaded from
+    node_definitions: HashMap<String, SerializableNode>,
+    /// Map of node names to node processors
+    processors: HashMap<String, Arc<dyn NodeProcessor<S>>>,
+    /// Map of node names to the routers that pick their successors
+    routers: HashMap<String, RouterFn<S>>,
+    /// Map of node names to their dynamic fan-outs
+    fan_outs: HashMap<String, FanOut<S>>,
+    /// Map of command node names to the nodes their commands may jump to
+    destinations: HashMap<String, Vec<String>>,
+    /// Execution strategy
+    execution_strategy: ExecutionStrategy,
+    /// Maximum number of steps before a run is aborted
+    max_steps: usize,
+    /// Reducer used to merge the states of parallel branches
+    reducer: Arc<dyn StateReducer<S>>,
+    /// Map of node names to how they join parallel branches
+    join_modes: HashMap<String, JoinMode>,
+    /// Maximum number of nodes running at once in a superstep or fan-out
+    max_concurrency: Option<usize>,
+    /// Map of node names to the concurrency tags they count against
+    concurrency_tags: HashMap<String, String>,
+    /// Map of concurrency tags to the semaphores enforcing their limits
+    concurrency_limits: HashMap<String, Arc<Semaphore>>,
+    /// Map of node names to the policies their failed attempts are retried with
+    retry_policies: HashMap<String, RetryPolicy>,
+    /// Map of node names to the longest time a single attempt may take
+    node_timeouts: HashMap<String, Duration>,
+    /// Map of node names to the policies their results are cached with
+    cache_policies: HashMap<String, CachePolicy<S>>,
+    /// Node that the failures of nodes without an error edge are routed to
+    error_handler: Option<String>,
+    /// Nodes that runs stop before
+    interrupt_before: HashSet<String>,
+    /// Nodes that runs stop after
+    interrupt_after: HashSet<String>,
+    /// Handlers called as the nodes of every run execute
+    callbacks: Callbacks,
+}
+
+impl<S: StateValue> fmt::Debug for Graph<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Graph")
+            .field("node_map", &self.node_map)
+            .field("node_count", &self.graph.node_count())
+            .field("edge_count", &self.graph.edge_count())
+            .field("execution_strategy", &self.execution_strategy)
+            .field("max_steps", &self.max_steps)
+            .field("max_concurrency", &self.max_concurrency)
+            .field("callbacks", &self.callbacks)
+            .finish()
+    }
+}
+
+impl<S: StateValue> Default for Graph<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: StateValue> Graph<S> {
+    /// Create a new empty graph
+    pub fn new() -> Self {
+        let mut graph = DiGraph::new();
+        let mut node_map = HashMap::new();
+
+        // Add start and end nodes
+        let start_idx = graph.add_node(START.to_string());
+        let end_idx = graph.add_node(END.to_string());
+
+        node_map.insert(START.to_string(), start_idx);
+        node_map.insert(END.to_string(), end_idx);
+
+        Self {
+            graph,
+            node_map,
+            edge_labels: HashMap::new(),
+            edge_conditions: HashMap::new(),
+            node_definitions: HashMap::new(),
+            processors: HashMap::new(),
+            routers: HashMap::new(),
+            fan_outs: HashMap::new(),
+            destinations: HashMap::new(),
+            execution_strategy: ExecutionStrategy::Sequential,
+            max_steps: 1000,
+            reducer: Arc::new(LastValueReducer),
+            join_modes: HashMap::new(),
+            max_concurrency: None,
+            concurrency_tags: HashMap::new(),
+            concurrency_limits: HashMap::new(),
+            retry_policies: HashMap::new(),
+            node_timeouts: HashMap::new(),
+            cache_policies: HashMap::new(),
+            error_handler: None,
+            interrupt_before: HashSet::new(),
+            interrupt_after: HashSet::new(),
+            callbacks: Callbacks::new(),
+        }
+    }
+
+    /// Set the execution strategy
+    pub fn with_execution_strategy(mut self, strategy: ExecutionStrategy) -> Self {
+        self.execution_strategy = strategy;
+        self
+    }
+
+    /// Set the maximum number of steps before a run is aborted
+    ///
+    /// Sequential execution counts every node execution as a step; parallel
+    /// execution counts every superstep as a step.
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Set the reducer used to merge the states of parallel branches
+    ///
+    /// Defaults to [`LastValueReducer`], which keeps the last branch's state.
+    pub fn with_reducer(mut self, reducer: impl StateReducer<S> + 'static) -> Self {
+        self.reducer = Arc::new(reducer);
+        self
+    }
+
+    /// Register a handler called as the nodes of every run of the graph execute
+    ///
+    /// Handlers of a graph also see the nodes of its subgraphs.
+    pub fn with_callback(mut self, handler: Arc<dyn CallbackHandler>) -> Self {
+        self.callbacks.add(handler);
+        self
+    }
+
+    /// Add a node to the graph
+    pub fn add_node(
+        &mut self,
+        name: impl Into<String>,
+        processor: impl NodeProcessor<S> + 'static,
+    ) -> Result<&mut Self> {
+        self.add_shared_node(name, Arc::new(processor))
+    }
+
+    /// Replace the processor of an existing node, keeping its edges and settings
+    ///
+    /// Lets tests stub out nodes that call models or external services.
+    pub fn replace_node(
+        &mut self,
+        name: impl Into<String>,
+        processor: impl NodeProcessor<S> + 'static,
+    ) -> Result<&mut Self> {
+        let name = name.into();
+        match self.processors.get_mut(&name) {
+            Some(existing) => *existing = Arc::new(processor),
+            None => return Err(Error::InvalidNode(format!("Node not found: {}", name))),
+        }
+        Ok(self)
+    }
+
+    /// Add a node whose processor may be shared with other nodes or graphs
+    pub(crate) fn add_shared_node(
+        &mut self,
+        name: impl Into<String>,
+        processor: Arc<dyn NodeProcessor<S>>,
+    ) -> Result<&mut Self> {
+        let name = name.into();
+        if name == START || name == END {
+            return Err(Error::InvalidNode(format!(
+                "Cannot use reserved node names: {} or {}",
+                START, END
+            )));
+        }
+
+        if name.contains(PATH_SEPARATOR) {
+            return Err(Error::InvalidNode(format!(
+                "Node names cannot contain the path separator '{}': {}",
+                PATH_SEPARATOR, name
+            )));
+        }
+
+        if !self.node_map.contains_key(&name) {
+            let node_idx = self.graph.add_node(name.clone());
+            self.node_map.insert(name.clone(), node_idx);
+
+            // Link command nodes that declared this node as a destination before it existed
+            let sources: Vec<NodeIndex> = self
+                .destinations
+                .iter()
+                .filter(|(_, targets)| targets.contains(&name))
+                .map(|(source, _)| self.node_map[source])
+                .collect();
+            for source_idx in sources {
+                self.link_destination(source_idx, node_idx);
+            }
+        }
+
+        self.processors.insert(name, processor);
+        Ok(self)
+    }
+
+    /// Add a node whose processor returns a [`Command`]
+    ///
+    /// `destinations` declares every node the command may jump to, so the possible
+    /// edges are known without running the graph. Destinations may be added to the
+    /// graph after this node. When a command does not jump anywhere, the node's
+    /// regular edges are followed.
+    pub fn add_command_node(
+        &mut self,
+        name: impl Into<String>,
+        processor: impl CommandProcessor<S> + 'static,
+        destinations: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<&mut Self> {
+        let name = name.into();
+        let destinations: Vec<String> = destinations.into_iter().map(Into::into).collect();
+        // Checked before the node is added, so a failed call leaves the graph unchanged
+        if destinations.iter().any(|target| target == START) {
+            return Err(Error::InvalidEdge(format!(
+                "Cannot jump to the start node: {}",
+                START
+            )));
+        }
+        self.add_node(name.clone(), CommandNode(processor))?;
+
+        let from_idx = self.node_map[&name];
+        let declared = self.destinations.entry(name).or_default();
+        let mut new_targets = Vec::new();
+        for target in destinations {
+            if !declared.contains(&target) {
+                declared.push(target.clone());
+                new_targets.push(target);
+            }
+        }
+
+        for target in new_targets {
+            if let Some(&to_idx) = self.node_map.get(&target) {
+                self.link_destination(from_idx, to_idx);
+            }
+        }
+        Ok(self)
+    }
+
+    /// Set how a node joins parallel branches during parallel execution
+    ///
+    /// By default a node waits for every scheduled node that can still reach it.
+    pub fn set_join_mode(&mut self, node: impl Into<String>, mode: JoinMode) -> Result<&mut Self> {
+        let node = node.into();
+        if !self.processors.contains_key(&node) {
+            return Err(Error::InvalidNode(format!("Node not found: {}", node)));
+        }
+
+        self.join_modes.insert(node, mode);
+        Ok(self)
+    }
+
+    /// Retry failed attempts of a node according to the given policy
+    ///
+    /// Only the final attempt's error fails the run; failed attempts are reported as
+    /// [`GraphEvent::NodeRetrying`] events and are not checkpointed.
+    pub fn add_retry_policy(
+        &mut self,
+        node: impl Into<String>,
+        policy: RetryPolicy,
+    ) -> Result<&mut Self> {
+        let node = node.into();
+        if !self.processors.contains_key(&node) {
+            return Err(Error::InvalidNode(format!("Node not found: {}", node)));
+        }
+
+        self.retry_policies.insert(node, policy);
+        Ok(self)
+    }
+
+    /// Fail attempts of a node that take longer than `timeout` with [`Error::Timeout`]
+    ///
+    /// The timeout applies to every attempt when the node also has a retry policy.
+    pub fn add_node_timeout(
+        &mut self,
+        node: impl Into<String>,
+        timeout: Duration,
+    ) -> Result<&mut Self> {
+        let node = node.into();
+        if !self.processors.contains_key(&node) {
+            return Err(Error::InvalidNode(format!("Node not found: {}", node)));
+        }
+
+        self.node_timeouts.insert(node, timeout);
+        Ok(self)
+    }
+
+    /// Record a possible command jump as an edge so graph analyses can see it
+    fn link_destination(&mut self, from_idx: NodeIndex, to_idx: NodeIndex) {
+        let exists = self
+            .graph
+            .edges_connecting(from_idx, to_idx)
+            .any(|edge| matches!(edge.weight(), EdgeKind::Routed));
+        if !exists {
+            self.graph.add_edge(from_idx, to_idx, EdgeKind::Routed);
+        }
+    }
+
+    /// Look up the index of a node that an edge starts from
+    fn source_index(&self, from: &str) -> Result<NodeIndex> {
+        let from_idx = self
+            .node_map
+            .get(from)
+            .ok_or_else(|| Error::InvalidNode(format!("Source node not found: {}", from)))?;
+
+        if self.routers.contains_key(from) || self.fan_outs.contains_key(from) {
+            return Err(Error::InvalidEdge(format!(
+                "Node already routes its successors: {}",
+                from
+            )));
+        }
+
+        Ok(*from_idx)
+    }
+
+    /// Look up the index of a node that an edge points to
+    fn target_index(&self, to: &str) -> Result<NodeIndex> {
+        self.node_map
+            .get(to)
+            .copied()
+            .ok_or_else(|| Error::InvalidNode(format!("Target node not found: {}", to)))
+    }
+
+    /// Add an edge between nodes with an optional condition
+    pub fn add_edge(
+        &mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+        condition: Option<EdgeConditionFn<S>>,
+    ) -> Result<&mut Self> {
+        self.insert_edge(&from.into(), &to.into(), condition)?;
+        Ok(self)
+    }
+
+    /// Add an edge between nodes with an optional condition and a label describing it
+    ///
+    /// The label is shown on the edge in exports such as [`Graph::to_mermaid`].
+    pub fn add_labelled_edge(
+        &mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+        condition: Option<EdgeConditionFn<S>>,
+        label: impl Into<String>,
+    ) -> Result<&mut Self> {
+        let edge_idx = self.insert_edge(&from.into(), &to.into(), condition)?;
+        self.edge_labels.insert(edge_idx, label.into());
+        Ok(self)
+    }
+
+    /// Add an edge between nodes and return its index
+    fn insert_edge(
+        &mut self,
+        from: &str,
+        to: &str,
+        condition: Option<EdgeConditionFn<S>>,
+    ) -> Result<EdgeIndex> {
+        let from_idx = self.source_index(from)?;
+        let to_idx = self.target_index(to)?;
+
+        let kind = match condition {
+            Some(condition) => EdgeKind::Conditional(condition),
+            None => EdgeKind::Direct,
+        };
+
+        Ok(self.graph.add_edge(from_idx, to_idx, kind))
+    }
+
+    /// Route from a node with a function that returns the names of the next nodes
+    ///
+    /// `targets` declares every node the router may return, so the possible edges are
+    /// known without running the graph. A node with a router can have no other
+    /// outgoing edges. When a router returns several nodes, sequential execution runs
+    /// them one after another and parallel execution runs them together.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glint::graph::{Graph, END};
+    /// use glint::state::State;
+    /// use std::sync::Arc;
+    /// # use glint::graph::NodeProcessor;
+    /// # struct Classify;
+    /// # #[async_trait::async_trait]
+    /// # impl NodeProcessor<String> for Classify {
+    /// #     async fn process(&self, state: State<String>) -> glint::Result<State<String>> {
+    /// #         Ok(state)
+    /// #     }
+    /// # }
+    ///
+    /// let mut graph = Graph::<String>::new();
+    /// graph.add_node("classify", Classify)?;
+    /// graph.add_node("billing", Classify)?;
+    /// graph.add_start_edge("classify")?;
+    /// graph.add_conditional_edges(
+    ///     "classify",
+    ///     Arc::new(|state: &State<String>| {
+    ///         let target = if state.data.contains("invoice") { "billing" } else { END };
+    ///         Ok(vec![target.to_string()])
+    ///     }),
+    ///     ["billing", END],
+    /// )?;
+    /// graph.add_end_edge("billing")?;
+    /// # Ok::<(), glint::Error>(())
+    /// ```
+    pub fn add_conditional_edges(
+        &mut self,
+        from: impl Into<String>,
+        router: RouterFn<S>,
+        targets: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<&mut Self> {
+        let from = from.into();
+        let from_idx = self.source_index(&from)?;
+
+        if self.graph.edges(from_idx).next().is_some() {
+            return Err(Error::InvalidEdge(format!(
+                "Node already has outgoing edges and cannot also route: {}",
+                from
+            )));
+        }
+
+        let mut target_indices = Vec::new();
+        for target in targets {
+            let target = target.into();
+            let to_idx = self.target_index(&target)?;
+            if !target_indices.contains(&to_idx) {
+                target_indices.push(to_idx);
+            }
+        }
+
+        for to_idx in target_indices {
+            self.graph.add_edge(from_idx, to_idx, EdgeKind::Routed);
+        }
+        self.routers.insert(from, router);
+        Ok(self)
+    }
+
+    /// Connect a node to the start node
+    pub fn add_start_edge(&mut self, to: impl Into<String>) -> Result<&mut Self> {
+        self.add_edge(START, to, None)
+    }
+
+    /// Connect a node to the end node
+    pub fn add_end_edge(&mut self, from: impl Into<String>) -> Result<&mut Self> {
+        self.add_edge(from, END, None)
+    }
+
+    /// Check whether a scheduled node must wait for other scheduled nodes that can
+    /// still reach it
+    ///
+    /// Nodes on a common cycle do not wait for each other, so every superstep has at
+    /// least one node ready to run.
+    fn must_wait(&self, node_idx: NodeIndex, scheduled: &[NodeIndex]) -> bool {
+        if self.join_modes.get(&self.graph[node_idx]) == Some(&JoinMode::Any) {
+            return false;
+        }
+
+        scheduled.iter().any(|&other| {
+            other != node_idx
+                && has_path_connecting(&self.graph, other, node_idx, None)
+                && !has_path_connecting(&self.graph, node_idx, other, None)
+        })
+    }
+
+    /// Determine the nodes to run after `node_idx` given the state it produced
+    ///
+    /// Routers decide for nodes that have one. Otherwise, sequential execution takes
+    /// the first edge whose condition holds and parallel execution takes all of them.
+    fn successors(&self, node_idx: NodeIndex, state: &State<S>) -> Result<Vec<NodeIndex>> {
+        let node_name = self.graph.node_weight(node_idx).unwrap();
+
+        if let Some(router) = self.routers.get(node_name) {
+            let mut next = Vec::new();
+            for target in router(state)? {
+                let target_idx = self
+                    .graph
+                    .edges(node_idx)
+                    .find(|edge| self.graph.node_weight(edge.target()) == Some(&target))
+                    .map(|edge| edge.target())
+                    .ok_or_else(|| {
+                        Error::InvalidEdge(format!(
+                            "Router of node {} returned undeclared target: {}",
+                            node_name, target
+                        ))
+                    })?;
+                next.push(target_idx);
+            }
+            return Ok(next);
+        }
+
+        // petgraph yields the most recently added edge first
+        let mut next = Vec::new();
+        for edge in self.graph.edges(node_idx) {
+            let taken = match edge.weight() {
+                EdgeKind::Direct => true,
+                EdgeKind::Conditional(condition) => condition(state)?,
+                EdgeKind::Routed | EdgeKind::Error => false,
+            };
+
+            if taken {
+                next.push(edge.target());
+                if self.execution_strategy == ExecutionStrategy::Sequential {
+                    break;
+                }
+            }
+        }
+        Ok(next)
+    }
+
+    /// Determine the nodes to run after `node_idx`, honouring a command's jump targets
+    fn next_nodes(
+        &self,
+        node_idx: NodeIndex,
+        state: &State<S>,
+        goto: &[String],
+    ) -> Result<Vec<NodeIndex>> {
+        let _span = tracing::debug_span!("edges", node = %self.graph[node_idx]).entered();
+        if goto.is_empty() {
+            return self.successors(node_idx, state);
+        }
+
+        let node_name = self.graph.node_weight(node_idx).unwrap();
+        let declared = self.destinations.get(node_name);
+        goto.iter()
+            .map(|target| {
+                let declared = declared.is_some_and(|targets| targets.contains(target));
+                if !declared && self.error_target(node_idx) != Some(target.as_str()) {
+                    return Err(Error::InvalidEdge(format!(
+                        "Node {} jumped to undeclared destination: {}",
+                        node_name, target
+                    )));
+                }
+                self.target_index(target)
+            })
+            .collect()
+    }
+
+    /// Merge the results of parallel branches into a single state
+    ///
+    /// The graph's reducer merges the states of branches that returned whole
+    /// states. The updates of branches run by update nodes are then folded into
+    /// the result in scheduling order, with their metadata merged like the
+    /// [`ChannelReducer`](crate::state::ChannelReducer) does.
+    fn merge_states(&self, base: &State<S>, branches: Vec<Command<S>>) -> Result<State<S>> {
+        if branches.is_empty() {
+            return Err(Error::State("No states to merge".to_string()));
+        }
+
+        let (updated, whole): (Vec<Command<S>>, Vec<Command<S>>) = branches
+            .into_iter()
+            .partition(|command| command.update.is_some());
+        let mut merged = if whole.is_empty() {
+            base.clone()
+        } else {
+            let states = whole.into_iter().map(|command| command.state).collect();
+            self.reducer.reduce(base, states)?
+        };
+
+        let mut written = HashSet::new();
+        for command in updated {
+            if let Some(update) = &command.update {
+                update.merge(&mut merged.data, &mut written)?;
+            }
+            for (key, value) in command.state.metadata {
+                if base.metadata.get(&key) != Some(&value) {
+                    merged.metadata.insert(key, value);
+                }
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Execute the graph with the given initial state
+    pub async fn execute(&self, initial_state: State<S>) -> Result<State<S>> {
+        self.execute_in(initial_state, &RunContext::root(None))
+            .await
+    }
+
+    /// Execute the graph with the given initial state and run configuration
+    ///
+    /// When the configuration has a checkpointer, a checkpoint of the input and of the
+    /// state after every node is saved under the configured thread, so a run that
+    /// did not finish can be continued with [`Graph::resume`].
+    pub async fn execute_with_config(
+        &self,
+        initial_state: State<S>,
+        config: &RunConfig<S>,
+    ) -> Result<State<S>> {
+        let run = config.run_context()?;
+        self.execute_in(initial_state, &run).await
+    }
+
+    /// Execute the graph from START as part of the given run
+    async fn execute_in(&self, initial_state: State<S>, run: &RunContext<S>) -> Result<State<S>> {
+        let start_idx = *self.node_map.get(START).unwrap();
+
+        let pending = self.successors(start_idx, &initial_state)?;
+        if pending.is_empty() {
+            return Err(Error::Graph(format!("No valid edges from node: {}", START)));
+        }
+
+        self.emit_edges(run, start_idx, &pending);
+        run.checkpoint(&[START], &initial_state, &self.node_names(&pending), None)?;
+        self.execute_from(initial_state, pending, run).await
+    }
+
+    /// Execute the graph starting with the given pending nodes
+    async fn execute_from(
+        &self,
+        state: State<S>,
+        pending: Vec<NodeIndex>,
+        run: &RunContext<S>,
+    ) -> Result<State<S>> {
+        let execution = async {
+            match self.execution_strategy {
+                ExecutionStrategy::Sequential => self.execute_sequential(state, pending, run).await,
+                ExecutionStrategy::Parallel => self.execute_parallel(state, pending, run).await,
+            }
+        };
+        let execution = run.limit(execution);
+        if run.is_nested() {
+            return execution.await;
+        }
+
+        // Trace the whole top-level run, including the nodes of nested runs
+        let callbacks = run.callbacks().merged(&self.callbacks);
+        let run_id = run.run_id();
+        let thread_id = run
+            .checkpointing()
+            .map(|checkpointing| checkpointing.thread_id());
+        let span = tracing::info_span!("graph_run", run_id, thread_id);
+        let scope = TraceScope {
+            run_id: run_id.to_string(),
+            node: None,
+        };
+        let traced = async {
+            callbacks.on_run_start(run_id, thread_id);
+            let started = Instant::now();
+            let result = execution.await;
+            callbacks.on_run_end(run_id, started.elapsed(), result.as_ref().err());
+            result
+        };
+        trace::in_scope(scope, traced).instrument(span).await
+    }
+
+    /// Get the names of scheduled nodes, leaving out END
+    fn node_names<'a>(&self, nodes: impl IntoIterator<Item = &'a NodeIndex>) -> Vec<&str> {
+        let end_idx = self.node_map[END];
+        nodes
+            .into_iter()
+            .filter(|&&node_idx| node_idx != end_idx)
+            .map(|&node_idx| self.graph[node_idx].as_str())
+            .collect()
+    }
+
+    /// Report the edges taken from a node to the run's event stream
+    fn emit_edges(&self, run: &RunContext<S>, from: NodeIndex, to: &[NodeIndex]) {
+        for &to_idx in to {
+            run.emit(|| GraphEvent::EdgeTaken {
+                from: run.node_path(&self.graph[from]),
+                to: run.node_path(&self.graph[to_idx]),
+            });
+        }
+    }
+
+    /// Run a single node and tag any error it returns with the node's path
+    ///
+    /// Returns an [`Error::Interrupted`] error instead when the run stops before the
+    /// node. A node with an error route returns a jump to its error handler instead
+    /// of failing.
+    async fn run_node(
+        &self,
+        node_idx: NodeIndex,
+        state: State<S>,
+        run: &RunContext<S>,
+        step: usize,
+    ) -> Result<Command<S>> {
+        let node_name = self.graph.node_weight(node_idx).unwrap();
+        let processor = self
+            .processors
+            .get(node_name)
+            .ok_or_else(|| Error::Graph(format!("No processor found for node: {}", node_name)))?;
+
+        if let Some(interrupt) = self.check_interrupt(node_name, InterruptKind::Before, run) {
+            return Err(interrupt.into());
+        }
+
+        let ctx = run.node_context(node_name, step, &self.callbacks);
+        let state = Self::handler_input(state, node_name);
+
+        // The handler of a failed node gets the node's input
+        let fallback = self
+            .error_target(node_idx)
+            .map(|target| (target, state.clone()));
+
+        let span = tracing::info_span!(
+            "node",
+            run_id = ctx.run_id(),
+            thread_id = ctx.thread_id(),
+            node = ctx.path(),
+            step
+        );
+        let scope = TraceScope {
+            run_id: ctx.run_id().to_string(),
+            node: Some(ctx.path().to_string()),
+        };
+        let processing = self.process_node(node_name, processor.as_ref(), state, &ctx);
+        let result = trace::in_scope(scope, processing).instrument(span).await;
+
+        match (result, fallback) {
+            (Ok(mut command), _) => {
+                // A routed error is only for the node it was routed to, and a resume
+                // value only for the nodes run first after resuming
+                command.state.metadata.remove(ERROR_KEY);
+                command.state.metadata.remove(RESUME_KEY);
+                Ok(command)
+            }
+            (Err(e), Some((target, input))) if !is_control_flow(&e) => {
+                Ok(Self::recover(input, &e, target, &ctx))
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Run a node's processor, reporting its progress to the run's event stream and
+    /// callback handlers
+    async fn process_node(
+        &self,
+        node_name: &str,
+        processor: &dyn NodeProcessor<S>,
+        state: State<S>,
+        ctx: &NodeContext<S>,
+    ) -> Result<Command<S>> {
+        ctx.emit(|| GraphEvent::NodeStarted {
+            node: ctx.path().to_string(),
+            step: ctx.step(),
+        });
+        ctx.callbacks()
+            .on_node_start(ctx.path(), ctx.step(), &state.data);
+
+        let started = Instant::now();
+        let result = self.process_cached(node_name, processor, state, ctx).await;
+        let command = match result {
+            Ok(command) => command,
+            // Interrupts pause the run rather than fail the node
+            Err(e @ Error::Interrupted(_)) => return Err(e),
+            Err(e) => {
+                ctx.callbacks().on_node_error(ctx.path(), &e);
+                return Err(e.at_node(ctx.path()));
+            }
+        };
+
+        ctx.callbacks()
+            .on_node_end(ctx.path(), &command.state.data, started.elapsed());
+        ctx.emit(|| GraphEvent::NodeFinished {
+            node: ctx.path().to_string(),
+            state: command.state.clone(),
+        });
+        Ok(command)
+    }
+
+    /// Execute the graph sequentially
+    ///
+    /// Nodes may be revisited, so loops such as "call model -> call tool -> call model"
+    /// are allowed. The run is bounded by `max_steps` node executions.
+    async fn execute_sequential(
+        &self,
+        initial_state: State<S>,
+        pending: Vec<NodeIndex>,
+        run: &RunContext<S>,
+    ) -> Result<State<S>> {
+        let end_idx = *self.node_map.get(END).unwrap();
+        let mut current_state = initial_state;
+        let mut trail = Vec::new();
+
+        // Nodes waiting to run, in order
+        let mut pending = VecDeque::from(pending);
+
+        // Execute until every pending node is done or the step limit is exceeded
+        while let Some(current_node) = pending.pop_front() {
+            if current_node == end_idx {
+                continue;
+            }
+
+            let node_name = self.graph.node_weight(current_node).unwrap();
+
+            trail.push(run.node_path(node_name));
+            if trail.len() > self.max_steps {
+                return Err(Error::RecursionLimit {
+                    limit: self.max_steps,
+                    trail,
+                });
+            }
+
+            let input = run.saves_interrupts().then(|| current_state.clone());
+            let interrupted = |e| {
+                let mut next = vec![node_name.as_str()];
+                next.extend(self.node_names(&pending));
+                self.save_interrupt(run, e, &[node_name], input.as_ref(), &next)
+            };
+            let mut routed = Self::routed_errors(&current_state);
+            routed.remove(node_name.as_str());
+            let mut command = self
+                .run_node(current_node, current_state, run, trail.len())
+                .await
+                .map_err(interrupted)?;
+            routed.extend(command.routed_error.take());
+
+            // Find next nodes based on the command, edge conditions, the node's router
+            // or its fan-out
+            let (mut state, next_nodes) = self
+                .advance(current_node, command.state, &command.goto, run, trail.len())
+                .await
+                .map_err(interrupted)?;
+            routed.extend(Self::take_routed_errors(&mut state));
+            Self::keep_routed_errors(&mut state, routed);
+            current_state = state;
+            if next_nodes.is_empty() {
+                return Err(Error::Graph(format!(
+                    "No valid edges from node: {}",
+                    node_name
+                )));
+            }
+
+            pending.extend(next_nodes);
+            let interrupt = self.check_interrupt(node_name, InterruptKind::After, run);
+            run.checkpoint(
+                &[node_name],
+                &current_state,
+                &self.node_names(&pending),
+                interrupt.as_ref(),
+            )?;
+            if let Some(interrupt) = interrupt {
+                return Err(interrupt.into());
+            }
+        }
+
+        // Errors routed to handlers that never ran are dropped
+        Self::take_routed_errors(&mut current_state);
+        Ok(current_state)
+    }
+
+    /// Execute the graph in supersteps, running the ready nodes of each step in
+    /// parallel
+    ///
+    /// A scheduled node waits while another scheduled node can still reach it, so a
+    /// fan-in node runs once, after all of its active upstream branches finished,
+    /// unless its [`JoinMode`] is `Any`. The states of the nodes of a step are merged
+    /// with the graph's reducer in scheduling order. The run is bounded by
+    /// `max_steps` supersteps.
+    async fn execute_parallel(
+        &self,
+        initial_state: State<S>,
+        pending: Vec<NodeIndex>,
+        run: &RunContext<S>,
+    ) -> Result<State<S>> {
+        let end_idx = *self.node_map.get(END).unwrap();
+        let mut current_state = initial_state;
+        let mut step_count = 0;
+        let mut trail = Vec::new();
+        let mut reached_end = false;
+
+        // Nodes scheduled for the next superstep, each once
+        let mut scheduled = Vec::new();
+        for node_idx in pending {
+            if !scheduled.contains(&node_idx) {
+                scheduled.push(node_idx);
+            }
+        }
+
+        loop {
+            if scheduled.contains(&end_idx) {
+                reached_end = true;
+                scheduled.retain(|&node_idx| node_idx != end_idx);
+            }
+            if scheduled.is_empty() {
+                break;
+            }
+
+            step_count += 1;
+            if step_count > self.max_steps {
+                return Err(Error::RecursionLimit {
+                    limit: self.max_steps,
+                    trail,
+                });
+            }
+
+            // Join nodes with active upstream branches wait for a later step
+            let (ready, waiting): (Vec<NodeIndex>, Vec<NodeIndex>) = scheduled
+                .iter()
+                .partition(|&&node_idx| !self.must_wait(node_idx, &scheduled));
+            let names = self.node_names(&ready);
+            trail.extend(names.iter().map(|name| run.node_path(name)));
+
+            // Resuming an interrupted step runs the whole step again
+            let input = run.saves_interrupts().then(|| current_state.clone());
+            let mut rerun = names.clone();
+            rerun.extend(self.node_names(&waiting));
+            let interrupted = |e| self.save_interrupt(run, e, &names, input.as_ref(), &rerun);
+
+            // Run the nodes of the step; results come back in scheduling order
+            let mut routed = Self::routed_errors(&current_state);
+            let inputs = ready
+                .iter()
+                .map(|&node_idx| (node_idx, current_state.clone()))
+                .collect();
+            let results = self
+                .run_nodes(inputs, run, step_count, None)
+                .await
+                .map_err(interrupted)?;
+
+            let mut jumps = Vec::with_capacity(results.len());
+            let mut branches = Vec::with_capacity(results.len());
+            for (node_idx, mut command) in results {
+                routed.remove(&self.graph[node_idx]);
+                routed.extend(command.routed_error.take());
+                jumps.push((node_idx, std::mem::take(&mut command.goto)));
+                branches.push(command);
+            }
+            current_state = if branches.len() == 1 {
+                branches.pop().unwrap().state
+            } else {
+                self.merge_states(&current_state, branches)?
+            };
+            // Routed errors are tracked apart from the merged state, as reducers
+            // may keep the entries of handlers that ran, and reducers may keep the
+            // resume value the step's nodes consumed
+            Self::take_routed_errors(&mut current_state);
+            current_state.metadata.remove(RESUME_KEY);
+
+            let mut next = waiting;
+            for (node_idx, goto) in &jumps {
+                let (mut state, next_nodes) = self
+                    .advance(*node_idx, current_state, goto, run, step_count)
+                    .await
+                    .map_err(interrupted)?;
+                routed.extend(Self::take_routed_errors(&mut state));
+                current_state = state;
+                for next_idx in next_nodes {
+                    if !next.contains(&next_idx) {
+                        next.push(next_idx);
+                    }
+                }
+            }
+            Self::keep_routed_errors(&mut current_state, routed);
+
+            let interrupt = names
+                .iter()
+                .find_map(|name| self.check_interrupt(name, InterruptKind::After, run));
+            run.checkpoint(
+                &names,
+                &current_state,
+                &self.node_names(&next),
+                interrupt.as_ref(),
+            )?;
+            if let Some(interrupt) = interrupt {
+                return Err(interrupt.into());
+            }
+
+            scheduled = next;
+        }
+
+        if reached_end {
+            Self::take_routed_errors(&mut current_state);
+            Ok(current_state)
+        } else {
+            Err(Error::Graph(
+                "Graph execution completed without reaching END node".to_string(),
+            ))
+        }
+    }
+}
+
+/// Builder for constructing a graph using a fluent interface
+pub struct GraphBuilder<S: StateValue> {
+    graph: Graph<S>,
+}
+
+impl<S: StateValue> Default for GraphBuilder<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: StateValue> GraphBuilder<S> {
+    /// Create a new graph builder
+    pub fn new() -> Self {
+        Self {
+            graph: Graph::new(),
+        }
+    }
+
+    /// Set the execution strategy
+    pub fn with_execution_strategy(mut self, strategy: ExecutionStrategy) -> Self {
+        self.graph.execution_strategy = strategy;
+        self
+    }
+
+    /// Set the maximum number of steps before a run is aborted
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.graph.max_steps = max_steps;
+        self
+    }
+
+    /// Set the reducer used to merge the states of parallel branches
+    pub fn with_reducer(mut self, reducer: impl StateReducer<S> + 'static) -> Self {
+        self.graph.reducer = Arc::new(reducer);
+        self
+    }
+
+    /// Register a handler called as the nodes of every run of the graph execute
+    pub fn with_callback(mut self, handler: Arc<dyn CallbackHandler>) -> Self {
+        self.graph = self.graph.with_callback(handler);
+        self
+    }
+
+    /// Stop runs before any of the given nodes run
+    ///
+    /// The run saves a checkpoint and returns [`RunOutcome::Interrupted`] from
+    /// [`Graph::invoke`]; continue it with [`Graph::resume_with`].
+    pub fn interrupt_before(mut self, nodes: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.graph = self.graph.with_interrupt_before(nodes);
+        self
+    }
+
+    /// Stop runs after any of the given nodes ran
+    pub fn interrupt_after(mut self, nodes: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.graph = self.graph.with_interrupt_after(nodes);
+        self
+    }
+
+    /// Add a node to the graph
+    pub fn with_node(
+        mut self,
+        name: impl Into<String>,
+        processor: impl NodeProcessor<S> + 'static,
+    ) -> Result<Self> {
+        self.graph.add_node(name, processor)?;
+        Ok(self)
+    }
+
+    /// Add an edge between nodes
+    pub fn with_edge(
+        mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+        condition: Option<EdgeConditionFn<S>>,
+    ) -> Result<Self> {
+        self.graph.add_edge(from, to, condition)?;
+        Ok(self)
+    }
+
+    /// Add an edge between nodes with a label describing it
+    pub fn with_labelled_edge(
+        mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+        condition: Option<EdgeConditionFn<S>>,
+        label: impl Into<String>,
+    ) -> Result<Self> {
+        self.graph.add_labelled_edge(from, to, condition, label)?;
+        Ok(self)
+    }
+
+    /// Route from a node with a function that returns the names of the next nodes
+    pub fn with_conditional_edges(
+        mut self,
+        from: impl Into<String>,
+        router: RouterFn<S>,
+        targets: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<Self> {
+        self.graph.add_conditional_edges(from, router, targets)?;
+        Ok(self)
+    }
+
+    /// Set the maximum number of nodes that run at once in a superstep or fan-out
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Result<Self> {
+        self.graph.set_max_concurrency(max_concurrency)?;
+        Ok(self)
+    }
+
+    /// Limit how many nodes tagged with `tag` run at once
+    pub fn with_concurrency_limit(mut self, tag: impl Into<String>, limit: usize) -> Result<Self> {
+        self.graph.set_concurrency_limit(tag, limit)?;
+        Ok(self)
+    }
+
+    /// Tag a node so that it counts against the concurrency limit of `tag`
+    pub fn with_concurrency_tag(
+        mut self,
+        node: impl Into<String>,
+        tag: impl Into<String>,
+    ) -> Result<Self> {
+        self.graph.set_concurrency_tag(node, tag)?;
+        Ok(self)
+    }
+
+    /// Set how a node joins parallel branches during parallel execution
+    pub fn with_join_mode(mut self, node: impl Into<String>, mode: JoinMode) -> Result<Self> {
+        self.graph.set_join_mode(node, mode)?;
+        Ok(self)
+    }
+
+    /// Retry failed attempts of a node according to the given policy
+    pub fn with_retry_policy(
+        mut self,
+        node: impl Into<String>,
+        policy: RetryPolicy,
+    ) -> Result<Self> {
+        self.graph.add_retry_policy(node, policy)?;
+        Ok(self)
+    }
+
+    /// Fail attempts of a node that take longer than `timeout`
+    pub fn with_node_timeout(mut self, node: impl Into<String>, timeout: Duration) -> Result<Self> {
+        self.graph.add_node_timeout(node, timeout)?;
+        Ok(self)
+    }
+
+    /// Cache the results of a node according to the given policy
+    pub fn with_cache_policy(
+        mut self,
+        node: impl Into<String>,
+        policy: CachePolicy<S>,
+    ) -> Result<Self> {
+        self.graph.add_cache_policy(node, policy)?;
+        Ok(self)
+    }
+
+    /// Route the failures of a node to another node
+    pub fn with_error_edge(
+        mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+    ) -> Result<Self> {
+        self.graph.add_error_edge(from, to)?;
+        Ok(self)
+    }
+
+    /// Route the failures of every node without an error edge to a handler node
+    pub fn with_error_handler(mut self, node: impl Into<String>) -> Result<Self> {
+        self.graph.set_error_handler(node)?;
+        Ok(self)
+    }
+
+    /// Fan out from a node into a number of branches decided at runtime
+    pub fn with_fan_out(
+        mut self,
+        from: impl Into<String>,
+        fan_out: FanOutFn<S>,
+        targets: impl IntoIterator<Item = impl Into<String>>,
+        max_concurrency: Option<usize>,
+    ) -> Result<Self> {
+        self.graph
+            .add_fan_out(from, fan_out, targets, max_concurrency)?;
+        Ok(self)
+    }
+
+    /// Add a node whose processor returns a [`Command`]
+    pub fn with_command_node(
+        mut self,
+        name: impl Into<String>,
+        processor: impl CommandProcessor<S> + 'static,
+        destinations: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<Self> {
+        self.graph.add_command_node(name, processor, destinations)?;
+        Ok(self)
+    }
+
+    /// Connect a node to the start node
+    pub fn with_start_edge(mut self, to: impl Into<String>) -> Result<Self> {
+        self.graph.add_start_edge(to)?;
+        Ok(self)
+    }
+
+    /// Connect a node to the end node
+    pub fn with_end_edge(mut self, from: impl Into<String>) -> Result<Self> {
+        self.graph.add_end_edge(from)?;
+        Ok(self)
+    }
+
+    /// Build the graph without checking its structure
+    pub fn build(self) -> Graph<S> {
+        self.graph
+    }
+
+    /// Build the graph after checking its structure with [`Graph::validate`]
+    pub fn compile(self) -> Result<Graph<S>> {
+        self.graph.validate()?;
+        Ok(self.graph)
+    }
+}
+
+/// 单元测试模块，覆盖核心功能：
+/// - 串行节点流转
+/// - 条件边分支
+/// - 并行节点
+/// - 错误处理
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::{CheckpointStore, MemoryCheckpointStore};
+    use crate::schema::{Message, MessageRole};
+    use crate::state::{ChannelReducer, Reducer};
+    use serde::{Deserialize, Serialize};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Debug, Clone)]
+    struct TestState {
+        counter: Arc<AtomicUsize>,
+        messages: Vec<Message>,
+    }
+
+    impl StateValue for TestState {}
+
+    struct CounterNode {
+        increment: usize,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<TestState> for CounterNode {
+        async fn process(&self, state: State<TestState>) -> Result<State<TestState>> {
+            state
+                .data
+                .counter
+                .fetch_add(self.increment, Ordering::SeqCst);
+            Ok(state)
+        }
+    }
+
+    struct MessageNode {
+        message: String,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<TestState> for MessageNode {
+        async fn process(&self, mut state: State<TestState>) -> Result<State<TestState>> {
+            state
+                .data
+                .messages
+                .push(Message::new(MessageRole::User, self.message.clone()));
+            Ok(state)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sequential_execution() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let initial_state = State::new(TestState {
+            counter: counter.clone(),
+            messages: vec![],
+        });
+
+        let graph = GraphBuilder::new()
+            .with_node("counter1", CounterNode { increment: 1 })
+            .unwrap()
+            .with_node("counter2", CounterNode { increment: 2 })
+            .unwrap()
+            .with_node(
+                "message",
+                MessageNode {
+                    message: "test".to_string(),
+                },
+            )
+            .unwrap()
+            .with_start_edge("counter1")
+            .unwrap()
+            .with_edge("counter1", "counter2", None)
+            .unwrap()
+            .with_edge("counter2", "message", None)
+            .unwrap()
+            .with_end_edge("message")
+            .unwrap()
+            .build();
+
+        let final_state = graph.execute(initial_state).await.unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 3);
+        assert_eq!(final_state.data.messages.len(), 1);
+        assert_eq!(final_state.data.messages[0].content, "test");
+    }
+
+    #[tokio::test]
+    async fn test_parallel_execution() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let initial_state = State::new(TestState {
+            counter: counter.clone(),
+            messages: vec![],
+        });
+
+        let graph = GraphBuilder::new()
+            .with_node("counter1", CounterNode { increment: 1 })
+            .unwrap()
+            .with_node("counter2", CounterNode { increment: 2 })
+            .unwrap()
+            .with_node(
+                "message",
+                MessageNode {
+                    message: "test".to_string(),
+                },
+            )
+            .unwrap()
+            .with_start_edge("counter1")
+            .unwrap()
+            .with_start_edge("counter2")
+            .unwrap()
+            .with_edge("counter1", "message", None)
+            .unwrap()
+            .with_edge("counter2", "message", None)
+            .unwrap()
+            .with_end_edge("message")
+            .unwrap()
+            .with_execution_strategy(ExecutionStrategy::Parallel)
+            .build();
+
+        let final_state = graph.execute(initial_state).await.unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 3);
+        assert_eq!(final_state.data.messages.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_conditional_edges() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let initial_state = State::new(TestState {
+            counter: counter.clone(),
+            messages: vec![],
+        });
+
+        let condition =
+            Arc::new(|state: &State<TestState>| Ok(state.data.counter.load(Ordering::SeqCst) >= 1));
+
+        let graph = GraphBuilder::new()
+            .with_node("counter1", CounterNode { increment: 1 })
+            .unwrap()
+            .with_node("counter2", CounterNode { increment: 2 })
+            .unwrap()
+            .with_node(
+                "message",
+                MessageNode {
+                    message: "test".to_string(),
+                },
+            )
+            .unwrap()
+            .with_start_edge("counter1")
+            .unwrap()
+            .with_edge("counter1", "counter2", Some(condition))
+            .unwrap()
+            .with_edge("counter2", "message", None)
+            .unwrap()
+            .with_end_edge("message")
+            .unwrap()
+            .build();
+
+        let final_state = graph.execute(initial_state).await.unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 3);
+        assert_eq!(final_state.data.messages.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_bounded_loop() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let initial_state = State::new(TestState {
+            counter: counter.clone(),
+            messages: vec![],
+        });
+
+        let done =
+            Arc::new(|state: &State<TestState>| Ok(state.data.counter.load(Ordering::SeqCst) >= 6));
+        let again =
+            Arc::new(|state: &State<TestState>| Ok(state.data.counter.load(Ordering::SeqCst) < 6));
+
+        let graph = GraphBuilder::new()
+            .with_node("model", CounterNode { increment: 1 })
+            .unwrap()
+            .with_node("tool", CounterNode { increment: 2 })
+            .unwrap()
+            .with_start_edge("model")
+            .unwrap()
+            .with_edge("model", "tool", None)
+            .unwrap()
+            .with_edge("tool", END, Some(done))
+            .unwrap()
+            .with_edge("tool", "model", Some(again))
+            .unwrap()
+            .build();
+
+        graph.execute(initial_state).await.unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn test_recursion_limit() {
+        let graph = GraphBuilder::new()
+            .with_node("node1", CounterNode { increment: 1 })
+            .unwrap()
+            .with_node("node2", CounterNode { increment: 2 })
+            .unwrap()
+            .with_start_edge("node1")
+            .unwrap()
+            .with_edge("node1", "node2", None)
+            .unwrap()
+            .with_edge("node2", "node1", None)
+            .unwrap()
+            .with_max_steps(5)
+            // Note: No end edge - this creates a true cycle with no escape
+            .build();
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let initial_state = State::new(TestState {
+            counter: counter.clone(),
+            messages: vec![],
+        });
+
+        let result = graph.execute(initial_state).await;
+        match result.unwrap_err() {
+            Error::RecursionLimit { limit, trail } => {
+                assert_eq!(limit, 5);
+                assert_eq!(
+                    trail,
+                    vec!["node1", "node2", "node1", "node2", "node1", "node2"]
+                );
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 7);
+    }
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    struct ReduceState {
+        documents: Vec<String>,
+        searches: i64,
+        answer: String,
+    }
+
+    impl StateValue for ReduceState {}
+
+    struct SearchNode {
+        document: String,
+        answer: Option<String>,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<ReduceState> for SearchNode {
+        async fn process(&self, mut state: State<ReduceState>) -> Result<State<ReduceState>> {
+            state.data.documents.push(self.document.clone());
+            state.data.searches += 1;
+            if let Some(answer) = &self.answer {
+                state.data.answer = answer.clone();
+            }
+            Ok(state)
+        }
+    }
+
+    fn search_graph(answers: [Option<&str>; 2]) -> Result<Graph<ReduceState>> {
+        Ok(GraphBuilder::new()
+            .with_node(
+                "web",
+                SearchNode {
+                    document: "web".to_string(),
+                    answer: answers[0].map(str::to_string),
+                },
+            )?
+            .with_node(
+                "docs",
+                SearchNode {
+                    document: "docs".to_string(),
+                    answer: answers[1].map(str::to_string),
+                },
+            )?
+            .with_start_edge("web")?
+            .with_start_edge("docs")?
+            .with_end_edge("web")?
+            .with_end_edge("docs")?
+            .with_execution_strategy(ExecutionStrategy::Parallel)
+            .with_reducer(
+                ChannelReducer::new()
+                    .with_field("documents", Reducer::Append)
+                    .with_field("searches", Reducer::Add),
+            )
+            .build())
+    }
+
+    fn join_graph(mode: JoinMode) -> Result<Graph<ReduceState>> {
+        let search = |document: &str| SearchNode {
+            document: document.to_string(),
+            answer: None,
+        };
+        Ok(GraphBuilder::new()
+            .with_node("web", search("web"))?
+            .with_node("docs", search("docs"))?
+            .with_node("review", search("review"))?
+            .with_node("answer", search("answer"))?
+            .with_join_mode("answer", mode)?
+            .with_start_edge("web")?
+            .with_start_edge("docs")?
+            .with_edge("docs", "review", None)?
+            .with_edge("web", "answer", None)?
+            .with_edge("review", "answer", None)?
+            .with_end_edge("answer")?
+            .with_execution_strategy(ExecutionStrategy::Parallel)
+            .with_reducer(
+                ChannelReducer::new()
+                    .with_field("documents", Reducer::Append)
+                    .with_field("searches", Reducer::Add),
+            )
+            .build())
+    }
+
+    #[tokio::test]
+    async fn test_join_waits_for_all_branches() {
+        let graph = join_graph(JoinMode::All).unwrap();
+
+        let final_state = graph
+            .execute(State::new(ReduceState::default()))
+            .await
+            .unwrap();
+        let documents = &final_state.data.documents;
+        assert_eq!(documents.len(), 4);
+        assert_eq!(documents.last().map(String::as_str), Some("answer"));
+        assert_eq!(final_state.data.searches, 4);
+    }
+
+    #[tokio::test]
+    async fn test_join_any_runs_per_branch() {
+        let graph = join_graph(JoinMode::Any).unwrap();
+
+        let final_state = graph
+            .execute(State::new(ReduceState::default()))
+            .await
+            .unwrap();
+        let answers = final_state
+            .data
+            .documents
+            .iter()
+            .filter(|document| *document == "answer")
+            .count();
+        assert_eq!(answers, 2);
+    }
+
+    #[tokio::test]
+    async fn test_parallel_loop() {
+        let again = Arc::new(|state: &State<ReduceState>| Ok(state.data.searches < 3));
+        let done = Arc::new(|state: &State<ReduceState>| Ok(state.data.searches >= 3));
+        let graph = GraphBuilder::new()
+            .with_node(
+                "search",
+                SearchNode {
+                    document: "page".to_string(),
+                    answer: None,
+                },
+            )
+            .unwrap()
+            .with_start_edge("search")
+            .unwrap()
+            .with_edge("search", "search", Some(again))
+            .unwrap()
+            .with_edge("search", END, Some(done))
+            .unwrap()
+            .with_execution_strategy(ExecutionStrategy::Parallel)
+            .build();
+
+        let final_state = graph
+            .execute(State::new(ReduceState::default()))
+            .await
+            .unwrap();
+        assert_eq!(final_state.data.searches, 3);
+    }
+
+    #[tokio::test]
+    async fn test_channel_reducer_merges_branches() {
+        let graph = search_graph([Some("from web"), None]).unwrap();
+
+        let final_state = graph
+            .execute(State::new(ReduceState::default()))
+            .await
+            .unwrap();
+        let mut documents = final_state.data.documents.clone();
+        documents.sort();
+        assert_eq!(documents, vec!["docs", "web"]);
+        assert_eq!(final_state.data.searches, 2);
+        assert_eq!(final_state.data.answer, "from web");
+    }
+
+    #[tokio::test]
+    async fn test_channel_reducer_conflict() {
+        let graph = search_graph([Some("from web"), Some("from docs")]).unwrap();
+
+        let result = graph.execute(State::new(ReduceState::default())).await;
+        assert!(matches!(result.unwrap_err(), Error::StateConflict(_)));
+    }
+
+    fn route_graph(router: RouterFn<TestState>) -> Result<Graph<TestState>> {
+        Ok(GraphBuilder::new()
+            .with_node("classify", CounterNode { increment: 1 })?
+            .with_node("small", CounterNode { increment: 10 })?
+            .with_node("large", CounterNode { increment: 100 })?
+            .with_start_edge("classify")?
+            .with_conditional_edges("classify", router, ["small", "large", END])?
+            .with_end_edge("small")?
+            .with_end_edge("large")?
+            .build())
+    }
+
+    #[tokio::test]
+    async fn test_router_edges() {
+        let graph = route_graph(Arc::new(|state: &State<TestState>| {
+            let target = if state.data.counter.load(Ordering::SeqCst) > 5 {
+                "large"
+            } else {
+                "small"
+            };
+            Ok(vec![target.to_string()])
+        }))
+        .unwrap();
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let initial_state = State::new(TestState {
+            counter: counter.clone(),
+            messages: vec![],
+        });
+        graph.execute(initial_state).await.unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 11);
+
+        let counter = Arc::new(AtomicUsize::new(10));
+        let initial_state = State::new(TestState {
+            counter: counter.clone(),
+            messages: vec![],
+        });
+        graph.execute(initial_state).await.unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 111);
+    }
+
+    #[tokio::test]
+    async fn test_router_multiple_targets() {
+        let graph = route_graph(Arc::new(|_: &State<TestState>| {
+            Ok(vec!["small".to_string(), "large".to_string()])
+        }))
+        .unwrap();
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let initial_state = State::new(TestState {
+            counter: counter.clone(),
+            messages: vec![],
+        });
+        graph.execute(initial_state).await.unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 111);
+    }
+
+    #[tokio::test]
+    async fn test_router_undeclared_target() {
+        let graph = route_graph(Arc::new(|_: &State<TestState>| {
+            Ok(vec!["missing".to_string()])
+        }))
+        .unwrap();
+
+        let initial_state = State::new(TestState {
+            counter: Arc::new(AtomicUsize::new(0)),
+            messages: vec![],
+        });
+        let result = graph.execute(initial_state).await;
+        assert!(matches!(result.unwrap_err(), Error::InvalidEdge(_)));
+
+        let mixed = GraphBuilder::<TestState>::new()
+            .with_node("classify", CounterNode { increment: 1 })
+            .unwrap()
+            .with_end_edge("classify")
+            .unwrap()
+            .with_conditional_edges(
+                "classify",
+                Arc::new(|_: &State<TestState>| Ok(vec![END.to_string()])),
+                [END],
+            );
+        assert!(matches!(mixed, Err(Error::InvalidEdge(_))));
+    }
+
+    /// Fails on its first call and succeeds afterwards
+    struct FlakyNode {
+        failed: AtomicBool,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<TestState> for FlakyNode {
+        async fn process(&self, state: State<TestState>) -> Result<State<TestState>> {
+            if !self.failed.swap(true, Ordering::SeqCst) {
+                return Err(Error::LLM("connection reset".to_string()));
+            }
+            state.data.counter.fetch_add(100, Ordering::SeqCst);
+            Ok(state)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_after_every_node() {
+        let store = Arc::new(MemoryCheckpointStore::new());
+        let config = RunConfig::new()
+            .with_thread_id("thread-1")
+            .with_checkpointer(store.clone());
+
+        let graph = GraphBuilder::new()
+            .with_node("counter1", CounterNode { increment: 1 })
+            .unwrap()
+            .with_node("counter2", CounterNode { increment: 2 })
+            .unwrap()
+            .with_start_edge("counter1")
+            .unwrap()
+            .with_edge("counter1", "counter2", None)
+            .unwrap()
+            .with_end_edge("counter2")
+            .unwrap()
+            .build();
+
+        let initial_state = State::new(TestState {
+            counter: Arc::new(AtomicUsize::new(0)),
+            messages: vec![],
+        });
+        graph
+            .execute_with_config(initial_state, &config)
+            .await
+            .unwrap();
+
+        let checkpoints = store.list_thread("thread-1").unwrap();
+        let nodes: Vec<&str> = checkpoints.iter().map(|c| c.node_name.as_str()).collect();
+        assert_eq!(nodes, vec![START, "counter1", "counter2"]);
+        let steps: Vec<usize> = checkpoints.iter().map(|c| c.step).collect();
+        assert_eq!(steps, vec![0, 1, 2]);
+        assert_eq!(checkpoints[1].next, vec!["counter2"]);
+        assert!(checkpoints[2].next.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resume_after_failure() {
+        let store = Arc::new(MemoryCheckpointStore::new());
+        let config = RunConfig::new()
+            .with_thread_id("thread-1")
+            .with_checkpointer(store.clone());
+
+        let graph = GraphBuilder::new()
+            .with_node("counter", CounterNode { increment: 1 })
+            .unwrap()
+            .with_node(
+                "flaky",
+                FlakyNode {
+                    failed: AtomicBool::new(false),
+                },
+            )
+            .unwrap()
+            .with_start_edge("counter")
+            .unwrap()
+            .with_edge("counter", "flaky", None)
+            .unwrap()
+            .with_end_edge("flaky")
+            .unwrap()
+            .build();
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let initial_state = State::new(TestState {
+            counter: counter.clone(),
+            messages: vec![],
+        });
+        let result = graph.execute_with_config(initial_state, &config).await;
+        assert!(matches!(result.unwrap_err(), Error::NodeFailed { .. }));
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+
+        // Only the failed node runs again
+        graph.resume(&config).await.unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 101);
+
+        let checkpoints = store.list_thread("thread-1").unwrap();
+        assert_eq!(checkpoints.last().unwrap().node_name, "flaky");
+        assert_eq!(checkpoints.last().unwrap().step, 2);
+    }
+
+    #[tokio::test]
+    async fn test_subgraph_checkpoint_paths() {
+        let store = Arc::new(MemoryCheckpointStore::new());
+        let config = RunConfig::new()
+            .with_thread_id("thread-1")
+            .with_checkpointer(store.clone());
+
+        let research = GraphBuilder::new()
+            .with_node("search", CounterNode { increment: 1 })
+            .unwrap()
+            .with_start_edge("search")
+            .unwrap()
+            .with_end_edge("search")
+            .unwrap()
+            .build();
+        let graph = GraphBuilder::new()
+            .with_node("research", research)
+            .unwrap()
+            .with_start_edge("research")
+            .unwrap()
+            .with_end_edge("research")
+            .unwrap()
+            .build();
+
+        let initial_state = State::new(TestState {
+            counter: Arc::new(AtomicUsize::new(0)),
+            messages: vec![],
+        });
+        graph
+            .execute_with_config(initial_state, &config)
+            .await
+            .unwrap();
+
+        let nodes: Vec<String> = store
+            .list_thread("thread-1")
+            .unwrap()
+            .into_iter()
+            .map(|c| c.node_name)
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                START.to_string(),
+                format!("research/{}", START),
+                "research/search".to_string(),
+                "research".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use super::{EdgeConditionFn, Graph, NodeProcessor};
+use crate::error::Error;
+use crate::serialization::{SerializableGraph, SerializableNode};
+use crate::state::{State, StateValue};
+use crate::Result;
+
+/// Type alias for functions that create the processor of a node from its configuration
+pub type NodeFactoryFn<S> = Arc<dyn Fn(&Value) -> Result<Arc<dyn NodeProcessor<S>>> + Send + Sync>;
+
+/// Named node types and edge conditions that graph definitions are loaded with
+///
+/// Every node of a definition names a registered type, and the type's factory
+/// creates the node's processor from the node's `config`. Every conditional edge
+/// names a registered condition. Workflows can then be changed by editing the
+/// definition, without recompiling.
+///
+/// # Examples
+///
+/// ```
+/// use glint::graph::{NodeProcessor, NodeRegistry};
+/// use glint::state::State;
+///
+/// struct Prefix(String);
+///
+/// #[async_trait::async_trait]
+/// impl NodeProcessor<String> for Prefix {
+///     async fn process(&self, state: State<String>) -> glint::Result<State<String>> {
+///         Ok(State::new(format!("{}{}", self.0, state.data)))
+///     }
+/// }
+///
+/// let registry = NodeRegistry::<String>::new()
+///     .with_node_type("prefix", |config| {
+///         Ok(Prefix(config["text"].as_str().unwrap_or_default().to_string()))
+///     })
+///     .with_condition("is_short", |state: &State<String>| Ok(state.data.len() < 80));
+///
+/// let graph = registry.load_yaml(
+///     r#"
+/// nodes:
+///   - name: greet
+///     type: prefix
+///     config: { text: "Hello, " }
+/// edges:
+///   - { from: __start__, to: greet }
+///   - { from: greet, to: __end__ }
+/// "#,
+/// )?;
+/// # Ok::<(), glint::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct NodeRegistry<S: StateValue> {
+    /// Map of type names to the factories creating their processors
+    node_types: HashMap<String, NodeFactoryFn<S>>,
+    /// Map of condition names to the conditions
+    conditions: HashMap<String, EdgeConditionFn<S>>,
+}
+
+impl<S: StateValue> fmt::Debug for NodeRegistry<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut node_types: Vec<&String> = self.node_types.keys().collect();
+        node_types.sort();
+        let mut conditions: Vec<&String> = self.conditions.keys().collect();
+        conditions.sort();
+
+        f.debug_struct("NodeRegistry")
+            .field("node_types", &node_types)
+            .field("conditions", &conditions)
+            .finish()
+    }
+}
+
+impl<S: StateValue> Default for NodeRegistry<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: StateValue> NodeRegistry<S> {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self {
+            node_types: HashMap::new(),
+            conditions: HashMap::new(),
+        }
+    }
+
+    /// Register a node type whose processors `factory` creates from the node's config
+    pub fn with_node_type<P: NodeProcessor<S> + 'static>(
+        mut self,
+        type_name: impl Into<String>,
+        factory: impl Fn(&Value) -> Result<P> + Send + Sync + 'static,
+    ) -> Self {
+        let factory: NodeFactoryFn<S> = Arc::new(move |config| {
+            let processor: Arc<dyn NodeProcessor<S>> = Arc::new(factory(config)?);
+            Ok(processor)
+        });
+        self.node_types.insert(type_name.into(), factory);
+        self
+    }
+
+    /// Register a condition that edges refer to by name
+    pub fn with_condition(
+        mut self,
+        name: impl Into<String>,
+        condition: impl Fn(&State<S>) -> Result<bool> + Send + Sync + 'static,
+    ) -> Self {
+        self.conditions.insert(name.into(), Arc::new(condition));
+        self
+    }
+
+    /// Build a graph from a definition and check it with [`Graph::validate`]
+    ///
+    /// Edges from [`START`](super::START) and to [`END`](super::END) are written
+    /// with those node names, and error edges are marked with `on_error`. Edges
+    /// are added in the order they are listed, so sequential execution tries the
+    /// later edges of a node first. Graphs loaded here export back to an
+    /// equivalent definition with [`Graph::export_serializable`].
+    pub fn load(&self, definition: &SerializableGraph) -> Result<Graph<S>> {
+        let mut graph = Graph::new();
+
+        for name in &definition.nodes {
+            let (node, type_name) = match definition.node_definition(name) {
+                Some(
+                    node @ SerializableNode {
+                        node_type: Some(type_name),
+                        ..
+                    },
+                ) => (node, type_name),
+                _ => return Err(Error::InvalidNode(format!("Node has no type: {}", name))),
+            };
+            let factory = self.node_types.get(type_name).ok_or_else(|| {
+                Error::InvalidNode(format!("Unknown type of node {}: {}", name, type_name))
+            })?;
+            let processor = factory(&node.config)
+                .map_err(|e| Error::InvalidNode(format!("Cannot create node {}: {}", name, e)))?;
+            graph.add_shared_node(name.clone(), processor)?;
+            graph.node_definitions.insert(name.clone(), node.clone());
+        }
+
+        for edge in &definition.edges {
+            if edge.on_error {
+                graph.add_error_edge(&edge.from, &edge.to)?;
+                continue;
+            }
+
+            let condition = match (&edge.condition, edge.has_condition) {
+                (Some(name), _) => Some(self.conditions.get(name).cloned().ok_or_else(|| {
+                    Error::InvalidEdge(format!(
+                        "Unknown condition of edge {} -> {}: {}",
+                        edge.from, edge.to, name
+                    ))
+                })?),
+                (None, true) => {
+                    return Err(Error::InvalidEdge(format!(
+                        "Edge {} -> {} has a condition but no condition name",
+                        edge.from, edge.to
+                    )))
+                }
+                (None, false) => None,
+            };
+            let edge_idx = graph.insert_edge(&edge.from, &edge.to, condition)?;
+            if let Some(name) = &edge.condition {
+                graph.edge_conditions.insert(edge_idx, name.clone());
+            }
+            if let Some(label) = &edge.condition_description {
+                graph.edge_labels.insert(edge_idx, label.clone());
+            }
+        }
+
+        graph.validate()?;
+        Ok(graph)
+    }
+
+    /// Build a graph from a JSON definition
+    pub fn load_json(&self, json: &str) -> Result<Graph<S>> {
+        self.load(&SerializableGraph::from_json(json)?)
+    }
+
+    /// Build a graph from a YAML definition
+    pub fn load_yaml(&self, yaml: &str) -> Result<Graph<S>> {
+        self.load(&SerializableGraph::from_yaml(yaml)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::END;
+    use async_trait::async_trait;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct AddConfig {
+        amount: i32,
+    }
+
+    /// Adds a configured amount to the state
+    struct Add(i32);
+
+    #[async_trait]
+    impl NodeProcessor<i32> for Add {
+        async fn process(&self, state: State<i32>) -> Result<State<i32>> {
+            Ok(State::new(state.data + self.0))
+        }
+    }
+
+    fn registry() -> NodeRegistry<i32> {
+        NodeRegistry::new()
+            .with_node_type("add", |config| {
+                let config: AddConfig = serde_json::from_value(config.clone())?;
+                Ok(Add(config.amount))
+            })
+            .with_condition("is_small", |state| Ok(state.data < 10))
+    }
+
+    const DEFINITION: &str = r#"
+nodes:
+  - name: add_one
+    type: add
+    config: { amount: 1 }
+  - name: add_ten
+    type: add
+    config: { amount: 10 }
+edges:
+  - { from: __start__, to: add_one }
+  - { from: add_one, to: __end__ }
+  - { from: add_one, to: add_ten, condition: is_small }
+  - { from: add_ten, to: __end__ }
+"#;
+
+    #[tokio::test]
+    async fn test_load_yaml_definition() {
+        let graph = registry().load_yaml(DEFINITION).unwrap();
+
+        let result = graph.execute(State::new(0)).await.unwrap();
+        assert_eq!(result.data, 11);
+        let result = graph.execute(State::new(20)).await.unwrap();
+        assert_eq!(result.data, 21);
+
+        // The same definition round-trips through JSON
+        let json = SerializableGraph::from_yaml(DEFINITION)
+            .unwrap()
+            .to_json()
+            .unwrap();
+        assert!(registry().load_json(&json).is_ok());
+    }
+
+    #[test]
+    fn test_load_reports_unknown_names() {
+        let unknown_type = DEFINITION.replace("type: add\n    config: { amount: 10 }", "type: mul");
+        let error = registry().load_yaml(&unknown_type).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid node: Unknown type of node add_ten: mul"
+        );
+
+        let unknown_condition = DEFINITION.replace("is_small", "is_large");
+        let error = registry().load_yaml(&unknown_condition).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid edge: Unknown condition of edge add_one -> add_ten: is_large"
+        );
+
+        let bad_config = DEFINITION.replace("amount: 1 }", "amount: one }");
+        let error = registry().load_yaml(&bad_config).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Invalid node: Cannot create node add_one"));
+    }
+
+    #[tokio::test]
+    async fn test_export_loads_again() {
+        let definition = format!(
+            "{}  - {{ from: add_ten, to: add_one, on_error: true }}\n",
+            DEFINITION
+        );
+        let mut graph = registry().load_yaml(&definition).unwrap();
+
+        let exported = graph.export_serializable();
+        let conditional = &exported.edges[2];
+        assert_eq!(conditional.condition.as_deref(), Some("is_small"));
+        assert!(exported.edges[4].on_error);
+
+        let reloaded = registry().load_yaml(&exported.to_yaml().unwrap()).unwrap();
+        assert_eq!(reloaded.to_mermaid(), graph.to_mermaid());
+        let result = reloaded.execute(State::new(0)).await.unwrap();
+        assert_eq!(result.data, 11);
+
+        // Conditions added in code have no name to load them by
+        graph
+            .add_edge("add_ten", END, Some(Arc::new(|_: &State<i32>| Ok(true))))
+            .unwrap();
+        let error = registry().load(&graph.export_serializable()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid edge: Edge add_ten -> __end__ has a condition but no condition name"
+        );
+    }
+}
@@ -0,0 +1,26 @@
+use serde::Serialize;
+
+use super::{Graph, RunConfig};
+use crate::replay::Cassette;
+use crate::state::{State, StateValue};
+use crate::Result;
+
+impl<S: StateValue + Serialize> Graph<S> {
+    /// Execute the graph while a cassette records or replays the run
+    ///
+    /// When recording, the final state is stored in the cassette. When replaying,
+    /// the run fails with [`Error::Replay`](crate::Error::Replay) if it makes a call
+    /// the recording does not have or ends in a different final state.
+    pub async fn execute_with_cassette(
+        &self,
+        initial_state: State<S>,
+        config: &RunConfig<S>,
+        cassette: &Cassette,
+    ) -> Result<State<S>> {
+        let final_state = cassette
+            .scope(self.execute_with_config(initial_state, config))
+            .await?;
+        cassette.finish(serde_json::to_value(&final_state)?)?;
+        Ok(final_state)
+    }
+}
@@ -0,0 +1,284 @@
+use serde_json::Value;
+
+use super::context::{Checkpointing, RunContext};
+use super::interrupt::{reruns_node, Interrupt, Release, INTERRUPT_KEY, RESUME_KEY};
+use super::{Graph, RunConfig, PATH_SEPARATOR};
+use crate::checkpoint::Checkpoint;
+use crate::error::Error;
+use crate::state::{State, StateValue};
+use crate::Result;
+
+impl<S: StateValue> Graph<S> {
+    /// Continue the run of a thread from its latest checkpoint
+    ///
+    /// Checkpoints saved by nested subgraphs are skipped, so a run interrupted inside
+    /// a subgraph restarts that subgraph node. A run stopped at an interrupt continues
+    /// past it. Resuming a finished run returns its final state.
+    pub async fn resume(&self, config: &RunConfig<S>) -> Result<State<S>> {
+        let checkpointing = Self::require_checkpointing(config)?;
+        let checkpoint = Self::latest_checkpoint(&checkpointing)?;
+
+        let run = Self::resumed_run(&checkpoint, checkpointing, config, None)?;
+        self.continue_from(checkpoint, &run).await
+    }
+
+    /// Load the latest checkpoint saved by a top-level run of the thread
+    pub(crate) fn latest_checkpoint(checkpointing: &Checkpointing<S>) -> Result<Checkpoint<S>> {
+        let latest = checkpointing
+            .store()
+            .list_thread(checkpointing.thread_id())?
+            .into_iter()
+            .rev()
+            .find(|metadata| !metadata.node_name.contains(PATH_SEPARATOR))
+            .ok_or_else(|| {
+                Error::Checkpoint(format!(
+                    "No checkpoints for thread: {}",
+                    checkpointing.thread_id()
+                ))
+            })?;
+        checkpointing.store().load(&latest.id)
+    }
+
+    /// Continue a run from any saved checkpoint, continuing at the nodes scheduled
+    /// after it
+    ///
+    /// New checkpoints are saved under the configured thread and follow the given
+    /// checkpoint, starting a new branch of its history. Unlike [`Graph::fork`], no
+    /// copy of the checkpoint is saved first.
+    pub async fn resume_from(
+        &self,
+        config: &RunConfig<S>,
+        checkpoint_id: &str,
+    ) -> Result<State<S>> {
+        let checkpointing = Self::require_checkpointing(config)?;
+        let checkpoint = Self::top_level_checkpoint(&checkpointing, checkpoint_id)?;
+        checkpointing.set_parent(checkpoint_id);
+
+        let run = Self::resumed_run(&checkpoint, checkpointing, config, None)?;
+        self.continue_from(checkpoint, &run).await
+    }
+
+    /// Continue a run from any saved checkpoint after patching its state
+    ///
+    /// A checkpoint holding the patched state is saved first, with the original
+    /// checkpoint as its parent, so the new branch of history records where it
+    /// started. This is how a run is rewound, edited and rerun:
+    ///
+    /// ```no_run
+    /// # use glint::graph::{Graph, RunConfig};
+    /// # use glint::checkpoint::CheckpointStore;
+    /// # async fn rewind(
+    /// #     graph: Graph<i32>,
+    /// #     store: std::sync::Arc<dyn CheckpointStore<i32>>,
+    /// # ) -> glint::Result<()> {
+    /// let config = RunConfig::new()
+    ///     .with_thread_id("thread-1")
+    ///     .with_checkpointer(store.clone());
+    ///
+    /// let history = store.list_thread("thread-1")?;
+    /// let three_steps_back = &history[history.len() - 4];
+    ///
+    /// graph
+    ///     .fork(&config, &three_steps_back.id, |mut state| {
+    ///         state.data = 0;
+    ///         Ok(state)
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn fork(
+        &self,
+        config: &RunConfig<S>,
+        checkpoint_id: &str,
+        patch: impl FnOnce(State<S>) -> Result<State<S>>,
+    ) -> Result<State<S>> {
+        let checkpointing = Self::require_checkpointing(config)?;
+        let mut checkpoint = Self::top_level_checkpoint(&checkpointing, checkpoint_id)?;
+
+        checkpoint.state = patch(checkpoint.state)?;
+        checkpointing.set_parent(checkpoint_id);
+        checkpointing.save(
+            checkpoint.metadata.node_name.clone(),
+            &checkpoint.state,
+            checkpoint.metadata.next.clone(),
+            None,
+        )?;
+
+        let run = Self::resumed_run(&checkpoint, checkpointing, config, None)?;
+        self.continue_from(checkpoint, &run).await
+    }
+
+    /// Load a checkpoint to continue from, rejecting those saved inside a subgraph
+    fn top_level_checkpoint(
+        checkpointing: &Checkpointing<S>,
+        checkpoint_id: &str,
+    ) -> Result<Checkpoint<S>> {
+        let checkpoint = checkpointing.store().load(checkpoint_id)?;
+        if checkpoint.metadata.node_name.contains(PATH_SEPARATOR) {
+            return Err(Error::Checkpoint(format!(
+                "Cannot continue from a checkpoint saved inside a subgraph: {}",
+                checkpoint.metadata.node_name
+            )));
+        }
+        Ok(checkpoint)
+    }
+
+    /// Set up checkpointing for a run that continues from a checkpoint
+    pub(crate) fn require_checkpointing(config: &RunConfig<S>) -> Result<Checkpointing<S>> {
+        config.checkpointing()?.ok_or_else(|| {
+            Error::Checkpoint("Resuming requires a checkpointer and a thread_id".to_string())
+        })
+    }
+
+    /// Create the run that continues from a checkpoint, letting it pass the
+    /// interrupt saved with the checkpoint
+    pub(crate) fn resumed_run(
+        checkpoint: &Checkpoint<S>,
+        checkpointing: Checkpointing<S>,
+        config: &RunConfig<S>,
+        value: Option<Value>,
+    ) -> Result<RunContext<S>> {
+        let run = RunContext::root(Some(checkpointing)).with_config(config);
+        match checkpoint.metadata.metadata.get(INTERRUPT_KEY) {
+            Some(interrupt) => {
+                let interrupt: Interrupt = serde_json::from_value(interrupt.clone())?;
+                if reruns_node(&interrupt) {
+                    return Ok(run.with_release(Release::new(interrupt, value)));
+                }
+                Ok(run)
+            }
+            None => Ok(run),
+        }
+    }
+
+    /// Run the nodes scheduled after a checkpoint, starting from its state
+    pub(crate) async fn continue_from(
+        &self,
+        checkpoint: Checkpoint<S>,
+        run: &RunContext<S>,
+    ) -> Result<State<S>> {
+        let pending = checkpoint
+            .metadata
+            .next
+            .iter()
+            .map(|name| self.target_index(name))
+            .collect::<Result<Vec<_>>>()?;
+
+        // The run this checkpoint belongs to already finished
+        if pending.is_empty() {
+            let mut state = checkpoint.state;
+            state.metadata.remove(RESUME_KEY);
+            return Ok(state);
+        }
+
+        self.execute_from(checkpoint.state, pending, run).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::{CheckpointStore, MemoryCheckpointStore};
+    use crate::graph::{GraphBuilder, NodeProcessor, END};
+    use async_trait::async_trait;
+    use std::sync::Arc;
+
+    struct Double;
+
+    #[async_trait]
+    impl NodeProcessor<i32> for Double {
+        async fn process(&self, mut state: State<i32>) -> Result<State<i32>> {
+            state.data *= 2;
+            Ok(state)
+        }
+    }
+
+    fn doubling_graph() -> Graph<i32> {
+        let done = Arc::new(|state: &State<i32>| Ok(state.data >= 16));
+        let again = Arc::new(|state: &State<i32>| Ok(state.data < 16));
+        GraphBuilder::new()
+            .with_node("double", Double)
+            .unwrap()
+            .with_start_edge("double")
+            .unwrap()
+            .with_edge("double", END, Some(done))
+            .unwrap()
+            .with_edge("double", "double", Some(again))
+            .unwrap()
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_fork_with_patched_state() {
+        let store = Arc::new(MemoryCheckpointStore::new());
+        let config = RunConfig::new()
+            .with_thread_id("thread-1")
+            .with_checkpointer(store.clone());
+        let graph = doubling_graph();
+
+        // 1 -> 2 -> 4 -> 8 -> 16
+        let final_state = graph
+            .execute_with_config(State::new(1), &config)
+            .await
+            .unwrap();
+        assert_eq!(final_state.data, 16);
+
+        let history = store.list_thread("thread-1").unwrap();
+        assert_eq!(history.len(), 5);
+        let rewind_to = history[2].clone();
+        assert_eq!(store.load(&rewind_to.id).unwrap().state.data, 4);
+
+        // Rewind to 4, patch it to 5, rerun: 5 -> 10 -> 20
+        let final_state = graph
+            .fork(&config, &rewind_to.id, |mut state| {
+                state.data = 5;
+                Ok(state)
+            })
+            .await
+            .unwrap();
+        assert_eq!(final_state.data, 20);
+
+        let history = store.list_thread("thread-1").unwrap();
+        assert_eq!(history.len(), 8);
+        let fork_point = &history[5];
+        assert_eq!(fork_point.parent_id.as_deref(), Some(rewind_to.id.as_str()));
+        assert_eq!(store.load(&fork_point.id).unwrap().state.data, 5);
+        assert_eq!(
+            history[6].parent_id.as_deref(),
+            Some(fork_point.id.as_str())
+        );
+
+        // The latest checkpoint is the end of the new branch
+        let resumed = graph.resume(&config).await.unwrap();
+        assert_eq!(resumed.data, 20);
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_checkpoint() {
+        let store = Arc::new(MemoryCheckpointStore::new());
+        let config = RunConfig::new()
+            .with_thread_id("thread-1")
+            .with_checkpointer(store.clone());
+        let graph = doubling_graph();
+
+        graph
+            .execute_with_config(State::new(2), &config)
+            .await
+            .unwrap();
+        let first = store.list_thread("thread-1").unwrap()[0].clone();
+
+        let fork_config = RunConfig::new()
+            .with_thread_id("thread-2")
+            .with_checkpointer(store.clone());
+        let final_state = graph.resume_from(&fork_config, &first.id).await.unwrap();
+        assert_eq!(final_state.data, 16);
+
+        // Only the nodes run after resuming save checkpoints: 4, 8 and 16
+        let branch = store.list_thread("thread-2").unwrap();
+        assert_eq!(branch.len(), 3);
+        assert_eq!(branch[0].parent_id.as_deref(), Some(first.id.as_str()));
+        assert_eq!(store.load(&branch[0].id).unwrap().state.data, 4);
+        assert_eq!(store.list_thread("thread-1").unwrap().len(), 4);
+    }
+}
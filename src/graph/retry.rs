@@ -0,0 +1,444 @@
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{Command, Graph, GraphEvent, NodeContext, NodeProcessor};
+use crate::error::Error;
+use crate::state::{State, StateValue};
+use crate::Result;
+
+/// Type alias for functions that decide whether a failed node attempt is retried
+pub type RetryPredicateFn = Arc<dyn Fn(&Error) -> bool + Send + Sync>;
+
+/// How often and how patiently a failing node is retried
+///
+/// The delay before the n-th retry is `initial_interval * backoff_factor^(n-1)`,
+/// capped at `max_interval`. With jitter, each delay is scaled by a random factor
+/// between 0.5 and 1 so that retrying nodes do not hit a service in lockstep.
+///
+/// # Examples
+///
+/// ```
+/// use glint::graph::RetryPolicy;
+/// use glint::Error;
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::new(5)
+///     .with_initial_interval(Duration::from_millis(200))
+///     .with_retry_on(|error| matches!(error, Error::LLM(_)));
+/// ```
+#[derive(Clone)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one
+    pub max_attempts: usize,
+    /// Delay before the first retry
+    pub initial_interval: Duration,
+    /// Multiplier applied to the delay after every retry
+    pub backoff_factor: f64,
+    /// Upper bound of the delay between attempts
+    pub max_interval: Duration,
+    /// Whether delays are randomized
+    pub jitter: bool,
+    /// Decides which errors are retried
+    retry_on: RetryPredicateFn,
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_interval", &self.initial_interval)
+            .field("backoff_factor", &self.backoff_factor)
+            .field("max_interval", &self.max_interval)
+            .field("jitter", &self.jitter)
+            .finish()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+impl RetryPolicy {
+    /// Create a policy that makes up to `max_attempts` attempts, retrying the errors
+    /// accepted by [`is_retryable`]
+    pub fn new(max_attempts: usize) -> Self {
+        Self {
+            max_attempts,
+            initial_interval: Duration::from_millis(500),
+            backoff_factor: 2.0,
+            max_interval: Duration::from_secs(30),
+            jitter: true,
+            retry_on: Arc::new(is_retryable),
+        }
+    }
+
+    /// Set the delay before the first retry
+    pub fn with_initial_interval(mut self, interval: Duration) -> Self {
+        self.initial_interval = interval;
+        self
+    }
+
+    /// Set the multiplier applied to the delay after every retry
+    pub fn with_backoff_factor(mut self, factor: f64) -> Self {
+        self.backoff_factor = factor;
+        self
+    }
+
+    /// Set the upper bound of the delay between attempts
+    pub fn with_max_interval(mut self, interval: Duration) -> Self {
+        self.max_interval = interval;
+        self
+    }
+
+    /// Enable or disable randomized delays
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set the function that decides which errors are retried
+    pub fn with_retry_on(
+        mut self,
+        retry_on: impl Fn(&Error) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retry_on = Arc::new(retry_on);
+        self
+    }
+
+    /// Check whether a failed attempt should be retried
+    fn should_retry(&self, attempt: usize, error: &Error) -> bool {
+        attempt < self.max_attempts && !is_control_flow(error) && (self.retry_on)(error)
+    }
+
+    /// Get the delay before the given retry, counting from 1
+    pub fn delay(&self, retry: usize) -> Duration {
+        let exponent = retry.saturating_sub(1).min(i32::MAX as usize) as i32;
+        // Capped in seconds, as the uncapped delay may not fit in a Duration
+        let seconds = self.initial_interval.as_secs_f64() * self.backoff_factor.powi(exponent);
+        let delay = if seconds.is_nan() || seconds <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::try_from_secs_f64(seconds)
+                .map_or(self.max_interval, |delay| delay.min(self.max_interval))
+        };
+
+        if self.jitter {
+            // Without a random number from the OS the delay is not randomized
+            let random = getrandom::u32().unwrap_or(u32::MAX) as f64 / u32::MAX as f64;
+            Duration::try_from_secs_f64(delay.as_secs_f64() * (0.5 + random / 2.0)).unwrap_or(delay)
+        } else {
+            delay
+        }
+    }
+}
+
+/// Check whether an error is worth retrying by default
+///
+/// Retried are node timeouts, request timeouts and connection failures, HTTP 429
+/// and 5xx responses, LLM errors that report such a status, e.g. `API error: 503`
+/// or `status 429`, or that mention rate limits or overload, and IO errors that
+/// are usually transient, such as timeouts and reset connections. Errors of nested
+/// graphs are classified by their cause, and replayed errors as they were when
+/// recorded.
+pub fn is_retryable(error: &Error) -> bool {
+    match error {
+        Error::NodeFailed { source, .. } => is_retryable(source),
+        Error::Request(e) => {
+            e.is_timeout()
+                || e.is_connect()
+                || e.status()
+                    .is_some_and(|status| status.as_u16() == 429 || status.is_server_error())
+        }
+        Error::LLM(message) => {
+            let message = message.to_lowercase();
+            [
+                "rate limit",
+                "rate_limit",
+                "too many requests",
+                "overloaded",
+                "timeout",
+                "timed out",
+            ]
+            .iter()
+            .any(|pattern| message.contains(pattern))
+                || reported_status(&message).is_some_and(|code| code == 429 || code >= 500)
+        }
+        Error::Replayed { retryable, .. } => *retryable,
+        Error::Io(e) => matches!(
+            e.kind(),
+            io::ErrorKind::TimedOut
+                | io::ErrorKind::Interrupted
+                | io::ErrorKind::WouldBlock
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::UnexpectedEof
+        ),
+        Error::Timeout(_) => true,
+        _ => false,
+    }
+}
+
+/// Find the HTTP status an error message reports after a marker such as
+/// `status` or `api error:`
+fn reported_status(message: &str) -> Option<u16> {
+    ["api error:", "status code", "status:", "status", "http"]
+        .iter()
+        .flat_map(|marker| message.match_indices(marker))
+        .find_map(|(index, marker)| {
+            let rest = message[index + marker.len()..].trim_start();
+            let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+            if digits != 3 {
+                return None;
+            }
+            rest[..3]
+                .parse()
+                .ok()
+                .filter(|code| (100..600).contains(code))
+        })
+}
+
+/// Check whether an error steers the run rather than reporting a failure
+pub(crate) fn is_control_flow(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::Interrupted(_) | Error::RecursionLimit { .. } | Error::Cancelled(_)
+    )
+}
+
+impl<S: StateValue> Graph<S> {
+    /// Run a node's processor, retrying failed attempts according to the node's
+    /// retry policy
+    ///
+    /// Failed attempts are reported to the run's event stream and are not
+    /// checkpointed; every attempt starts from the same input state and is bounded
+    /// by the node's timeout. Every attempt takes its own slot under the node's
+    /// concurrency limit, so a node waiting to retry does not hold one.
+    pub(crate) async fn process_with_retry(
+        &self,
+        node_name: &str,
+        processor: &dyn NodeProcessor<S>,
+        state: State<S>,
+        ctx: &NodeContext<S>,
+    ) -> Result<Command<S>> {
+        let timeout = self.node_timeouts.get(node_name).copied();
+        let policy = match self.retry_policies.get(node_name) {
+            Some(policy) => policy,
+            None => {
+                return self
+                    .attempt(node_name, processor, state, ctx, timeout)
+                    .await
+            }
+        };
+
+        let mut attempt = 1;
+        loop {
+            let result = self
+                .attempt(node_name, processor, state.clone(), ctx, timeout)
+                .await;
+            let error = match result {
+                Ok(command) => return Ok(command),
+                Err(e) => e,
+            };
+            if !policy.should_retry(attempt, &error) {
+                return Err(error);
+            }
+
+            let delay = policy.delay(attempt);
+            ctx.emit(|| GraphEvent::NodeRetrying {
+                node: ctx.path().to_string(),
+                attempt,
+                error: error.to_string(),
+                delay,
+            });
+            tracing::warn!(
+                node = ctx.path(),
+                attempt,
+                error = %error,
+                "Retrying failed node in {:?}",
+                delay
+            );
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Make a single attempt at running a node's processor, holding a slot under
+    /// the node's concurrency limit
+    async fn attempt(
+        &self,
+        node_name: &str,
+        processor: &dyn NodeProcessor<S>,
+        state: State<S>,
+        ctx: &NodeContext<S>,
+        timeout: Option<Duration>,
+    ) -> Result<Command<S>> {
+        let _slot = self.acquire_slot(node_name).await?;
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => return processor.process_command(state, ctx).await,
+        };
+
+        tokio::time::timeout(timeout, processor.process_command(state, ctx))
+            .await
+            .map_err(|_| Error::Timeout(format!("Node exceeded its timeout of {:?}", timeout)))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{GraphBuilder, RunConfig};
+    use async_trait::async_trait;
+    use futures::StreamExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Fails with a rate limit error until it has been called `failures` times
+    struct RateLimited {
+        calls: Arc<AtomicUsize>,
+        failures: usize,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<i32> for RateLimited {
+        async fn process(&self, mut state: State<i32>) -> Result<State<i32>> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(Error::LLM("429 Too Many Requests".to_string()));
+            }
+            state.data += 1;
+            Ok(state)
+        }
+    }
+
+    fn retry_graph(failures: usize, policy: RetryPolicy) -> (Graph<i32>, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let node = RateLimited {
+            calls: calls.clone(),
+            failures,
+        };
+        let graph = GraphBuilder::new()
+            .with_node("model", node)
+            .unwrap()
+            .with_retry_policy("model", policy)
+            .unwrap()
+            .with_start_edge("model")
+            .unwrap()
+            .with_end_edge("model")
+            .unwrap()
+            .build();
+        (graph, calls)
+    }
+
+    fn fast_policy(max_attempts: usize) -> RetryPolicy {
+        RetryPolicy::new(max_attempts)
+            .with_initial_interval(Duration::from_millis(1))
+            .with_jitter(false)
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        let (graph, calls) = retry_graph(2, fast_policy(3));
+
+        let events: Vec<GraphEvent<i32>> = graph
+            .stream(State::new(0), &RunConfig::new())
+            .collect()
+            .await;
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let retries: Vec<usize> = events
+            .iter()
+            .filter_map(|event| match event {
+                GraphEvent::NodeRetrying { attempt, .. } => Some(*attempt),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(retries, vec![1, 2]);
+        assert!(matches!(events.last(), Some(GraphEvent::Completed(state)) if state.data == 1));
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let (graph, calls) = retry_graph(5, fast_policy(3));
+
+        let result = graph.execute(State::new(0)).await;
+        assert!(matches!(result, Err(Error::NodeFailed { .. })));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // Errors the predicate rejects are not retried
+        let (graph, calls) = retry_graph(5, fast_policy(3).with_retry_on(|_| false));
+        assert!(graph.execute(State::new(0)).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_backoff_delays() {
+        let policy = RetryPolicy::new(10)
+            .with_initial_interval(Duration::from_millis(100))
+            .with_max_interval(Duration::from_millis(350))
+            .with_jitter(false);
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(350));
+
+        let jittered = policy.with_jitter(true).delay(2);
+        assert!(jittered >= Duration::from_millis(100) && jittered <= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_backoff_delays_do_not_overflow() {
+        let policy = RetryPolicy::new(1000).with_jitter(false);
+        assert_eq!(policy.delay(100), Duration::from_secs(30));
+        assert_eq!(policy.delay(usize::MAX), Duration::from_secs(30));
+
+        let policy = policy.with_backoff_factor(1e300);
+        assert_eq!(policy.delay(3), Duration::from_secs(30));
+        let policy = policy.with_backoff_factor(f64::INFINITY);
+        assert_eq!(policy.delay(2), Duration::from_secs(30));
+
+        let policy = policy
+            .with_max_interval(Duration::MAX)
+            .with_initial_interval(Duration::ZERO)
+            .with_jitter(true);
+        assert_eq!(policy.delay(2), Duration::ZERO);
+        let jittered = policy
+            .with_initial_interval(Duration::from_secs(1))
+            .delay(500);
+        assert!(jittered >= Duration::MAX / 2);
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let llm = |message: &str| Error::LLM(message.to_string());
+        assert!(is_retryable(&llm("Rate limit exceeded")));
+        assert!(is_retryable(&llm("upstream returned status 503")));
+        assert!(is_retryable(&llm(
+            "OpenAI API error: 502 Bad Gateway - try again"
+        )));
+        assert!(!is_retryable(&llm("invalid api key")));
+        assert!(!is_retryable(&Error::State("bad state".to_string())));
+        assert!(is_retryable(
+            &llm("OpenAI API error: 429 Too Many Requests - slow down").at_node("model")
+        ));
+
+        // Numbers that are not statuses
+        assert!(!is_retryable(&llm("max_tokens 512 exceeds the limit")));
+        assert!(!is_retryable(&llm("you requested 550 tokens")));
+        assert!(!is_retryable(&llm("the prompt has 14290 tokens")));
+        assert!(!is_retryable(&llm(
+            "OpenAI API error: 400 Bad Request - max_tokens 512 is too large"
+        )));
+
+        let io = |kind| Error::Io(io::Error::from(kind));
+        assert!(is_retryable(&io(io::ErrorKind::TimedOut)));
+        assert!(is_retryable(&io(io::ErrorKind::ConnectionReset)));
+        assert!(!is_retryable(&io(io::ErrorKind::NotFound)));
+        assert!(!is_retryable(&io(io::ErrorKind::PermissionDenied)));
+    }
+}
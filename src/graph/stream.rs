@@ -0,0 +1,266 @@
+use futures::channel::mpsc::{self, UnboundedSender};
+use futures::stream::{self, Stream, StreamExt};
+use futures::FutureExt;
+use std::time::Duration;
+
+use super::{Graph, NodeContext, RunConfig};
+use crate::error::Error;
+use crate::state::{State, StateValue};
+
+/// An event emitted while a graph runs
+///
+/// Nodes are identified by their path, e.g. `research/search` for a node inside a
+/// subgraph.
+#[derive(Debug)]
+pub enum GraphEvent<S: StateValue> {
+    /// A node started running
+    NodeStarted {
+        /// Path of the node
+        node: String,
+        /// Step of the run at which the node runs
+        step: usize,
+    },
+    /// A node finished running
+    NodeFinished {
+        /// Path of the node
+        node: String,
+        /// The state the node returned
+        state: State<S>,
+    },
+    /// An attempt of a node failed and will be retried
+    NodeRetrying {
+        /// Path of the node
+        node: String,
+        /// Number of the failed attempt, counting from 1
+        attempt: usize,
+        /// Description of the error the attempt failed with
+        error: String,
+        /// Delay before the next attempt
+        delay: Duration,
+    },
+    /// Execution moved along an edge
+    EdgeTaken {
+        /// Path of the node the edge starts from
+        from: String,
+        /// Path of the node the edge points to
+        to: String,
+    },
+    /// A checkpoint was saved
+    CheckpointSaved {
+        /// ID of the checkpoint
+        checkpoint_id: String,
+        /// Paths of the nodes that produced the saved state, joined with ","
+        node: String,
+    },
+    /// A chunk of LLM output emitted by a node with [`NodeContext::emit_token`]
+    Token {
+        /// Path of the node
+        node: String,
+        /// The text of the chunk
+        chunk: String,
+    },
+    /// The run reached END; always the last event of a successful run
+    Completed(State<S>),
+    /// The run failed or stopped at an interrupt; always the last event of such a run
+    Failed(Error),
+}
+
+/// Sends the events of a run to its stream
+pub(crate) type EventSink<S> = UnboundedSender<GraphEvent<S>>;
+
+impl<S: StateValue> NodeContext<S> {
+    /// Emit a chunk of LLM output to the stream of the enclosing run
+    ///
+    /// The chunk is also reported to the run's callback handlers. It is streamed
+    /// only if the run was started with [`Graph::stream`].
+    pub fn emit_token(&self, chunk: impl Into<String>) {
+        let chunk = chunk.into();
+        self.report_token(&chunk);
+        self.emit(|| GraphEvent::Token {
+            node: self.path().to_string(),
+            chunk,
+        });
+    }
+}
+
+impl<S: StateValue> Graph<S> {
+    /// Execute the graph and stream the events of the run as they happen
+    ///
+    /// The stream ends with [`GraphEvent::Completed`] holding the final state, or
+    /// with [`GraphEvent::Failed`]. Subgraphs with a different state type run
+    /// without emitting events.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use glint::graph::{Graph, GraphEvent, RunConfig};
+    /// # use glint::state::State;
+    /// use futures::StreamExt;
+    ///
+    /// # async fn show_progress(graph: Graph<String>) {
+    /// let config = RunConfig::new();
+    /// let mut events = Box::pin(graph.stream(State::new(String::new()), &config));
+    ///
+    /// while let Some(event) = events.next().await {
+    ///     match event {
+    ///         GraphEvent::NodeStarted { node, .. } => println!("running {}", node),
+    ///         GraphEvent::Token { chunk, .. } => print!("{}", chunk),
+    ///         GraphEvent::Completed(state) => println!("done: {}", state.data),
+    ///         GraphEvent::Failed(error) => eprintln!("failed: {}", error),
+    ///         _ => {}
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn stream<'a>(
+        &'a self,
+        initial_state: State<S>,
+        config: &RunConfig<S>,
+    ) -> impl Stream<Item = GraphEvent<S>> + 'a {
+        let (sink, events) = mpsc::unbounded();
+        let run = config.run_context();
+
+        let run = async move {
+            let result = match run {
+                Ok(run) => {
+                    let run = run.with_events(sink.clone());
+                    self.execute_in(initial_state, &run).await
+                }
+                Err(e) => Err(e),
+            };
+
+            let last = match result {
+                Ok(state) => GraphEvent::Completed(state),
+                Err(e) => GraphEvent::Failed(e),
+            };
+            let _ = sink.unbounded_send(last);
+        };
+
+        // Drive the run while forwarding its events; the event channel closes once
+        // the run and every sender it handed out are done
+        stream::select(events.map(Some), run.into_stream().map(|_| None))
+            .filter_map(futures::future::ready)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::MemoryCheckpointStore;
+    use crate::graph::{Command, GraphBuilder, NodeProcessor};
+    use crate::Result;
+    use async_trait::async_trait;
+    use std::sync::Arc;
+
+    struct Chat;
+
+    #[async_trait]
+    impl NodeProcessor<String> for Chat {
+        async fn process(&self, state: State<String>) -> Result<State<String>> {
+            Ok(state)
+        }
+
+        async fn process_command(
+            &self,
+            mut state: State<String>,
+            ctx: &NodeContext<String>,
+        ) -> Result<Command<String>> {
+            for chunk in ["Hel", "lo"] {
+                ctx.emit_token(chunk);
+                state.data.push_str(chunk);
+            }
+            Ok(Command::new(state))
+        }
+    }
+
+    struct Fail;
+
+    #[async_trait]
+    impl NodeProcessor<String> for Fail {
+        async fn process(&self, _state: State<String>) -> Result<State<String>> {
+            Err(Error::LLM("rate limited".to_string()))
+        }
+    }
+
+    fn chat_graph(tools: impl NodeProcessor<String> + 'static) -> Graph<String> {
+        GraphBuilder::new()
+            .with_node("chat", Chat)
+            .unwrap()
+            .with_node("tools", tools)
+            .unwrap()
+            .with_start_edge("chat")
+            .unwrap()
+            .with_edge("chat", "tools", None)
+            .unwrap()
+            .with_end_edge("tools")
+            .unwrap()
+            .build()
+    }
+
+    /// Describe events in a compact form for assertions
+    fn describe(event: &GraphEvent<String>) -> String {
+        match event {
+            GraphEvent::NodeStarted { node, step } => format!("start {} {}", node, step),
+            GraphEvent::NodeFinished { node, state } => format!("finish {} {}", node, state.data),
+            GraphEvent::NodeRetrying { node, attempt, .. } => format!("retry {} {}", node, attempt),
+            GraphEvent::EdgeTaken { from, to } => format!("edge {} {}", from, to),
+            GraphEvent::CheckpointSaved { node, .. } => format!("checkpoint {}", node),
+            GraphEvent::Token { node, chunk } => format!("token {} {}", node, chunk),
+            GraphEvent::Completed(state) => format!("completed {}", state.data),
+            GraphEvent::Failed(error) => format!("failed {}", error),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_events() {
+        let graph = chat_graph(Chat);
+        let events: Vec<String> = graph
+            .stream(State::new(String::new()), &RunConfig::new())
+            .map(|event| describe(&event))
+            .collect()
+            .await;
+
+        assert_eq!(
+            events,
+            vec![
+                "edge __start__ chat",
+                "start chat 1",
+                "token chat Hel",
+                "token chat lo",
+                "finish chat Hello",
+                "edge chat tools",
+                "start tools 2",
+                "token tools Hel",
+                "token tools lo",
+                "finish tools HelloHello",
+                "edge tools __end__",
+                "completed HelloHello",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_checkpoints_and_failure() {
+        let config = RunConfig::new()
+            .with_thread_id("thread-1")
+            .with_checkpointer(Arc::new(MemoryCheckpointStore::new()));
+        let graph = chat_graph(Fail);
+
+        let events: Vec<GraphEvent<String>> = graph
+            .stream(State::new(String::new()), &config)
+            .collect()
+            .await;
+
+        let checkpoints: Vec<String> = events
+            .iter()
+            .filter(|event| matches!(event, GraphEvent::CheckpointSaved { .. }))
+            .map(describe)
+            .collect();
+        assert_eq!(checkpoints, vec!["checkpoint __start__", "checkpoint chat"]);
+
+        match events.last() {
+            Some(GraphEvent::Failed(Error::NodeFailed { node, .. })) => assert_eq!(node, "tools"),
+            other => panic!("unexpected last event: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use super::{Command, Graph, NodeContext, NodeProcessor, RunContext};
+use crate::state::{State, StateValue};
+use crate::Result;
+
+/// Type alias for functions that project a parent state into a subgraph state
+pub type InputMapFn<S, T> = Arc<dyn Fn(&State<S>) -> Result<State<T>> + Send + Sync>;
+
+/// Type alias for functions that fold a subgraph's final state back into the parent state
+pub type OutputMapFn<S, T> = Arc<dyn Fn(State<S>, State<T>) -> Result<State<S>> + Send + Sync>;
+
+/// A graph can be used as a node of another graph with the same state type.
+///
+/// Nodes of the inner graph report their path prefixed with the name of the node the
+/// graph was added as, e.g. `research/search`.
+#[async_trait]
+impl<S: StateValue> NodeProcessor<S> for Graph<S> {
+    async fn process(&self, state: State<S>) -> Result<State<S>> {
+        self.execute(state).await
+    }
+
+    async fn process_command(&self, state: State<S>, ctx: &NodeContext<S>) -> Result<Command<S>> {
+        let state = self.execute_in(state, &RunContext::nested(ctx)).await?;
+        Ok(Command::new(state))
+    }
+}
+
+/// A node that runs a graph with a different state type.
+///
+/// The `input` function projects the parent state into the subgraph's initial state,
+/// and the `output` function folds the subgraph's final state back into the parent.
+///
+/// # Examples
+///
+/// ```
+/// use glint::graph::{Graph, GraphBuilder, Subgraph};
+/// use glint::state::State;
+///
+/// let search: Graph<String> = Graph::new();
+///
+/// let node = Subgraph::new(
+///     search,
+///     |parent: &State<Vec<String>>| {
+///         Ok(State::new(parent.data.last().cloned().unwrap_or_default()))
+///     },
+///     |mut parent: State<Vec<String>>, child: State<String>| {
+///         parent.data.push(child.data);
+///         Ok(parent)
+///     },
+/// );
+///
+/// let builder = GraphBuilder::new().with_node("research", node)?;
+/// # Ok::<(), glint::Error>(())
+/// ```
+pub struct Subgraph<S: StateValue, T: StateValue> {
+    graph: Graph<T>,
+    input: InputMapFn<S, T>,
+    output: OutputMapFn<S, T>,
+}
+
+impl<S: StateValue, T: StateValue> Subgraph<S, T> {
+    /// Create a subgraph node from a graph and its input and output projections
+    pub fn new(
+        graph: Graph<T>,
+        input: impl Fn(&State<S>) -> Result<State<T>> + Send + Sync + 'static,
+        output: impl Fn(State<S>, State<T>) -> Result<State<S>> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            graph,
+            input: Arc::new(input),
+            output: Arc::new(output),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: StateValue, T: StateValue> NodeProcessor<S> for Subgraph<S, T> {
+    async fn process(&self, state: State<S>) -> Result<State<S>> {
+        let child_state = (self.input)(&state)?;
+        let child_state = self.graph.execute(child_state).await?;
+        (self.output)(state, child_state)
+    }
+
+    async fn process_command(&self, state: State<S>, ctx: &NodeContext<S>) -> Result<Command<S>> {
+        let child_state = (self.input)(&state)?;
+        let child_state = self
+            .graph
+            .execute_in(child_state, &RunContext::nested(&ctx.for_state::<T>()))
+            .await?;
+        Ok(Command::new((self.output)(state, child_state)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::graph::GraphBuilder;
+
+    struct Append {
+        text: &'static str,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<String> for Append {
+        async fn process(&self, mut state: State<String>) -> Result<State<String>> {
+            state.data.push_str(self.text);
+            Ok(state)
+        }
+    }
+
+    struct Fail;
+
+    #[async_trait]
+    impl NodeProcessor<String> for Fail {
+        async fn process(&self, _state: State<String>) -> Result<State<String>> {
+            Err(Error::LLM("rate limited".to_string()))
+        }
+    }
+
+    fn research_graph(search: impl NodeProcessor<String> + 'static) -> Graph<String> {
+        GraphBuilder::new()
+            .with_node("search", search)
+            .unwrap()
+            .with_start_edge("search")
+            .unwrap()
+            .with_end_edge("search")
+            .unwrap()
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_graph_as_node() {
+        let graph = GraphBuilder::new()
+            .with_node("plan", Append { text: "plan;" })
+            .unwrap()
+            .with_node("research", research_graph(Append { text: "search;" }))
+            .unwrap()
+            .with_start_edge("plan")
+            .unwrap()
+            .with_edge("plan", "research", None)
+            .unwrap()
+            .with_end_edge("research")
+            .unwrap()
+            .build();
+
+        let final_state = graph.execute(State::new(String::new())).await.unwrap();
+        assert_eq!(final_state.data, "plan;search;");
+    }
+
+    #[tokio::test]
+    async fn test_subgraph_error_path() {
+        let graph = GraphBuilder::new()
+            .with_node("research", research_graph(Fail))
+            .unwrap()
+            .with_start_edge("research")
+            .unwrap()
+            .with_end_edge("research")
+            .unwrap()
+            .build();
+
+        let error = graph.execute(State::new(String::new())).await.unwrap_err();
+        assert_eq!(error.failed_node(), Some("research/search"));
+        assert!(matches!(error.root_cause(), Error::LLM(_)));
+        match error {
+            Error::NodeFailed { node, source } => {
+                assert_eq!(node, "research/search");
+                assert!(matches!(*source, Error::LLM(_)));
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subgraph_recursion_trail() {
+        let looping = GraphBuilder::new()
+            .with_node("search", Append { text: "." })
+            .unwrap()
+            .with_start_edge("search")
+            .unwrap()
+            .with_edge("search", "search", None)
+            .unwrap()
+            .with_max_steps(2)
+            .build();
+
+        let graph = GraphBuilder::new()
+            .with_node("research", looping)
+            .unwrap()
+            .with_start_edge("research")
+            .unwrap()
+            .with_end_edge("research")
+            .unwrap()
+            .build();
+
+        match graph.execute(State::new(String::new())).await.unwrap_err() {
+            Error::RecursionLimit { trail, .. } => {
+                assert_eq!(trail, vec!["research/search"; 3]);
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subgraph_with_mapped_state() {
+        let node = Subgraph::new(
+            research_graph(Append { text: " results" }),
+            |parent: &State<Vec<String>>| Ok(State::new(parent.data[0].clone())),
+            |mut parent: State<Vec<String>>, child: State<String>| {
+                parent.data.push(child.data);
+                Ok(parent)
+            },
+        );
+
+        let graph = GraphBuilder::new()
+            .with_node("research", node)
+            .unwrap()
+            .with_start_edge("research")
+            .unwrap()
+            .with_end_edge("research")
+            .unwrap()
+            .build();
+
+        let final_state = graph
+            .execute(State::new(vec!["rust".to_string()]))
+            .await
+            .unwrap();
+        assert_eq!(final_state.data, vec!["rust", "rust results"]);
+    }
+}
@@ -0,0 +1,347 @@
+use async_trait::async_trait;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use super::{Command, Graph, GraphBuilder, NodeContext, NodeProcessor};
+use crate::state::{PartialState, State, StateValue};
+use crate::Result;
+
+/// Trait for node processors that return only the fields they changed.
+///
+/// The update is folded into the node's input state with the fields' reducers,
+/// see [`PartialState`]. When update nodes run as parallel branches, their
+/// updates are folded into the merged state one after the other, so a custom
+/// reducer sees every update once. Register them with [`Graph::add_update_node`].
+///
+/// # Examples
+///
+/// ```
+/// use glint::graph::UpdateProcessor;
+/// use glint::state::{State, StateValue};
+/// use glint::Result;
+/// use async_trait::async_trait;
+///
+/// #[derive(Debug, Clone, StateValue)]
+/// struct ChatState {
+///     #[reducer(append)]
+///     messages: Vec<String>,
+///     turns: i32,
+/// }
+///
+/// struct Reply;
+///
+/// #[async_trait]
+/// impl UpdateProcessor<ChatState> for Reply {
+///     async fn process(&self, state: &State<ChatState>) -> Result<ChatStateUpdate> {
+///         Ok(ChatStateUpdate::default()
+///             .with_messages(vec!["Hello!".to_string()])
+///             .with_turns(state.data.turns + 1))
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait UpdateProcessor<S: PartialState>: Send + Sync {
+    /// Process the state and return new values for the fields that changed
+    async fn process(&self, state: &State<S>) -> Result<S::Update>;
+}
+
+/// Type alias for functions folding a node's update into merged data
+type MergeUpdateFn<S> = Arc<dyn Fn(&mut S, &mut HashSet<&'static str>) -> Result<()> + Send + Sync>;
+
+/// A partial update returned by a node, kept so that parallel branches can be
+/// merged update by update
+pub(crate) struct NodeUpdate<S> {
+    merge: MergeUpdateFn<S>,
+}
+
+impl<S: PartialState> NodeUpdate<S> {
+    fn new(update: S::Update) -> Self {
+        Self {
+            merge: Arc::new(move |data, written| data.merge_update(update.clone(), written)),
+        }
+    }
+}
+
+impl<S: StateValue> NodeUpdate<S> {
+    /// Fold the update into merged data, see [`PartialState::merge_update`]
+    pub(crate) fn merge(&self, data: &mut S, written: &mut HashSet<&'static str>) -> Result<()> {
+        (self.merge)(data, written)
+    }
+}
+
+impl<S> Clone for NodeUpdate<S> {
+    fn clone(&self) -> Self {
+        Self {
+            merge: self.merge.clone(),
+        }
+    }
+}
+
+impl<S> fmt::Debug for NodeUpdate<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("NodeUpdate")
+    }
+}
+
+/// Adapter that lets an [`UpdateProcessor`] be stored as a [`NodeProcessor`]
+struct UpdateNode<P>(P);
+
+#[async_trait]
+impl<S, P> NodeProcessor<S> for UpdateNode<P>
+where
+    S: PartialState,
+    P: UpdateProcessor<S>,
+{
+    async fn process(&self, state: State<S>) -> Result<State<S>> {
+        let update = self.0.process(&state).await?;
+        Ok(state.with_update(update))
+    }
+
+    async fn process_command(&self, state: State<S>, _ctx: &NodeContext<S>) -> Result<Command<S>> {
+        let update = self.0.process(&state).await?;
+        let mut command = Command::new(state.with_update(update.clone()));
+        command.update = Some(NodeUpdate::new(update));
+        Ok(command)
+    }
+}
+
+impl<S: PartialState> Graph<S> {
+    /// Add a node whose processor returns a partial update of the state
+    pub fn add_update_node(
+        &mut self,
+        name: impl Into<String>,
+        processor: impl UpdateProcessor<S> + 'static,
+    ) -> Result<&mut Self> {
+        self.add_node(name, UpdateNode(processor))
+    }
+}
+
+impl<S: PartialState> GraphBuilder<S> {
+    /// Add a node whose processor returns a partial update of the state
+    pub fn with_update_node(
+        mut self,
+        name: impl Into<String>,
+        processor: impl UpdateProcessor<S> + 'static,
+    ) -> Result<Self> {
+        self.graph.add_update_node(name, processor)?;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::ExecutionStrategy;
+    use crate::state::{StateReducer, StateUpdate, StateValue};
+    use serde::{Deserialize, Serialize};
+
+    fn max(current: i64, update: i64) -> i64 {
+        current.max(update)
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, StateValue)]
+    struct SearchState {
+        query: String,
+        #[reducer(append)]
+        documents: Vec<String>,
+        #[reducer(add)]
+        searches: i32,
+        #[reducer(custom = "max")]
+        best_score: i64,
+    }
+
+    /// Finds one document with the given score
+    struct Search {
+        document: &'static str,
+        score: i64,
+    }
+
+    #[async_trait]
+    impl UpdateProcessor<SearchState> for Search {
+        async fn process(&self, _state: &State<SearchState>) -> Result<SearchStateUpdate> {
+            Ok(SearchStateUpdate::default()
+                .with_documents(vec![self.document.to_string()])
+                .with_searches(1)
+                .with_best_score(self.score))
+        }
+    }
+
+    #[test]
+    fn test_apply_update_with_reducers() {
+        let state = SearchState {
+            query: "rust".to_string(),
+            documents: vec!["a".to_string()],
+            searches: 1,
+            best_score: 5,
+        };
+
+        let update = SearchStateUpdate::default()
+            .with_query("rust graphs".to_string())
+            .with_documents(vec!["b".to_string()])
+            .with_searches(2)
+            .with_best_score(3);
+        let updated = update.apply(State::new(state)).unwrap().data;
+        assert_eq!(
+            updated,
+            SearchState {
+                query: "rust graphs".to_string(),
+                documents: vec!["a".to_string(), "b".to_string()],
+                searches: 3,
+                best_score: 5,
+            }
+        );
+
+        // Fields the update does not set are left alone
+        let unchanged = State::new(updated.clone()).with_update(SearchStateUpdate::default());
+        assert_eq!(unchanged.data, updated);
+    }
+
+    #[tokio::test]
+    async fn test_update_nodes_in_parallel_branches() {
+        let graph = GraphBuilder::new()
+            .with_execution_strategy(ExecutionStrategy::Parallel)
+            .with_reducer(SearchState::channel_reducer())
+            .with_update_node(
+                "web",
+                Search {
+                    document: "web",
+                    score: 7,
+                },
+            )
+            .unwrap()
+            .with_update_node(
+                "papers",
+                Search {
+                    document: "papers",
+                    score: 9,
+                },
+            )
+            .unwrap()
+            .with_start_edge("web")
+            .unwrap()
+            .with_start_edge("papers")
+            .unwrap()
+            .with_end_edge("web")
+            .unwrap()
+            .with_end_edge("papers")
+            .unwrap()
+            .build();
+
+        let result = graph
+            .execute(State::new(SearchState::default()))
+            .await
+            .unwrap();
+        let mut documents = result.data.documents.clone();
+        documents.sort();
+        assert_eq!(documents, vec!["papers", "web"]);
+        assert_eq!(result.data.searches, 2);
+        assert_eq!(result.data.best_score, 9);
+    }
+
+    fn sum(current: i64, update: i64) -> i64 {
+        current + update
+    }
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize, StateValue)]
+    struct Tally {
+        #[reducer(custom = "sum")]
+        total: i64,
+        label: String,
+    }
+
+    /// Adds an amount to the total, optionally relabelling the tally
+    struct Count {
+        amount: i64,
+        label: Option<&'static str>,
+    }
+
+    #[async_trait]
+    impl UpdateProcessor<Tally> for Count {
+        async fn process(&self, _state: &State<Tally>) -> Result<TallyUpdate> {
+            let mut update = TallyUpdate::default().with_total(self.amount);
+            if let Some(label) = self.label {
+                update = update.with_label(label.to_string());
+            }
+            Ok(update)
+        }
+    }
+
+    fn tally_graph(counts: Vec<(&'static str, Count)>) -> Graph<Tally> {
+        let mut builder = GraphBuilder::new()
+            .with_execution_strategy(ExecutionStrategy::Parallel)
+            .with_reducer(Tally::channel_reducer());
+        for (name, count) in counts {
+            builder = builder
+                .with_update_node(name, count)
+                .unwrap()
+                .with_start_edge(name)
+                .unwrap()
+                .with_end_edge(name)
+                .unwrap();
+        }
+        builder.build()
+    }
+
+    #[tokio::test]
+    async fn test_parallel_updates_fold_with_custom_reducer() {
+        let count = |amount| Count {
+            amount,
+            label: None,
+        };
+        let graph = tally_graph(vec![("a", count(5)), ("b", count(7)), ("c", count(0))]);
+
+        let initial = Tally {
+            total: 10,
+            label: "votes".to_string(),
+        };
+        let result = graph.execute(State::new(initial)).await.unwrap();
+        assert_eq!(result.data.total, 22);
+        assert_eq!(result.data.label, "votes");
+    }
+
+    #[tokio::test]
+    async fn test_parallel_updates_conflict_without_reducer() {
+        let graph = tally_graph(vec![
+            (
+                "a",
+                Count {
+                    amount: 1,
+                    label: Some("a"),
+                },
+            ),
+            (
+                "b",
+                Count {
+                    amount: 2,
+                    label: Some("b"),
+                },
+            ),
+        ]);
+
+        let error = graph
+            .execute(State::new(Tally::default()))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, crate::error::Error::StateConflict(_)));
+    }
+
+    #[test]
+    fn test_channel_reducer_does_not_combine_custom_fields() {
+        // Whole branch states do not say what each branch added, so the custom
+        // reducer cannot combine them
+        let base = State::new(Tally::default());
+        let branch = |amount| {
+            base.clone()
+                .with_update(TallyUpdate::default().with_total(amount))
+        };
+
+        let reducer = Tally::channel_reducer();
+        let merged = reducer.reduce(&base, vec![branch(5)]).unwrap();
+        assert_eq!(merged.data.total, 5);
+        let error = reducer
+            .reduce(&base, vec![branch(5), branch(7)])
+            .unwrap_err();
+        assert!(matches!(error, crate::error::Error::StateConflict(_)));
+    }
+}
@@ -0,0 +1,283 @@
+use petgraph::visit::{Dfs, EdgeRef, Reversed};
+use petgraph::Direction;
+use std::collections::HashSet;
+
+use super::{EdgeKind, Graph, END, START};
+use crate::error::Error;
+use crate::state::StateValue;
+use crate::Result;
+
+impl<S: StateValue> Graph<S> {
+    /// Check the structure of the graph and report every problem found.
+    ///
+    /// The following are reported, with node names:
+    /// - a missing edge from START
+    /// - nodes that cannot be reached from START or from the error handler
+    /// - nodes that cannot reach END
+    /// - edges into START or out of END
+    /// - duplicate unconditional edges
+    /// - command destinations that were never added to the graph
+    /// - interrupts at nodes that were never added to the graph
+    /// - concurrency tags without a concurrency limit
+    pub fn validate(&self) -> Result<()> {
+        let start_idx = self.node_map[START];
+        let end_idx = self.node_map[END];
+        let mut problems = Vec::new();
+
+        if self.graph.edges(start_idx).next().is_none() {
+            problems.push(format!("No edge from {}", START));
+        }
+
+        for edge in self.graph.edges_directed(start_idx, Direction::Incoming) {
+            problems.push(format!(
+                "Edge into {} from node: {}",
+                START,
+                self.graph[edge.source()]
+            ));
+        }
+
+        for edge in self.graph.edges(end_idx) {
+            problems.push(format!(
+                "Edge out of {} to node: {}",
+                END,
+                self.graph[edge.target()]
+            ));
+        }
+
+        // The error handler and the nodes after it are reached through the
+        // failures of other nodes
+        let mut reachable = HashSet::new();
+        let mut dfs = Dfs::new(&self.graph, start_idx);
+        if let Some(handler) = self.error_handler() {
+            dfs.move_to(self.node_map[handler]);
+            while let Some(node_idx) = dfs.next(&self.graph) {
+                reachable.insert(node_idx);
+            }
+            dfs.move_to(start_idx);
+        }
+        while let Some(node_idx) = dfs.next(&self.graph) {
+            reachable.insert(node_idx);
+        }
+
+        let reversed = Reversed(&self.graph);
+        let mut reaches_end = HashSet::new();
+        let mut dfs = Dfs::new(reversed, end_idx);
+        while let Some(node_idx) = dfs.next(reversed) {
+            reaches_end.insert(node_idx);
+        }
+
+        for node_idx in self.graph.node_indices() {
+            let node_name = &self.graph[node_idx];
+            if node_idx != start_idx && node_idx != end_idx {
+                if !reachable.contains(&node_idx) {
+                    problems.push(format!("Node unreachable from {}: {}", START, node_name));
+                }
+                if !reaches_end.contains(&node_idx) {
+                    problems.push(format!("Node cannot reach {}: {}", END, node_name));
+                }
+            }
+
+            let mut direct_targets = HashSet::new();
+            for edge in self.graph.edges(node_idx) {
+                if matches!(edge.weight(), EdgeKind::Direct)
+                    && !direct_targets.insert(edge.target())
+                {
+                    problems.push(format!(
+                        "Duplicate edge: {} -> {}",
+                        node_name,
+                        self.graph[edge.target()]
+                    ));
+                }
+            }
+        }
+
+        let mut sources: Vec<&String> = self.destinations.keys().collect();
+        sources.sort();
+        for source in sources {
+            for target in &self.destinations[source] {
+                if !self.node_map.contains_key(target) {
+                    problems.push(format!(
+                        "Unknown destination of node {}: {}",
+                        source, target
+                    ));
+                }
+            }
+        }
+
+        let mut interrupts: Vec<&String> = self
+            .interrupt_before
+            .iter()
+            .chain(&self.interrupt_after)
+            .filter(|node| !self.processors.contains_key(*node))
+            .collect();
+        interrupts.sort();
+        interrupts.dedup();
+        for node in interrupts {
+            problems.push(format!("Interrupt at unknown node: {}", node));
+        }
+
+        let mut tagged: Vec<(&String, &String)> = self
+            .concurrency_tags
+            .iter()
+            .filter(|(_, tag)| !self.concurrency_limits.contains_key(*tag))
+            .collect();
+        tagged.sort();
+        for (node, tag) in tagged {
+            problems.push(format!(
+                "Concurrency tag of node {} has no limit: {}",
+                node, tag
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(problems))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{Command, CommandProcessor, GraphBuilder, NodeProcessor};
+    use crate::state::State;
+    use async_trait::async_trait;
+
+    struct Noop;
+
+    #[async_trait]
+    impl NodeProcessor<i32> for Noop {
+        async fn process(&self, state: State<i32>) -> Result<State<i32>> {
+            Ok(state)
+        }
+    }
+
+    #[async_trait]
+    impl CommandProcessor<i32> for Noop {
+        async fn process(&self, state: State<i32>) -> Result<Command<i32>> {
+            Ok(Command::new(state))
+        }
+    }
+
+    #[test]
+    fn test_compile_valid_graph() {
+        let graph = GraphBuilder::new()
+            .with_node("a", Noop)
+            .unwrap()
+            .with_node("b", Noop)
+            .unwrap()
+            .with_start_edge("a")
+            .unwrap()
+            .with_edge("a", "b", None)
+            .unwrap()
+            .with_edge("b", "a", None)
+            .unwrap()
+            .with_end_edge("b")
+            .unwrap()
+            .compile();
+        assert!(graph.is_ok());
+    }
+
+    #[test]
+    fn test_compile_reports_all_problems() {
+        let result = GraphBuilder::new()
+            .with_node("a", Noop)
+            .unwrap()
+            .with_node("orphan", Noop)
+            .unwrap()
+            .with_command_node("router", Noop, ["missing"])
+            .unwrap()
+            .with_edge("a", "a", None)
+            .unwrap()
+            .with_edge("a", "a", None)
+            .unwrap()
+            .with_edge("a", START, None)
+            .unwrap()
+            .with_edge(END, "orphan", None)
+            .unwrap()
+            .interrupt_before(["tools"])
+            .compile();
+
+        let problems = match result {
+            Err(Error::Validation(problems)) => problems,
+            _ => panic!("expected validation error"),
+        };
+        for expected in [
+            format!("No edge from {}", START),
+            format!("Edge into {} from node: a", START),
+            format!("Edge out of {} to node: orphan", END),
+            format!("Node unreachable from {}: a", START),
+            format!("Node cannot reach {}: a", END),
+            format!("Node unreachable from {}: orphan", START),
+            format!("Node cannot reach {}: router", END),
+            "Duplicate edge: a -> a".to_string(),
+            "Unknown destination of node router: missing".to_string(),
+            "Interrupt at unknown node: tools".to_string(),
+        ] {
+            assert!(
+                problems.contains(&expected),
+                "missing problem: {}",
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_compile_reports_duplicate_start_edges() {
+        let result = GraphBuilder::new()
+            .with_node("a", Noop)
+            .unwrap()
+            .with_start_edge("a")
+            .unwrap()
+            .with_start_edge("a")
+            .unwrap()
+            .with_end_edge("a")
+            .unwrap()
+            .compile();
+
+        match result {
+            Err(Error::Validation(problems)) => {
+                assert_eq!(problems, vec![format!("Duplicate edge: {} -> a", START)])
+            }
+            _ => panic!("expected validation error"),
+        }
+    }
+
+    #[test]
+    fn test_compile_accepts_error_handler_chain() {
+        let builder = || {
+            GraphBuilder::new()
+                .with_node("a", Noop)
+                .unwrap()
+                .with_node("handler", Noop)
+                .unwrap()
+                .with_node("cleanup", Noop)
+                .unwrap()
+                .with_start_edge("a")
+                .unwrap()
+                .with_end_edge("a")
+                .unwrap()
+                .with_edge("handler", "cleanup", None)
+                .unwrap()
+                .with_end_edge("cleanup")
+                .unwrap()
+        };
+        assert!(builder()
+            .with_error_handler("handler")
+            .unwrap()
+            .compile()
+            .is_ok());
+
+        match builder().compile() {
+            Err(Error::Validation(problems)) => assert_eq!(
+                problems,
+                vec![
+                    format!("Node unreachable from {}: handler", START),
+                    format!("Node unreachable from {}: cleanup", START),
+                ]
+            ),
+            _ => panic!("expected validation error"),
+        }
+    }
+}
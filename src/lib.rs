@@ -1,3 +1,5 @@
+pub mod cache;
+pub mod callbacks;
 pub mod checkpoint;
 pub mod document_loaders;
 pub mod embeddings;
@@ -6,19 +8,27 @@ pub mod graph;
 pub mod llms;
 pub mod pregel;
 pub mod prompts;
+pub mod replay;
 pub mod schema;
 pub mod serialization;
 pub mod state;
+pub mod testing;
 pub mod text_splitters;
+pub mod trace;
 pub mod traits;
 pub mod utils;
 pub mod vectorstores;
 
+// Lets code generated by glint-derive refer to `::glint` within this crate
+extern crate self as glint;
+
 pub use error::Error;
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// Re-exports for common types
 pub mod prelude {
+    pub use crate::cache::*;
+    pub use crate::callbacks::*;
     pub use crate::checkpoint::*;
     pub use crate::error::Error;
     pub use crate::graph::*;
@@ -2,7 +2,10 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::Instrument;
 
+use crate::callbacks::{CallbackHandler, Callbacks, TokenUsage};
 use crate::error::Error;
 use crate::schema::{Message, MessageRole};
 use crate::traits::{ChatModel, Runnable};
@@ -53,6 +56,16 @@ struct ChatOpenAIUsage {
     total_tokens: u32,
 }
 
+impl From<ChatOpenAIUsage> for TokenUsage {
+    fn from(usage: ChatOpenAIUsage) -> Self {
+        Self {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+        }
+    }
+}
+
 /// OpenAI chat model implementation
 pub struct ChatOpenAI {
     api_key: String,
@@ -65,6 +78,7 @@ pub struct ChatOpenAI {
     n: Option<u32>,
     stop: Option<Vec<String>>,
     client: reqwest::Client,
+    callbacks: Callbacks,
 }
 
 impl ChatOpenAI {
@@ -81,6 +95,7 @@ impl ChatOpenAI {
             n: None,
             stop: None,
             client: reqwest::Client::new(),
+            callbacks: Callbacks::new(),
         }
     }
 
@@ -96,6 +111,12 @@ impl ChatOpenAI {
         self
     }
 
+    /// Register a handler called on every request to the model
+    pub fn with_callback(mut self, handler: Arc<dyn CallbackHandler>) -> Self {
+        self.callbacks.add(handler);
+        self
+    }
+
     /// Convert messages to OpenAI format
     fn convert_messages(&self, messages: &[Message]) -> Vec<ChatOpenAIMessage> {
         messages
@@ -117,16 +138,14 @@ impl ChatOpenAI {
             })
             .collect()
     }
-}
 
-#[async_trait]
-impl Runnable<Vec<Message>, Message> for ChatOpenAI {
-    async fn invoke(&self, input: Vec<Message>) -> Result<Message> {
+    /// Request a chat completion, returning the reply and the tokens it used
+    async fn complete(&self, input: &[Message]) -> Result<(Message, Option<TokenUsage>)> {
         if input.is_empty() {
             return Err(Error::LLM("No messages provided".to_string()));
         }
 
-        let openai_messages = self.convert_messages(&input);
+        let openai_messages = self.convert_messages(input);
 
         let request = ChatOpenAIRequest {
             model: self.model.clone(),
@@ -167,6 +186,7 @@ impl Runnable<Vec<Message>, Message> for ChatOpenAI {
             return Err(Error::LLM("No chat completions returned".to_string()));
         }
 
+        let usage = response.usage.map(TokenUsage::from);
         let choice = &response.choices[0];
         let role = match choice.message.role.as_str() {
             "system" => MessageRole::System,
@@ -176,7 +196,36 @@ impl Runnable<Vec<Message>, Message> for ChatOpenAI {
             _ => MessageRole::Assistant, // Default to assistant for unknown roles
         };
 
-        Ok(Message::new(role, choice.message.content.clone()))
+        Ok((Message::new(role, choice.message.content.clone()), usage))
+    }
+}
+
+#[async_trait]
+impl Runnable<Vec<Message>, Message> for ChatOpenAI {
+    async fn invoke(&self, input: Vec<Message>) -> Result<Message> {
+        let span = tracing::info_span!(
+            "llm",
+            model = %self.model,
+            prompt_tokens = tracing::field::Empty,
+            completion_tokens = tracing::field::Empty,
+            total_tokens = tracing::field::Empty
+        );
+        self.callbacks.on_chat_model_start(&self.model, &input);
+        match self.complete(&input).instrument(span.clone()).await {
+            Ok((message, usage)) => {
+                if let Some(usage) = &usage {
+                    usage.record(&span);
+                }
+                self.callbacks
+                    .on_llm_end(&self.model, &message.content, usage.as_ref());
+                Ok(message)
+            }
+            Err(e) => {
+                tracing::warn!(parent: &span, error = %e, "Chat completion failed");
+                self.callbacks.on_llm_error(&self.model, &e);
+                Err(e)
+            }
+        }
     }
 }
 
@@ -2,7 +2,10 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::Instrument;
 
+use crate::callbacks::{CallbackHandler, Callbacks, TokenUsage};
 use crate::error::Error;
 use crate::traits::{LanguageModel, Runnable};
 use crate::Result;
@@ -44,6 +47,16 @@ struct OpenAIUsage {
     total_tokens: u32,
 }
 
+impl From<OpenAIUsage> for TokenUsage {
+    fn from(usage: OpenAIUsage) -> Self {
+        Self {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+        }
+    }
+}
+
 /// OpenAI LLM implementation
 pub struct OpenAI {
     api_key: String,
@@ -56,6 +69,7 @@ pub struct OpenAI {
     n: Option<u32>,
     stop: Option<Vec<String>>,
     client: reqwest::Client,
+    callbacks: Callbacks,
 }
 
 impl OpenAI {
@@ -72,6 +86,7 @@ impl OpenAI {
             n: None,
             stop: None,
             client: reqwest::Client::new(),
+            callbacks: Callbacks::new(),
         }
     }
 
@@ -87,6 +102,12 @@ impl OpenAI {
         self
     }
 
+    /// Register a handler called on every request to the model
+    pub fn with_callback(mut self, handler: Arc<dyn CallbackHandler>) -> Self {
+        self.callbacks.add(handler);
+        self
+    }
+
     /// Create a completion request from the prompt
     fn create_request(&self, prompt: &str) -> OpenAICompletionRequest {
         OpenAICompletionRequest {
@@ -101,12 +122,10 @@ impl OpenAI {
             frequency_penalty: self.frequency_penalty,
         }
     }
-}
 
-#[async_trait]
-impl Runnable<String, String> for OpenAI {
-    async fn invoke(&self, input: String) -> Result<String> {
-        let request = self.create_request(&input);
+    /// Request a completion, returning the text and the tokens it used
+    async fn complete(&self, prompt: &str) -> Result<(String, Option<TokenUsage>)> {
+        let request = self.create_request(prompt);
 
         let res = self
             .client
@@ -135,7 +154,38 @@ impl Runnable<String, String> for OpenAI {
             return Err(Error::LLM("No completions returned".to_string()));
         }
 
-        Ok(completion.choices[0].text.clone())
+        let usage = completion.usage.map(TokenUsage::from);
+        Ok((completion.choices[0].text.clone(), usage))
+    }
+}
+
+#[async_trait]
+impl Runnable<String, String> for OpenAI {
+    async fn invoke(&self, input: String) -> Result<String> {
+        let span = tracing::info_span!(
+            "llm",
+            model = %self.model,
+            prompt_tokens = tracing::field::Empty,
+            completion_tokens = tracing::field::Empty,
+            total_tokens = tracing::field::Empty
+        );
+        self.callbacks
+            .on_llm_start(&self.model, std::slice::from_ref(&input));
+        match self.complete(&input).instrument(span.clone()).await {
+            Ok((text, usage)) => {
+                if let Some(usage) = &usage {
+                    usage.record(&span);
+                }
+                self.callbacks
+                    .on_llm_end(&self.model, &text, usage.as_ref());
+                Ok(text)
+            }
+            Err(e) => {
+                tracing::warn!(parent: &span, error = %e, "Completion failed");
+                self.callbacks.on_llm_error(&self.model, &e);
+                Err(e)
+            }
+        }
     }
 }
 
@@ -2,6 +2,8 @@ use async_trait::async_trait;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::Debug;
 use std::sync::Arc;
+use tracing::Instrument;
+use uuid::Uuid;
 
 use crate::error::Error;
 use crate::state::{State, StateValue};
@@ -97,6 +99,7 @@ impl<S: StateValue> PregelGraph<S> {
 
         let mut message_queue = VecDeque::new();
         message_queue.push_back(initial_message);
+        let run_id = Uuid::new_v4().to_string();
 
         let mut step_count = 0;
         let max_steps = max_steps.unwrap_or(1000); // Default to 1000 steps to prevent infinite loops
@@ -135,7 +138,13 @@ impl<S: StateValue> PregelGraph<S> {
                     .get(&node_name)
                     .ok_or_else(|| Error::InvalidNode(format!("Node not found: {}", node_name)))?;
 
-                let result_messages = node.process(messages).await?;
+                let span = tracing::info_span!(
+                    "pregel_node",
+                    run_id = %run_id,
+                    node = %node_name,
+                    step = step_count
+                );
+                let result_messages = node.process(messages).instrument(span).await?;
 
                 // Validate and enqueue new messages
                 for msg in result_messages {
@@ -170,10 +179,9 @@ impl<S: StateValue> PregelGraph<S> {
             }
 
             // If message queue is empty but we have sink messages, return the last one's state
-            if message_queue.is_empty() && sink_messages.is_some() {
-                let messages = sink_messages.unwrap();
-                if !messages.is_empty() {
-                    return Ok(messages.last().unwrap().payload.clone());
+            if message_queue.is_empty() {
+                if let Some(last) = sink_messages.as_ref().and_then(|messages| messages.last()) {
+                    return Ok(last.payload.clone());
                 }
             }
         }
@@ -0,0 +1,645 @@
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::graph::is_retryable;
+use crate::schema::Message;
+use crate::trace;
+use crate::traits::{ChatModel, EmbeddingModel, LanguageModel, Runnable};
+use crate::Result;
+
+tokio::task_local! {
+    static CASSETTE: Cassette;
+}
+
+/// What a recorded interaction was
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InteractionKind {
+    /// Call of a language model
+    Llm,
+    /// Call of a chat model
+    ChatModel,
+    /// Call of an embedding model
+    Embedding,
+    /// Call of a tool
+    Tool,
+    /// Generation of a random ID
+    Id,
+    /// Reading of the current time
+    Timestamp,
+}
+
+/// A single recorded interaction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    /// What the interaction was
+    pub kind: InteractionKind,
+    /// Name of the model or tool called
+    pub name: String,
+    /// Path of the node the interaction happened in, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<String>,
+    /// Input of the call
+    #[serde(default)]
+    pub input: Value,
+    /// Output of the call
+    #[serde(default)]
+    pub output: Value,
+    /// Message of the error the call failed with, if it failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Whether the error the call failed with was retryable
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub retryable: bool,
+}
+
+/// The contents of a cassette file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    /// The recorded interactions, in the order they happened
+    pub interactions: Vec<Interaction>,
+    /// The final state of the recorded run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_state: Option<Value>,
+}
+
+/// A recording, which of its interactions were replayed, and the first value the
+/// recording did not have
+#[derive(Debug, Default)]
+struct Tape {
+    recording: Recording,
+    replayed: Vec<bool>,
+    missed: Option<String>,
+}
+
+/// Records the interactions of runs, or replays them from an earlier recording
+///
+/// A cassette records every non-deterministic interaction of a run: model and tool
+/// calls made through [`Recorded`] or [`Cassette::call`], and the IDs and
+/// timestamps of messages and checkpoints. Replaying the run against a saved
+/// cassette returns the recorded results instead, so a production incident can be
+/// turned into an offline regression test. Interactions are matched to the node
+/// that made them, in order, so runs with concurrent nodes replay
+/// deterministically.
+///
+/// A cassette applies to the futures run with [`Cassette::scope`], e.g. a run
+/// executed with [`Graph::execute_with_cassette`](crate::graph::Graph::execute_with_cassette).
+///
+/// # Examples
+///
+/// ```no_run
+/// # use glint::graph::{Graph, RunConfig};
+/// # use glint::replay::Cassette;
+/// # use glint::state::State;
+/// # async fn regression(graph: Graph<String>) -> glint::Result<()> {
+/// // Replay a run recorded in production and check it ends the same way
+/// let cassette = Cassette::load("tests/cassettes/incident-1234.json")?;
+/// let input = State::new("Why was I charged twice?".to_string());
+/// graph.execute_with_cassette(input, &RunConfig::new(), &cassette).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Cassette {
+    replaying: bool,
+    tape: Arc<Mutex<Tape>>,
+}
+
+impl fmt::Debug for Cassette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tape = self.tape.lock().unwrap();
+        f.debug_struct("Cassette")
+            .field("replaying", &self.replaying)
+            .field("interactions", &tape.recording.interactions.len())
+            .finish()
+    }
+}
+
+impl Default for Cassette {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cassette {
+    /// Create an empty cassette that records
+    pub fn new() -> Self {
+        Self {
+            replaying: false,
+            tape: Arc::new(Mutex::new(Tape::default())),
+        }
+    }
+
+    /// Create a cassette that replays a recording
+    pub fn replay(recording: Recording) -> Self {
+        let tape = Tape {
+            replayed: vec![false; recording.interactions.len()],
+            recording,
+            missed: None,
+        };
+        Self {
+            replaying: true,
+            tape: Arc::new(Mutex::new(tape)),
+        }
+    }
+
+    /// Load a cassette file saved with [`Cassette::save`] for replaying
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Ok(Self::replay(serde_json::from_str(&content)?))
+    }
+
+    /// Save the recording to a cassette file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self.recording())?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Check whether the cassette replays rather than records
+    pub fn is_replaying(&self) -> bool {
+        self.replaying
+    }
+
+    /// Get a copy of the recording
+    pub fn recording(&self) -> Recording {
+        self.tape.lock().unwrap().recording.clone()
+    }
+
+    /// Run a future with this cassette recording or replaying its interactions
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        CASSETTE.scope(self.clone(), future).await
+    }
+
+    /// Get the cassette of the current task, if any
+    pub fn current() -> Option<Cassette> {
+        CASSETTE.try_with(|cassette| cassette.clone()).ok()
+    }
+
+    /// Record the final state of a run, or check it against the recorded one
+    ///
+    /// When replaying, this also fails if the run asked for an ID or timestamp the
+    /// recording does not have, even where the caller could not report it.
+    pub fn finish(&self, final_state: Value) -> Result<()> {
+        let mut tape = self.tape.lock().unwrap();
+        if !self.replaying {
+            tape.recording.final_state = Some(final_state);
+            return Ok(());
+        }
+        if let Some(message) = &tape.missed {
+            return Err(Error::Replay(message.clone()));
+        }
+
+        match &tape.recording.final_state {
+            Some(recorded) if *recorded != final_state => Err(Error::Replay(format!(
+                "Final state differs from the recording: expected {}, got {}",
+                recorded, final_state
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Make a call through the cassette of the current task
+    ///
+    /// When recording, `call` runs and its input and result are recorded. When
+    /// replaying, `call` does not run; the next recorded call of the same kind and
+    /// name made by the current node is returned, after checking that it had the
+    /// same input. Recorded failures are returned as [`Error::Replayed`]. Without a
+    /// cassette, `call` simply runs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glint::replay::{Cassette, InteractionKind};
+    ///
+    /// # async fn search(query: &str) -> glint::Result<Vec<String>> { Ok(Vec::new()) }
+    /// # async fn run() -> glint::Result<()> {
+    /// let query = "rust async";
+    /// let results: Vec<String> =
+    ///     Cassette::call(InteractionKind::Tool, "web_search", &query, search(query)).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn call<I, O>(
+        kind: InteractionKind,
+        name: &str,
+        input: &I,
+        call: impl Future<Output = Result<O>>,
+    ) -> Result<O>
+    where
+        I: Serialize + ?Sized,
+        O: Serialize + DeserializeOwned,
+    {
+        let cassette = match Self::current() {
+            Some(cassette) => cassette,
+            None => return call.await,
+        };
+        let input = serde_json::to_value(input)?;
+
+        if cassette.replaying {
+            let interaction = cassette.next(kind, name, &input)?;
+            return match interaction.error {
+                Some(message) => Err(Error::Replayed {
+                    message,
+                    retryable: interaction.retryable,
+                }),
+                None => Ok(serde_json::from_value(interaction.output)?),
+            };
+        }
+
+        let result = call.await;
+        let (output, error, retryable) = match &result {
+            Ok(output) => (serde_json::to_value(output)?, None, false),
+            Err(e) => (Value::Null, Some(e.to_string()), is_retryable(e)),
+        };
+        cassette.record(Interaction {
+            kind,
+            name: name.to_string(),
+            node: current_node(),
+            input,
+            output,
+            error,
+            retryable,
+        });
+        result
+    }
+
+    /// Record or replay a value produced without input, such as an ID
+    ///
+    /// A replayed value the recording does not have is an [`Error::Replay`], which is
+    /// also remembered so [`Cassette::finish`] fails the run.
+    fn value(
+        &self,
+        kind: InteractionKind,
+        name: &str,
+        produce: impl FnOnce() -> Value,
+    ) -> Result<Value> {
+        if self.replaying {
+            return match self.next(kind, name, &Value::Null) {
+                Ok(interaction) => Ok(interaction.output),
+                Err(e) => {
+                    self.tape
+                        .lock()
+                        .unwrap()
+                        .missed
+                        .get_or_insert_with(|| e.to_string());
+                    Err(e)
+                }
+            };
+        }
+
+        let output = produce();
+        self.record(Interaction {
+            kind,
+            name: name.to_string(),
+            node: current_node(),
+            input: Value::Null,
+            output: output.clone(),
+            error: None,
+            retryable: false,
+        });
+        Ok(output)
+    }
+
+    /// Append an interaction to the recording
+    fn record(&self, interaction: Interaction) {
+        self.tape
+            .lock()
+            .unwrap()
+            .recording
+            .interactions
+            .push(interaction);
+    }
+
+    /// Take the next recorded interaction of a kind and name made by the current node
+    fn next(&self, kind: InteractionKind, name: &str, input: &Value) -> Result<Interaction> {
+        let node = current_node();
+        let mut tape = self.tape.lock().unwrap();
+        let Tape {
+            recording,
+            replayed,
+            ..
+        } = &mut *tape;
+
+        let position = recording
+            .interactions
+            .iter()
+            .zip(replayed.iter())
+            .position(|(interaction, replayed)| {
+                !replayed
+                    && interaction.kind == kind
+                    && interaction.name == name
+                    && interaction.node == node
+            })
+            .ok_or_else(|| {
+                Error::Replay(format!(
+                    "No recorded {:?} interaction with {} at node {}",
+                    kind,
+                    name,
+                    node.as_deref().unwrap_or("-")
+                ))
+            })?;
+        replayed[position] = true;
+
+        let interaction = recording.interactions[position].clone();
+        if interaction.input != *input {
+            return Err(Error::Replay(format!(
+                "Input of {} at node {} differs from the recording: expected {}, got {}",
+                name,
+                node.as_deref().unwrap_or("-"),
+                interaction.input,
+                input
+            )));
+        }
+        Ok(interaction)
+    }
+}
+
+/// Get the path of the node the current task is executing, if any
+fn current_node() -> Option<String> {
+    trace::current_scope().and_then(|scope| scope.node)
+}
+
+/// Generate a random ID, recorded or replayed by the current task's cassette
+///
+/// Fails with [`Error::Replay`] when replaying a run that did not record an ID here.
+pub fn new_id() -> Result<String> {
+    let produce = || json!(Uuid::new_v4().to_string());
+    match Cassette::current() {
+        Some(cassette) => match cassette.value(InteractionKind::Id, "uuid", produce)? {
+            Value::String(id) => Ok(id),
+            other => Ok(other.to_string()),
+        },
+        None => Ok(Uuid::new_v4().to_string()),
+    }
+}
+
+/// Get the time since the Unix epoch, recorded or replayed by the current task's
+/// cassette with millisecond precision
+///
+/// Fails with [`Error::Replay`] when replaying a run that did not record a timestamp
+/// here.
+pub fn unix_time() -> Result<Duration> {
+    let now = || {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    };
+    match Cassette::current() {
+        Some(cassette) => {
+            let millis = cassette.value(InteractionKind::Timestamp, "unix_time", || {
+                json!(now().as_millis() as u64)
+            })?;
+            Ok(Duration::from_millis(millis.as_u64().unwrap_or_default()))
+        }
+        None => Ok(now()),
+    }
+}
+
+/// A model whose calls are recorded or replayed by the current task's cassette
+///
+/// Wraps language, chat and embedding models. Calls made without a cassette go
+/// straight to the wrapped model.
+#[derive(Debug, Clone)]
+pub struct Recorded<M> {
+    inner: M,
+}
+
+impl<M> Recorded<M> {
+    /// Wrap a model
+    pub fn new(inner: M) -> Self {
+        Self { inner }
+    }
+
+    /// Get the wrapped model
+    pub fn into_inner(self) -> M {
+        self.inner
+    }
+}
+
+#[async_trait]
+impl<M: LanguageModel + Send + Sync> Runnable<String, String> for Recorded<M> {
+    async fn invoke(&self, input: String) -> Result<String> {
+        let call = Runnable::<String, String>::invoke(&self.inner, input.clone());
+        let name = LanguageModel::model_name(&self.inner);
+        Cassette::call(InteractionKind::Llm, name, &input, call).await
+    }
+}
+
+impl<M: LanguageModel + Send + Sync> LanguageModel for Recorded<M> {
+    fn model_name(&self) -> &str {
+        LanguageModel::model_name(&self.inner)
+    }
+
+    fn parameters(&self) -> HashMap<String, Value> {
+        LanguageModel::parameters(&self.inner)
+    }
+}
+
+#[async_trait]
+impl<M: ChatModel + Send + Sync> Runnable<Vec<Message>, Message> for Recorded<M> {
+    async fn invoke(&self, input: Vec<Message>) -> Result<Message> {
+        // Message IDs are random, so only what the model sees is compared
+        let prompt: Vec<Value> = input
+            .iter()
+            .map(|message| json!({"role": message.role, "content": message.content}))
+            .collect();
+        let call = Runnable::<Vec<Message>, Message>::invoke(&self.inner, input);
+        let name = ChatModel::model_name(&self.inner);
+        Cassette::call(InteractionKind::ChatModel, name, &prompt, call).await
+    }
+}
+
+impl<M: ChatModel + Send + Sync> ChatModel for Recorded<M> {
+    fn model_name(&self) -> &str {
+        ChatModel::model_name(&self.inner)
+    }
+
+    fn parameters(&self) -> HashMap<String, Value> {
+        ChatModel::parameters(&self.inner)
+    }
+}
+
+#[async_trait]
+impl<M: EmbeddingModel> Runnable<String, Vec<f32>> for Recorded<M> {
+    async fn invoke(&self, input: String) -> Result<Vec<f32>> {
+        let call = Runnable::<String, Vec<f32>>::invoke(&self.inner, input.clone());
+        let name = EmbeddingModel::model_name(&self.inner);
+        Cassette::call(InteractionKind::Embedding, name, &input, call).await
+    }
+}
+
+#[async_trait]
+impl<M: EmbeddingModel> EmbeddingModel for Recorded<M> {
+    fn model_name(&self) -> &str {
+        EmbeddingModel::model_name(&self.inner)
+    }
+
+    fn embedding_dimension(&self) -> usize {
+        self.inner.embedding_dimension()
+    }
+
+    async fn embed_batch(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let call = self.inner.embed_batch(texts.clone());
+        let name = EmbeddingModel::model_name(&self.inner);
+        Cassette::call(InteractionKind::Embedding, name, &texts, call).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{GraphBuilder, NodeProcessor, RunConfig};
+    use crate::llms::MockLLM;
+    use crate::state::{State, StateValue};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    struct Chat {
+        question: String,
+        messages: Vec<Message>,
+    }
+
+    impl StateValue for Chat {}
+
+    /// Answers the question with a language model, counting the real calls
+    struct Answer {
+        llm: Recorded<MockLLM>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<Chat> for Answer {
+        async fn process(&self, mut state: State<Chat>) -> Result<State<Chat>> {
+            if Cassette::current().is_none_or(|cassette| !cassette.is_replaying()) {
+                self.calls.fetch_add(1, Ordering::SeqCst);
+            }
+            let answer = self.llm.invoke(state.data.question.clone()).await?;
+            state.data.messages.push(Message::assistant(answer));
+            Ok(state)
+        }
+    }
+
+    fn answer_graph(calls: &Arc<AtomicUsize>) -> crate::graph::Graph<Chat> {
+        let llm = MockLLM::new().with_response("Why?", "Because.");
+        GraphBuilder::new()
+            .with_node(
+                "answer",
+                Answer {
+                    llm: Recorded::new(llm),
+                    calls: calls.clone(),
+                },
+            )
+            .unwrap()
+            .with_start_edge("answer")
+            .unwrap()
+            .with_end_edge("answer")
+            .unwrap()
+            .build()
+    }
+
+    fn question(text: &str) -> State<Chat> {
+        State::new(Chat {
+            question: text.to_string(),
+            messages: Vec::new(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_replay_reproduces_run() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let graph = answer_graph(&calls);
+        let config = RunConfig::new();
+
+        let cassette = Cassette::new();
+        let recorded = graph
+            .execute_with_cassette(question("Why?"), &config, &cassette)
+            .await
+            .unwrap();
+        let kinds: Vec<InteractionKind> = cassette
+            .recording()
+            .interactions
+            .iter()
+            .map(|interaction| interaction.kind)
+            .collect();
+        assert_eq!(kinds, vec![InteractionKind::Llm, InteractionKind::Id]);
+
+        let path = std::env::temp_dir().join(format!("glint-cassette-{}.json", Uuid::new_v4()));
+        cassette.save(&path).unwrap();
+        let replay = Cassette::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let replayed = graph
+            .execute_with_cassette(question("Why?"), &config, &replay)
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(replayed.data.messages[0].content, "Because.");
+        assert_eq!(replayed.data.messages[0].id, recorded.data.messages[0].id);
+    }
+
+    #[tokio::test]
+    async fn test_replay_fails_on_missing_value() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let graph = answer_graph(&calls);
+        let config = RunConfig::new();
+
+        let cassette = Cassette::new();
+        graph
+            .execute_with_cassette(question("Why?"), &config, &cassette)
+            .await
+            .unwrap();
+
+        let mut recording = cassette.recording();
+        recording
+            .interactions
+            .retain(|interaction| interaction.kind != InteractionKind::Id);
+        assert!(matches!(
+            Cassette::replay(recording.clone())
+                .scope(async { new_id() })
+                .await,
+            Err(Error::Replay(_))
+        ));
+
+        let replay = Cassette::replay(recording);
+        let error = graph
+            .execute_with_cassette(question("Why?"), &config, &replay)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::Replay(_)));
+    }
+
+    #[tokio::test]
+    async fn test_replay_detects_divergence() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let graph = answer_graph(&calls);
+        let config = RunConfig::new();
+
+        let cassette = Cassette::new();
+        graph
+            .execute_with_cassette(question("Why?"), &config, &cassette)
+            .await
+            .unwrap();
+
+        let replay = Cassette::replay(cassette.recording());
+        let error = graph
+            .execute_with_cassette(question("How?"), &config, &replay)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::NodeFailed { ref source, .. } if matches!(**source, Error::Replay(_))
+        ));
+    }
+}
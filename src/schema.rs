@@ -1,6 +1,5 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use uuid::Uuid;
 
 /// Document represents a piece of text and associated metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -68,11 +67,14 @@ pub struct Message {
 
 impl Message {
     /// Create a new message
+    ///
+    /// The message has no ID when replaying a run that did not record one here;
+    /// the replayed run then fails when it finishes.
     pub fn new(role: MessageRole, content: impl Into<String>) -> Self {
         Self {
             role,
             content: content.into(),
-            id: Some(Uuid::new_v4().to_string()),
+            id: crate::replay::new_id().ok(),
             metadata: HashMap::new(),
             priority: 0,
         }
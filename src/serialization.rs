@@ -1,18 +1,138 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
 
+use crate::graph::{END, START};
+use crate::Result;
+
 /// A serializable representation of a graph
+///
+/// In JSON and YAML, `nodes` lists every node either by its name alone or as a
+/// map with its name, type and configuration; the maps are kept in
+/// `node_definitions`. A definition whose nodes have types can be turned into a
+/// runnable graph with [`NodeRegistry::load`](crate::graph::NodeRegistry::load).
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "GraphDefinition", into = "GraphDefinition")]
 pub struct SerializableGraph {
     /// Nodes in the graph
     pub nodes: Vec<String>,
+    /// Types and configurations of the nodes that have them
+    pub node_definitions: Vec<SerializableNode>,
     /// Edges in the graph with their conditions
     pub edges: Vec<SerializableEdge>,
     /// Graph metadata
-    #[serde(default)]
     pub metadata: HashMap<String, serde_json::Value>,
 }
 
+/// The form a graph definition is written in
+#[derive(Serialize, Deserialize)]
+struct GraphDefinition {
+    nodes: Vec<NodeDefinition>,
+    edges: Vec<SerializableEdge>,
+    #[serde(default)]
+    metadata: HashMap<String, serde_json::Value>,
+}
+
+impl From<GraphDefinition> for SerializableGraph {
+    fn from(definition: GraphDefinition) -> Self {
+        let nodes: Vec<SerializableNode> = definition
+            .nodes
+            .into_iter()
+            .map(SerializableNode::from)
+            .collect();
+        Self {
+            nodes: nodes.iter().map(|node| node.name.clone()).collect(),
+            node_definitions: nodes
+                .into_iter()
+                .filter(|node| node.node_type.is_some() || !node.config.is_null())
+                .collect(),
+            edges: definition.edges,
+            metadata: definition.metadata,
+        }
+    }
+}
+
+impl From<SerializableGraph> for GraphDefinition {
+    fn from(graph: SerializableGraph) -> Self {
+        let nodes = graph
+            .nodes
+            .iter()
+            .map(|name| match graph.node_definition(name) {
+                Some(node) => NodeDefinition::Full {
+                    name: node.name.clone(),
+                    node_type: node.node_type.clone(),
+                    config: node.config.clone(),
+                },
+                None => NodeDefinition::Name(name.clone()),
+            })
+            .collect();
+        Self {
+            nodes,
+            edges: graph.edges,
+            metadata: graph.metadata,
+        }
+    }
+}
+
+/// A serializable node
+///
+/// A node is deserialized from either its name alone or a map with its name,
+/// type and configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "NodeDefinition")]
+pub struct SerializableNode {
+    /// Name of the node
+    pub name: String,
+    /// Name the node's processor type is registered under
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub node_type: Option<String>,
+    /// Configuration passed to the factory of the node's type
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub config: Value,
+}
+
+impl SerializableNode {
+    /// Create a node without a type
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            node_type: None,
+            config: Value::Null,
+        }
+    }
+}
+
+/// The forms a node can be written in
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum NodeDefinition {
+    Name(String),
+    Full {
+        name: String,
+        #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+        node_type: Option<String>,
+        #[serde(default, skip_serializing_if = "Value::is_null")]
+        config: Value,
+    },
+}
+
+impl From<NodeDefinition> for SerializableNode {
+    fn from(definition: NodeDefinition) -> Self {
+        match definition {
+            NodeDefinition::Name(name) => Self::new(name),
+            NodeDefinition::Full {
+                name,
+                node_type,
+                config,
+            } => Self {
+                name,
+                node_type,
+                config,
+            },
+        }
+    }
+}
+
 /// A serializable edge
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SerializableEdge {
@@ -21,39 +141,172 @@ pub struct SerializableEdge {
     /// Target node
     pub to: String,
     /// If this edge has a condition
+    #[serde(default)]
     pub has_condition: bool,
+    /// Name the edge's condition is registered under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
     /// Description of the condition (for documentation)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub condition_description: Option<String>,
+    /// If this edge is taken when the source node fails
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub on_error: bool,
+}
+
+impl SerializableEdge {
+    /// Get the text shown on the edge: its description, else its condition
+    /// name, else "error" for error edges
+    pub fn label(&self) -> Option<&str> {
+        self.condition_description
+            .as_deref()
+            .or(self.condition.as_deref())
+            .or(if self.on_error { Some("error") } else { None })
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
 }
 
 impl SerializableGraph {
+    /// Get the type and configuration of a node, if it has them
+    pub fn node_definition(&self, name: &str) -> Option<&SerializableNode> {
+        self.node_definitions.iter().find(|node| node.name == name)
+    }
+
+    /// Parse a graph definition from JSON
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Parse a graph definition from YAML
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        Ok(serde_yaml::from_str(yaml)?)
+    }
+
+    /// Write the graph definition as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Write the graph definition as YAML
+    pub fn to_yaml(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
     /// Create a DOT graph representation for visualization
+    ///
+    /// Conditional edges are dashed and labelled with their [`label`](SerializableEdge::label).
     pub fn to_dot(&self) -> String {
         let mut dot = String::from("digraph G {\n");
 
+        // Draw the entry and exit points apart from the nodes
+        for (node, shape, label) in [(START, "circle", "start"), (END, "doublecircle", "end")] {
+            if self.references(node) {
+                dot.push_str(&format!(
+                    "    \"{}\" [shape={}, label=\"{}\"];\n",
+                    node, shape, label
+                ));
+            }
+        }
+
         // Add nodes
         for node in &self.nodes {
-            dot.push_str(&format!("    \"{}\";\n", node));
+            dot.push_str(&format!("    \"{}\";\n", escape_dot(node)));
         }
 
         // Add edges
         for edge in &self.edges {
-            if edge.has_condition {
-                // If the edge has a condition, add a label
-                let label = edge.condition_description.as_deref().unwrap_or("condition");
-                dot.push_str(&format!(
+            let from = escape_dot(&edge.from);
+            let to = escape_dot(&edge.to);
+            let label = edge.label().map(escape_dot);
+            match (edge.has_condition, label) {
+                (true, label) => dot.push_str(&format!(
+                    "    \"{}\" -> \"{}\" [label=\"{}\", style=dashed];\n",
+                    from,
+                    to,
+                    label.as_deref().unwrap_or("condition")
+                )),
+                (false, Some(label)) => dot.push_str(&format!(
                     "    \"{}\" -> \"{}\" [label=\"{}\"];\n",
-                    edge.from, edge.to, label
-                ));
-            } else {
-                dot.push_str(&format!("    \"{}\" -> \"{}\";\n", edge.from, edge.to));
+                    from, to, label
+                )),
+                (false, None) => dot.push_str(&format!("    \"{}\" -> \"{}\";\n", from, to)),
             }
         }
 
         dot.push_str("}\n");
         dot
     }
+
+    /// Create a Mermaid flowchart representation for design docs
+    ///
+    /// Nodes get generated IDs so that any node name can be shown. Conditional
+    /// edges are dotted and labelled with their [`label`](SerializableEdge::label).
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::from("flowchart TD\n");
+        let mut ids: HashMap<&str, String> = HashMap::new();
+
+        for (node, label) in [(START, "start"), (END, "end")] {
+            if self.references(node) {
+                ids.insert(node, node.to_string());
+                // Unquoted, the label `end` would close the flowchart
+                mermaid.push_str(&format!("    {}([\"{}\"])\n", node, label));
+            }
+        }
+
+        // Declare the listed nodes first, then any node only named by an edge
+        let mut next_id = 0;
+        let names = self.nodes.iter().map(String::as_str).chain(
+            self.edges
+                .iter()
+                .flat_map(|e| [e.from.as_str(), e.to.as_str()]),
+        );
+        for name in names {
+            if !ids.contains_key(name) {
+                let id = format!("n{}", next_id);
+                next_id += 1;
+                mermaid.push_str(&format!("    {}[\"{}\"]\n", id, escape_mermaid(name)));
+                ids.insert(name, id);
+            }
+        }
+
+        for edge in &self.edges {
+            let from = &ids[edge.from.as_str()];
+            let to = &ids[edge.to.as_str()];
+            let arrow = if edge.has_condition { "-.->" } else { "-->" };
+            match edge.label() {
+                Some(label) => mermaid.push_str(&format!(
+                    "    {} {}|\"{}\"| {}\n",
+                    from,
+                    arrow,
+                    escape_mermaid(label),
+                    to
+                )),
+                None => mermaid.push_str(&format!("    {} {} {}\n", from, arrow, to)),
+            }
+        }
+
+        mermaid
+    }
+
+    /// Check whether any edge starts or ends at a node
+    fn references(&self, node: &str) -> bool {
+        self.edges
+            .iter()
+            .any(|edge| edge.from == node || edge.to == node)
+    }
+}
+
+/// Escape a string for use inside a quoted DOT identifier or label
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Escape a string for use inside a quoted Mermaid label
+fn escape_mermaid(text: &str) -> String {
+    text.replace('"', "#quot;")
 }
 
 /// Generate a DOT graph representation from a SerializableGraph
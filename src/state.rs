@@ -1,11 +1,16 @@
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fmt::Debug;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Debug};
 use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::Arc;
 
 use crate::error::Error;
 use crate::Result;
 
+pub use glint_derive::StateValue;
+
 /// Trait for types that can be used as state in a graph.
 ///
 /// This trait is automatically implemented for common types like:
@@ -82,12 +87,376 @@ impl<T: StateValue> State<T> {
     }
 }
 
+impl<T: PartialState> State<T> {
+    /// Fold a partial update into the data, combining every field it sets with
+    /// the field's reducer
+    pub fn with_update(mut self, update: T::Update) -> Self {
+        self.data.apply_update(update);
+        self
+    }
+}
+
+/// Trait for states that nodes can update field by field.
+///
+/// Derived with `#[derive(StateValue)]` for structs with named fields, together
+/// with a partial-update type named after the struct with an `Update` suffix.
+/// Fields declare how updates are combined with `#[reducer(append)]`,
+/// `#[reducer(add)]` or `#[reducer(custom = "path::to::fn")]`, and are replaced
+/// otherwise.
+///
+/// # Examples
+///
+/// ```
+/// use glint::state::{PartialState, State, StateValue};
+/// use serde::{Deserialize, Serialize};
+///
+/// fn longest(current: String, update: String) -> String {
+///     if update.len() > current.len() { update } else { current }
+/// }
+///
+/// #[derive(Debug, Clone, Default, Serialize, Deserialize, StateValue)]
+/// pub struct ResearchState {
+///     pub question: String,
+///     #[reducer(append)]
+///     pub documents: Vec<String>,
+///     #[reducer(add)]
+///     pub searches: i64,
+///     #[reducer(custom = "longest")]
+///     pub answer: String,
+/// }
+///
+/// let state = State::new(ResearchState::default()).with_update(
+///     ResearchStateUpdate::default()
+///         .with_documents(vec!["doc".to_string()])
+///         .with_searches(1),
+/// );
+/// assert_eq!(state.data.documents, vec!["doc"]);
+/// assert_eq!(state.data.searches, 1);
+///
+/// // Parallel branches that return whole states are merged with the `append`
+/// // and `add` reducers
+/// let reducer = ResearchState::channel_reducer();
+/// ```
+pub trait PartialState: StateValue {
+    /// Partial update holding new values for some of the fields
+    type Update: Clone + Debug + Default + Send + Sync + 'static;
+
+    /// Fold an update into the state, combining every field it sets with the
+    /// field's reducer
+    fn apply_update(&mut self, update: Self::Update);
+
+    /// Fold the update of one of several parallel branches into their merged
+    /// state
+    ///
+    /// Works like [`apply_update`](PartialState::apply_update), but fails with
+    /// [`Error::StateConflict`] when the update sets a field without a reducer
+    /// that is already in `written`, the fields earlier branches set. Fields
+    /// without a reducer that the update sets are added to `written`.
+    fn merge_update(
+        &mut self,
+        update: Self::Update,
+        written: &mut HashSet<&'static str>,
+    ) -> Result<()>;
+
+    /// Create a [`ChannelReducer`] that merges parallel branches returning whole
+    /// states with the fields' `append` and `add` reducers
+    ///
+    /// Fields are named as in Rust, so fields renamed for serialization keep no
+    /// reducer. A custom reducer combines a current value with an update, which
+    /// cannot be recovered from whole branch states, so fields with one are merged
+    /// like fields without a reducer. Branches run by update nodes are merged with
+    /// [`merge_update`](PartialState::merge_update) instead.
+    fn channel_reducer() -> ChannelReducer<Self>;
+}
+
 /// Trait for operations that update state
 pub trait StateUpdate<S: StateValue>: Send + Sync {
     /// Apply the update to the state
     fn apply(&self, state: State<S>) -> Result<State<S>>;
 }
 
+/// Trait for combining the states produced by parallel branches into one state.
+///
+/// `base` is the state every branch started from and `branches` holds the branch
+/// results in scheduling order, so implementations can tell which branch wrote what.
+pub trait StateReducer<S: StateValue>: Send + Sync {
+    /// Merge the branch states into a single state
+    fn reduce(&self, base: &State<S>, branches: Vec<State<S>>) -> Result<State<S>>;
+}
+
+impl<S, F> StateReducer<S> for F
+where
+    S: StateValue,
+    F: Fn(&State<S>, Vec<State<S>>) -> Result<State<S>> + Send + Sync,
+{
+    fn reduce(&self, base: &State<S>, branches: Vec<State<S>>) -> Result<State<S>> {
+        self(base, branches)
+    }
+}
+
+/// Reducer that keeps the state of the last branch (the default)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LastValueReducer;
+
+impl<S: StateValue> StateReducer<S> for LastValueReducer {
+    fn reduce(&self, _base: &State<S>, branches: Vec<State<S>>) -> Result<State<S>> {
+        branches
+            .into_iter()
+            .last()
+            .ok_or_else(|| Error::State("No states to merge".to_string()))
+    }
+}
+
+/// Type alias for custom field reducer functions.
+///
+/// Receives the merged value so far, the value every branch started from and the
+/// value written by a branch. A branch's write includes the original value, so
+/// reducers that accumulate must fold in what the branch changed relative to the
+/// original, or the original is counted once per branch.
+pub type FieldReducerFn = Arc<dyn Fn(&Value, &Value, &Value) -> Result<Value> + Send + Sync>;
+
+/// How writes from several branches to the same field are combined
+#[derive(Clone)]
+pub enum Reducer {
+    /// Keep the value written by the last branch
+    LastValue,
+    /// Concatenate the items each branch appended to an array
+    Append,
+    /// Sum the amounts each branch added to a number
+    Add,
+    /// Union of array items or object keys
+    Union,
+    /// Combine values with a custom function
+    Custom(FieldReducerFn),
+}
+
+impl Debug for Reducer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reducer::LastValue => write!(f, "LastValue"),
+            Reducer::Append => write!(f, "Append"),
+            Reducer::Add => write!(f, "Add"),
+            Reducer::Union => write!(f, "Union"),
+            Reducer::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+impl Reducer {
+    /// Create a custom reducer from a function of the merged, original and
+    /// written values
+    pub fn custom(
+        f: impl Fn(&Value, &Value, &Value) -> Result<Value> + Send + Sync + 'static,
+    ) -> Self {
+        Reducer::Custom(Arc::new(f))
+    }
+
+    /// Fold a branch write into the merged value
+    fn apply(
+        &self,
+        field: &str,
+        merged: &Value,
+        original: &Value,
+        written: &Value,
+    ) -> Result<Value> {
+        match self {
+            Reducer::LastValue => Ok(written.clone()),
+            Reducer::Append => {
+                let (Value::Array(merged), Value::Array(written)) = (merged, written) else {
+                    return Err(Error::State(format!(
+                        "Append reducer requires an array field: {}",
+                        field
+                    )));
+                };
+                // Only the items the branch added on top of the original are appended
+                let appended = match original {
+                    Value::Array(original) if written.starts_with(original) => {
+                        &written[original.len()..]
+                    }
+                    _ => &written[..],
+                };
+                let mut result = merged.clone();
+                result.extend(appended.iter().cloned());
+                Ok(Value::Array(result))
+            }
+            Reducer::Add => {
+                let not_numeric =
+                    || Error::State(format!("Add reducer requires a numeric field: {}", field));
+                if [merged, original, written]
+                    .iter()
+                    .any(|value| value.is_f64())
+                {
+                    let number = |value: &Value| value.as_f64().ok_or_else(not_numeric);
+                    let result = number(merged)? + (number(written)? - number(original)?);
+                    return Ok(Value::from(result));
+                }
+
+                // Integers are added exactly, covering both i64 and u64 fields
+                let integer = |value: &Value| {
+                    value
+                        .as_i64()
+                        .map(i128::from)
+                        .or_else(|| value.as_u64().map(i128::from))
+                        .ok_or_else(not_numeric)
+                };
+                let (m, o, w) = (integer(merged)?, integer(original)?, integer(written)?);
+                let overflow = || Error::State(format!("Add reducer overflowed field: {}", field));
+                let result = w
+                    .checked_sub(o)
+                    .and_then(|delta| m.checked_add(delta))
+                    .ok_or_else(overflow)?;
+                match i64::try_from(result) {
+                    Ok(result) => Ok(Value::from(result)),
+                    Err(_) => u64::try_from(result)
+                        .map(Value::from)
+                        .map_err(|_| overflow()),
+                }
+            }
+            Reducer::Union => match (merged, written) {
+                (Value::Array(merged), Value::Array(written)) => {
+                    let mut result = merged.clone();
+                    for item in written {
+                        if !result.contains(item) {
+                            result.push(item.clone());
+                        }
+                    }
+                    Ok(Value::Array(result))
+                }
+                (Value::Object(merged), Value::Object(written)) => {
+                    let mut result = merged.clone();
+                    for (key, value) in written {
+                        result.insert(key.clone(), value.clone());
+                    }
+                    Ok(Value::Object(result))
+                }
+                _ => Err(Error::State(format!(
+                    "Union reducer requires an array or object field: {}",
+                    field
+                ))),
+            },
+            Reducer::Custom(f) => f(merged, original, written),
+        }
+    }
+}
+
+/// Reducer that merges parallel branch results field by field.
+///
+/// The state is serialized to a JSON object and every top-level field is treated as
+/// a channel. A field written by a single branch takes that branch's value; a field
+/// written by several branches is combined with the field's [`Reducer`], and fails
+/// with [`Error::StateConflict`] if no reducer was declared and the writes differ.
+/// Metadata keys are merged with last-write-wins.
+///
+/// # Examples
+///
+/// ```
+/// use glint::state::{ChannelReducer, Reducer};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, Clone, Serialize, Deserialize)]
+/// struct ResearchState {
+///     documents: Vec<String>,
+///     searches: i64,
+/// }
+///
+/// impl glint::state::StateValue for ResearchState {}
+///
+/// let reducer = ChannelReducer::<ResearchState>::new()
+///     .with_field("documents", Reducer::Append)
+///     .with_field("searches", Reducer::Add);
+/// ```
+pub struct ChannelReducer<S: StateValue> {
+    reducers: HashMap<String, Reducer>,
+    _phantom: PhantomData<fn() -> S>,
+}
+
+impl<S: StateValue> Debug for ChannelReducer<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChannelReducer")
+            .field("reducers", &self.reducers)
+            .finish()
+    }
+}
+
+impl<S: StateValue> Default for ChannelReducer<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: StateValue> ChannelReducer<S> {
+    /// Create a reducer with no field reducers declared
+    pub fn new() -> Self {
+        Self {
+            reducers: HashMap::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Declare how writes to a field are combined
+    pub fn with_field(mut self, field: impl Into<String>, reducer: Reducer) -> Self {
+        self.reducers.insert(field.into(), reducer);
+        self
+    }
+}
+
+impl<S> StateReducer<S> for ChannelReducer<S>
+where
+    S: StateValue + Serialize + DeserializeOwned,
+{
+    fn reduce(&self, base: &State<S>, branches: Vec<State<S>>) -> Result<State<S>> {
+        let to_object = |data: &S| match serde_json::to_value(data)? {
+            Value::Object(map) => Ok(map),
+            _ => Err(Error::State(
+                "ChannelReducer requires state that serializes to a JSON object".to_string(),
+            )),
+        };
+
+        let original = to_object(&base.data)?;
+        let mut merged = original.clone();
+        let mut metadata = base.metadata.clone();
+        let mut written_by: HashMap<String, usize> = HashMap::new();
+
+        for (index, branch) in branches.iter().enumerate() {
+            for (field, value) in to_object(&branch.data)? {
+                let original_value = original.get(&field).unwrap_or(&Value::Null);
+                if &value == original_value {
+                    continue;
+                }
+
+                let merged_value = merged.get(&field).unwrap_or(&Value::Null);
+                let new_value = match self.reducers.get(&field) {
+                    Some(reducer) => reducer.apply(&field, merged_value, original_value, &value)?,
+                    None => {
+                        if let Some(previous) = written_by.get(&field) {
+                            if merged_value != &value {
+                                return Err(Error::StateConflict(format!(
+                                    "Branches {} and {} both wrote field '{}' without a reducer",
+                                    previous, index, field
+                                )));
+                            }
+                        }
+                        value
+                    }
+                };
+                merged.insert(field.clone(), new_value);
+                written_by.insert(field, index);
+            }
+
+            for (key, value) in &branch.metadata {
+                if base.metadata.get(key) != Some(value) {
+                    metadata.insert(key.clone(), value.clone());
+                }
+            }
+        }
+
+        Ok(State {
+            data: serde_json::from_value(Value::Object(merged))?,
+            metadata,
+        })
+    }
+}
+
 /// A simple state type that holds a map of string keys to values
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MapState {
@@ -154,3 +523,138 @@ impl MapState {
         self.values.is_empty()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Research {
+        documents: Vec<String>,
+        tags: Vec<String>,
+        searches: i64,
+        score: i64,
+        summary: String,
+    }
+
+    impl StateValue for Research {}
+
+    fn base() -> State<Research> {
+        State::new(Research {
+            documents: vec!["intro".to_string()],
+            tags: vec!["rust".to_string()],
+            searches: 10,
+            score: 100,
+            summary: String::new(),
+        })
+    }
+
+    fn branch(update: impl FnOnce(&mut Research)) -> State<Research> {
+        let mut state = base();
+        update(&mut state.data);
+        state
+    }
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn test_channel_reducer_folds_branch_writes() {
+        let reducer = ChannelReducer::new()
+            .with_field("documents", Reducer::Append)
+            .with_field("tags", Reducer::Union)
+            .with_field("searches", Reducer::Add)
+            .with_field("summary", Reducer::LastValue);
+
+        let branches = vec![
+            branch(|data| {
+                data.documents.push("web".to_string());
+                data.tags.push("graphs".to_string());
+                data.searches += 1;
+                data.summary = "first".to_string();
+            }),
+            branch(|data| {
+                data.documents.push("papers".to_string());
+                data.tags = strings(&["graphs", "rust", "llm"]);
+                data.searches += 2;
+                data.summary = "second".to_string();
+            }),
+        ];
+        let merged = reducer.reduce(&base(), branches).unwrap().data;
+
+        assert_eq!(merged.documents, strings(&["intro", "web", "papers"]));
+        assert_eq!(merged.tags, strings(&["rust", "graphs", "llm"]));
+        assert_eq!(merged.searches, 13);
+        assert_eq!(merged.summary, "second");
+        assert_eq!(merged.score, 100);
+    }
+
+    #[test]
+    fn test_custom_reducer_receives_original() {
+        // Sums what every branch changed, counting the original score once
+        let reducer = ChannelReducer::new().with_field(
+            "score",
+            Reducer::custom(|merged, original, written| {
+                let value = |v: &Value| v.as_i64().unwrap_or_default();
+                Ok(Value::from(
+                    value(merged) + value(written) - value(original),
+                ))
+            }),
+        );
+
+        let branches = vec![
+            branch(|data| data.score += 5),
+            branch(|data| data.score += 7),
+        ];
+        let merged = reducer.reduce(&base(), branches).unwrap();
+        assert_eq!(merged.data.score, 112);
+    }
+
+    #[test]
+    fn test_channel_reducer_errors() {
+        let branches = || {
+            vec![
+                branch(|data| data.summary = "first".to_string()),
+                branch(|data| data.summary = "second".to_string()),
+            ]
+        };
+        let error = ChannelReducer::new()
+            .reduce(&base(), branches())
+            .unwrap_err();
+        assert!(matches!(error, Error::StateConflict(_)));
+
+        let error = ChannelReducer::new()
+            .with_field("summary", Reducer::Append)
+            .reduce(&base(), branches())
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "State error: Append reducer requires an array field: summary"
+        );
+    }
+
+    #[test]
+    fn test_add_reducer_keeps_integers_exact() {
+        let add = |merged: Value, original: Value, written: Value| {
+            Reducer::Add.apply("count", &merged, &original, &written)
+        };
+
+        assert_eq!(add(json!(3), json!(1), json!(-4)).unwrap(), json!(-2));
+        assert_eq!(
+            add(json!(u64::MAX - 2), json!(0), json!(2)).unwrap(),
+            json!(u64::MAX)
+        );
+        assert!(add(json!(7), json!(0), json!(2)).unwrap().is_i64());
+        assert_eq!(add(json!(1.5), json!(1), json!(2)).unwrap(), json!(2.5));
+
+        let error = add(json!(u64::MAX), json!(0), json!(1)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "State error: Add reducer overflowed field: count"
+        );
+        let error = add(json!(i64::MIN), json!(0), json!(-1)).unwrap_err();
+        assert!(matches!(error, Error::State(_)));
+    }
+}
@@ -0,0 +1,393 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::callbacks::CallbackHandler;
+use crate::error::Error;
+use crate::graph::{Command, Graph, NodeContext, NodeProcessor, RunConfig, END, PATH_SEPARATOR};
+use crate::state::{State, StateValue};
+use crate::Result;
+
+/// Environment variable that makes [`TestRun::assert_snapshot`] rewrite snapshots
+pub const UPDATE_SNAPSHOTS_ENV: &str = "GLINT_UPDATE_SNAPSHOTS";
+
+/// Runs a graph in tests, with nodes stubbed out by name and the executed path
+/// recorded
+///
+/// Works with both execution strategies. Within a parallel superstep, nodes are
+/// recorded in order of their names, so paths do not depend on which node
+/// happened to start first.
+///
+/// # Examples
+///
+/// ```
+/// use glint::graph::{Graph, GraphBuilder, END};
+/// use glint::testing::GraphTester;
+/// # use glint::graph::NodeProcessor;
+/// # use glint::state::State;
+/// # struct CallModel;
+/// # #[async_trait::async_trait]
+/// # impl NodeProcessor<i32> for CallModel {
+/// #     async fn process(&self, _state: State<i32>) -> glint::Result<State<i32>> {
+/// #         Err(glint::Error::LLM("no network in tests".to_string()))
+/// #     }
+/// # }
+/// # fn agent_graph() -> glint::Result<Graph<i32>> {
+/// #     Ok(GraphBuilder::new()
+/// #         .with_node("agent", CallModel)?
+/// #         .with_start_edge("agent")?
+/// #         .with_end_edge("agent")?
+/// #         .build())
+/// # }
+///
+/// # async fn test() -> glint::Result<()> {
+/// let tester = GraphTester::new(agent_graph()?)
+///     .stub("agent", |mut state: State<i32>| {
+///         state.data += 1;
+///         Ok(state)
+///     })?;
+///
+/// let run = tester.run(State::new(0)).await;
+/// run.assert_path(&["agent", END]).assert_not_visited("escalate");
+/// assert_eq!(run.state().data, 1);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct GraphTester<S: StateValue> {
+    graph: Graph<S>,
+}
+
+impl<S: StateValue> GraphTester<S> {
+    /// Create a tester for a graph
+    pub fn new(graph: Graph<S>) -> Self {
+        Self { graph }
+    }
+
+    /// Replace a node with a function of its input state
+    pub fn stub(
+        self,
+        node: impl Into<String>,
+        stub: impl Fn(State<S>) -> Result<State<S>> + Send + Sync + 'static,
+    ) -> Result<Self> {
+        self.stub_command(node, move |state| Ok(Command::new(stub(state)?)))
+    }
+
+    /// Replace a node with a function returning a [`Command`], e.g. to stub a
+    /// command node's jumps
+    pub fn stub_command(
+        mut self,
+        node: impl Into<String>,
+        stub: impl Fn(State<S>) -> Result<Command<S>> + Send + Sync + 'static,
+    ) -> Result<Self> {
+        self.graph.replace_node(node, Stub(stub))?;
+        Ok(self)
+    }
+
+    /// Replace a node with another processor
+    pub fn stub_node(
+        mut self,
+        node: impl Into<String>,
+        processor: impl NodeProcessor<S> + 'static,
+    ) -> Result<Self> {
+        self.graph.replace_node(node, processor)?;
+        Ok(self)
+    }
+
+    /// Get the graph with its stubs
+    pub fn graph(&self) -> &Graph<S> {
+        &self.graph
+    }
+
+    /// Run the graph and record the path it took
+    pub async fn run(&self, initial_state: State<S>) -> TestRun<S> {
+        self.run_with_config(initial_state, &RunConfig::new()).await
+    }
+
+    /// Run the graph with a run configuration and record the path it took
+    pub async fn run_with_config(
+        &self,
+        initial_state: State<S>,
+        config: &RunConfig<S>,
+    ) -> TestRun<S> {
+        let recorder = Arc::new(PathRecorder::default());
+        let config = config.clone().with_callback(recorder.clone());
+        let result = self.graph.execute_with_config(initial_state, &config).await;
+
+        let mut path = recorder.path();
+        if result.is_ok() {
+            path.push(END.to_string());
+        }
+        TestRun { result, path }
+    }
+}
+
+/// A node processor backed by a function
+struct Stub<F>(F);
+
+#[async_trait]
+impl<S, F> NodeProcessor<S> for Stub<F>
+where
+    S: StateValue,
+    F: Fn(State<S>) -> Result<Command<S>> + Send + Sync,
+{
+    async fn process(&self, state: State<S>) -> Result<State<S>> {
+        Ok((self.0)(state)?.state)
+    }
+
+    async fn process_command(&self, state: State<S>, _ctx: &NodeContext<S>) -> Result<Command<S>> {
+        (self.0)(state)
+    }
+}
+
+/// Records the top-level nodes a run starts, with their steps
+#[derive(Debug, Default)]
+struct PathRecorder {
+    visits: Mutex<Vec<(usize, String)>>,
+}
+
+impl PathRecorder {
+    /// Get the visited nodes ordered by step, then by name
+    fn path(&self) -> Vec<String> {
+        let mut visits = self.visits.lock().unwrap().clone();
+        visits.sort();
+        visits.into_iter().map(|(_, node)| node).collect()
+    }
+}
+
+impl CallbackHandler for PathRecorder {
+    fn on_node_start(&self, node: &str, step: usize, _input: &dyn std::fmt::Debug) {
+        // Nodes of nested graphs count their steps separately
+        if !node.contains(PATH_SEPARATOR) {
+            self.visits.lock().unwrap().push((step, node.to_string()));
+        }
+    }
+}
+
+/// The outcome of a run made by a [`GraphTester`]
+#[derive(Debug)]
+pub struct TestRun<S: StateValue> {
+    /// The result of the run
+    pub result: Result<State<S>>,
+    /// The top-level nodes the run executed, followed by END if it finished
+    pub path: Vec<String>,
+}
+
+impl<S: StateValue> TestRun<S> {
+    /// Get the final state, panicking if the run failed
+    #[track_caller]
+    pub fn state(&self) -> &State<S> {
+        match &self.result {
+            Ok(state) => state,
+            Err(e) => panic!("graph run failed: {}", e),
+        }
+    }
+
+    /// Get the error the run failed with, panicking if it succeeded
+    #[track_caller]
+    pub fn error(&self) -> &Error {
+        match &self.result {
+            Ok(state) => panic!("graph run succeeded with state {:?}", state.data),
+            Err(e) => e,
+        }
+    }
+
+    /// Count how often a node was executed
+    pub fn visits(&self, node: &str) -> usize {
+        self.path.iter().filter(|visited| *visited == node).count()
+    }
+
+    /// Assert that the run took exactly the given path
+    #[track_caller]
+    pub fn assert_path(&self, expected: &[&str]) -> &Self {
+        assert_eq!(self.path, expected, "unexpected graph path");
+        self
+    }
+
+    /// Assert that the path ends with the given nodes, e.g. `["tools", END]`
+    #[track_caller]
+    pub fn assert_path_ends_with(&self, expected: &[&str]) -> &Self {
+        assert!(
+            self.path.ends_with(
+                &expected
+                    .iter()
+                    .map(|node| node.to_string())
+                    .collect::<Vec<_>>()
+            ),
+            "graph path {:?} does not end with {:?}",
+            self.path,
+            expected
+        );
+        self
+    }
+
+    /// Assert that a node was executed exactly `times` times
+    #[track_caller]
+    pub fn assert_visited(&self, node: &str, times: usize) -> &Self {
+        assert_eq!(
+            self.visits(node),
+            times,
+            "node {} visited an unexpected number of times in graph path {:?}",
+            node,
+            self.path
+        );
+        self
+    }
+
+    /// Assert that a node was never executed
+    #[track_caller]
+    pub fn assert_not_visited(&self, node: &str) -> &Self {
+        self.assert_visited(node, 0)
+    }
+}
+
+impl<S: StateValue + Serialize> TestRun<S> {
+    /// Assert that the final state matches the JSON snapshot stored at `path`
+    ///
+    /// A missing snapshot is written instead. Set the environment variable
+    /// [`UPDATE_SNAPSHOTS_ENV`] to rewrite snapshots that no longer match.
+    #[track_caller]
+    pub fn assert_snapshot(&self, path: impl AsRef<Path>) -> &Self {
+        let path = path.as_ref();
+        let actual = serde_json::to_string_pretty(self.state())
+            .unwrap_or_else(|e| panic!("cannot serialize final state: {}", e));
+
+        let update = std::env::var_os(UPDATE_SNAPSHOTS_ENV).is_some();
+        match fs::read_to_string(path) {
+            Ok(expected) if !update => {
+                assert_eq!(
+                    expected.trim_end(),
+                    actual,
+                    "final state differs from snapshot {}",
+                    path.display()
+                );
+            }
+            _ => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)
+                        .unwrap_or_else(|e| panic!("cannot create snapshot directory: {}", e));
+                }
+                fs::write(path, actual + "\n")
+                    .unwrap_or_else(|e| panic!("cannot write snapshot: {}", e));
+            }
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{ExecutionStrategy, GraphBuilder};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    /// Stands in for a node that calls an external service
+    struct Remote;
+
+    #[async_trait]
+    impl NodeProcessor<i32> for Remote {
+        async fn process(&self, _state: State<i32>) -> Result<State<i32>> {
+            Err(Error::LLM("no network in tests".to_string()))
+        }
+    }
+
+    /// An agent that calls tools until it has two results, escalating large states
+    fn agent_graph(strategy: ExecutionStrategy) -> Graph<i32> {
+        GraphBuilder::new()
+            .with_execution_strategy(strategy)
+            .with_node("agent", Remote)
+            .unwrap()
+            .with_node("tools", Remote)
+            .unwrap()
+            .with_node("escalate", Remote)
+            .unwrap()
+            .with_start_edge("agent")
+            .unwrap()
+            .with_edge(
+                "agent",
+                "escalate",
+                Some(Arc::new(|state: &State<i32>| Ok(state.data > 100))),
+            )
+            .unwrap()
+            .with_edge(
+                "agent",
+                END,
+                Some(Arc::new(|state: &State<i32>| {
+                    Ok((2..=100).contains(&state.data))
+                })),
+            )
+            .unwrap()
+            .with_edge(
+                "agent",
+                "tools",
+                Some(Arc::new(|state: &State<i32>| Ok(state.data < 2))),
+            )
+            .unwrap()
+            .with_edge("tools", "agent", None)
+            .unwrap()
+            .with_end_edge("escalate")
+            .unwrap()
+            .build()
+    }
+
+    fn tester(strategy: ExecutionStrategy) -> GraphTester<i32> {
+        GraphTester::new(agent_graph(strategy))
+            .stub("agent", Ok)
+            .unwrap()
+            .stub("tools", |mut state: State<i32>| {
+                state.data += 1;
+                Ok(state)
+            })
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_stubbed_path_with_both_strategies() {
+        for strategy in [ExecutionStrategy::Sequential, ExecutionStrategy::Parallel] {
+            let run = tester(strategy).run(State::new(0)).await;
+            run.assert_path(&["agent", "tools", "agent", "tools", "agent", END])
+                .assert_visited("tools", 2)
+                .assert_path_ends_with(&["agent", END])
+                .assert_not_visited("escalate");
+            assert_eq!(run.state().data, 2);
+        }
+
+        // Unstubbed nodes run for real
+        let run = GraphTester::new(agent_graph(ExecutionStrategy::Sequential))
+            .stub("agent", Ok)
+            .unwrap()
+            .run(State::new(500))
+            .await;
+        run.assert_path(&["agent", "escalate"]);
+        assert!(run.error().to_string().contains("no network in tests"));
+
+        let missing = tester(ExecutionStrategy::Sequential).stub("search", Ok);
+        assert!(matches!(missing, Err(Error::InvalidNode(_))));
+    }
+
+    #[tokio::test]
+    async fn test_assert_snapshot() {
+        let directory =
+            std::env::temp_dir().join(format!("glint-snapshots-{}", uuid::Uuid::new_v4()));
+        let snapshot = directory.join("agent.json");
+
+        let run = tester(ExecutionStrategy::Sequential)
+            .run(State::new(0))
+            .await;
+        run.assert_snapshot(&snapshot);
+        assert!(snapshot.exists());
+        run.assert_snapshot(&snapshot);
+
+        let other = tester(ExecutionStrategy::Sequential)
+            .run(State::new(50))
+            .await;
+        let mismatch = catch_unwind(AssertUnwindSafe(|| {
+            other.assert_snapshot(&snapshot);
+        }));
+        assert!(mismatch.is_err());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
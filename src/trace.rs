@@ -0,0 +1,644 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::callbacks::{CallbackHandler, TokenUsage};
+use crate::error::Error;
+use crate::graph::PATH_SEPARATOR;
+use crate::schema::{Document, Message};
+use crate::Result;
+
+/// The graph run, and the node within it, that the current task is executing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceScope {
+    /// ID of the top-level run
+    pub run_id: String,
+    /// Path of the node being executed, if the run is inside a node
+    pub node: Option<String>,
+}
+
+tokio::task_local! {
+    static SCOPE: TraceScope;
+}
+
+/// Get the graph run and node the current task is executing, if any
+///
+/// Lets callback handlers attribute model, retriever and tool calls to the node
+/// that made them, even when nodes run concurrently.
+pub fn current_scope() -> Option<TraceScope> {
+    SCOPE.try_with(|scope| scope.clone()).ok()
+}
+
+/// Run a future within the given scope
+pub(crate) async fn in_scope<F: Future>(scope: TraceScope, future: F) -> F::Output {
+    SCOPE.scope(scope, future).await
+}
+
+/// What a span of a run tree records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpanKind {
+    /// Execution of a graph node
+    Node,
+    /// Call of a language or chat model
+    Llm,
+    /// Call of an embedding model
+    Embedding,
+    /// Query of a retriever
+    Retriever,
+    /// Call of a tool
+    Tool,
+}
+
+/// A timed operation within a run, with the operations it made
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceSpan {
+    /// What the span records
+    pub kind: SpanKind,
+    /// Path of the node, or name of the model or tool
+    pub name: String,
+    /// Step at which a node ran
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<usize>,
+    /// Input of the operation
+    pub input: Value,
+    /// Output of the operation, if it finished
+    pub output: Value,
+    /// The error the operation failed with, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Tokens used by a model call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    /// Milliseconds from the start of the run to the start of the operation
+    pub start_ms: f64,
+    /// How long the operation took in milliseconds; missing if it never finished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<f64>,
+    /// Operations made during this one, in the order they started
+    #[serde(default)]
+    pub children: Vec<TraceSpan>,
+}
+
+/// The nested trace of a single graph run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunTree {
+    /// ID of the run
+    pub run_id: String,
+    /// Thread the run belongs to, if it was checkpointed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<String>,
+    /// When the run started, in milliseconds since the Unix epoch
+    pub started_at_ms: u64,
+    /// How long the run took in milliseconds; missing if it is still running
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<f64>,
+    /// The error the run failed with, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The top-level operations of the run
+    pub children: Vec<TraceSpan>,
+}
+
+impl RunTree {
+    /// Write the tree to a file as pretty-printed JSON
+    pub fn write_to(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// A span recorded while its run is in progress
+struct SpanRecord {
+    /// Index of the enclosing span in the run's spans
+    parent: Option<usize>,
+    span: TraceSpan,
+    started: Instant,
+    open: bool,
+}
+
+/// A run recorded while it is in progress
+struct RunRecord {
+    tree: RunTree,
+    started: Instant,
+    spans: Vec<SpanRecord>,
+}
+
+impl RunRecord {
+    /// Find the latest unfinished span of the given kind and name, below `parent`
+    /// if given
+    fn open_span(
+        &self,
+        kind: SpanKind,
+        name: &str,
+        parent: Option<Option<usize>>,
+    ) -> Option<usize> {
+        self.spans.iter().rposition(|record| {
+            record.open
+                && record.span.kind == kind
+                && record.span.name == name
+                && parent.is_none_or(|parent| record.parent == parent)
+        })
+    }
+
+    /// Find the span of the node a task is executing
+    fn node_span(&self, node: Option<&str>) -> Option<usize> {
+        node.and_then(|node| self.open_span(SpanKind::Node, node, None))
+    }
+
+    /// Start a span, returning it so more details can be filled in
+    fn start(
+        &mut self,
+        parent: Option<usize>,
+        kind: SpanKind,
+        name: &str,
+        input: Value,
+    ) -> &mut TraceSpan {
+        let now = Instant::now();
+        self.spans.push(SpanRecord {
+            parent,
+            span: TraceSpan {
+                kind,
+                name: name.to_string(),
+                step: None,
+                input,
+                output: Value::Null,
+                error: None,
+                usage: None,
+                start_ms: millis(now - self.started),
+                duration_ms: None,
+                children: Vec::new(),
+            },
+            started: now,
+            open: true,
+        });
+        &mut self.spans.last_mut().unwrap().span
+    }
+
+    /// Finish a span, recording its output or error
+    fn finish(&mut self, index: usize, output: Value, error: Option<&Error>) -> &mut TraceSpan {
+        let record = &mut self.spans[index];
+        record.open = false;
+        record.span.output = output;
+        record.span.error = error.map(|e| e.to_string());
+        record.span.duration_ms = Some(millis(record.started.elapsed()));
+        &mut record.span
+    }
+
+    /// Assemble the recorded spans into a tree
+    fn tree(&self) -> RunTree {
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); self.spans.len()];
+        let mut roots = Vec::new();
+        for (index, record) in self.spans.iter().enumerate() {
+            match record.parent {
+                Some(parent) => children[parent].push(index),
+                None => roots.push(index),
+            }
+        }
+
+        fn build(spans: &[SpanRecord], children: &[Vec<usize>], index: usize) -> TraceSpan {
+            let mut span = spans[index].span.clone();
+            span.children = children[index]
+                .iter()
+                .map(|&child| build(spans, children, child))
+                .collect();
+            span
+        }
+
+        let mut tree = self.tree.clone();
+        tree.children = roots
+            .into_iter()
+            .map(|index| build(&self.spans, &children, index))
+            .collect();
+        tree
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Turn a run ID into a file name without path separators
+fn file_stem(run_id: &str) -> String {
+    run_id
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// A callback handler that records graph runs as nested traces
+///
+/// Nodes are nested under the subgraph nodes that ran them, and model, embedding,
+/// retriever and tool calls under the node that made them. States are recorded
+/// with their `Debug` representation. Calls made outside of a graph run are not
+/// recorded.
+///
+/// Register the recorder on the graph and on the models, stores and runs whose
+/// calls it should see.
+///
+/// # Examples
+///
+/// ```no_run
+/// use glint::graph::{Graph, RunConfig};
+/// use glint::llms::ChatOpenAI;
+/// use glint::trace::RunTreeRecorder;
+/// use std::sync::Arc;
+///
+/// let recorder = Arc::new(RunTreeRecorder::new().with_directory("traces"));
+/// let model = ChatOpenAI::new("api-key", "gpt-4o").with_callback(recorder.clone());
+/// let config = RunConfig::<String>::new()
+///     .with_run_id("run-1")
+///     .with_callback(recorder.clone());
+///
+/// // After the run, the trace is written to traces/run-1.json
+/// ```
+#[derive(Default)]
+pub struct RunTreeRecorder {
+    /// Runs in progress, and finished runs unless they are written to a directory
+    runs: Mutex<HashMap<String, RunRecord>>,
+    /// Directory finished runs are written to
+    directory: Option<PathBuf>,
+}
+
+impl fmt::Debug for RunTreeRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RunTreeRecorder")
+            .field("directory", &self.directory)
+            .finish()
+    }
+}
+
+impl RunTreeRecorder {
+    /// Create a recorder that keeps the traces of finished runs in memory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write the trace of every finished run to `<directory>/<run_id>.json`
+    ///
+    /// Characters of the run ID other than letters, digits, `-`, `_` and `.` are
+    /// written as `_`, so every trace lands directly in the directory. Written
+    /// traces are no longer kept in memory. Failures to write are logged.
+    pub fn with_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    /// Get the trace of a run, finished or in progress
+    pub fn run_tree(&self, run_id: &str) -> Option<RunTree> {
+        self.runs.lock().unwrap().get(run_id).map(RunRecord::tree)
+    }
+
+    /// Remove the trace of a run from memory and return it
+    pub fn take_run_tree(&self, run_id: &str) -> Option<RunTree> {
+        self.runs
+            .lock()
+            .unwrap()
+            .remove(run_id)
+            .map(|record| record.tree())
+    }
+
+    /// Update the recorded run of the current task, passing the path of the node
+    /// the task is executing
+    fn update(&self, f: impl FnOnce(&mut RunRecord, Option<&str>)) {
+        if let Some(scope) = current_scope() {
+            if let Some(run) = self.runs.lock().unwrap().get_mut(&scope.run_id) {
+                f(run, scope.node.as_deref());
+            }
+        }
+    }
+
+    /// Record the start of a call made by the node of the current task
+    fn start_call(&self, kind: SpanKind, name: &str, input: Value) {
+        self.update(|run, node| {
+            let parent = run.node_span(node);
+            run.start(parent, kind, name, input);
+        });
+    }
+
+    /// Record the end of a call made by the node of the current task
+    fn finish_call(
+        &self,
+        kind: SpanKind,
+        name: &str,
+        output: Value,
+        error: Option<&Error>,
+        usage: Option<&TokenUsage>,
+    ) {
+        self.update(|run, node| {
+            let parent = run.node_span(node);
+            if let Some(index) = run.open_span(kind, name, Some(parent)) {
+                run.finish(index, output, error).usage = usage.copied();
+            }
+        });
+    }
+
+    /// Record the end of a node
+    fn finish_node(&self, node: &str, output: Value, error: Option<&Error>) {
+        self.update(|run, _| {
+            if let Some(index) = run.open_span(SpanKind::Node, node, None) {
+                run.finish(index, output, error);
+            }
+        });
+    }
+}
+
+impl CallbackHandler for RunTreeRecorder {
+    fn on_run_start(&self, run_id: &str, thread_id: Option<&str>) {
+        let started_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        let record = RunRecord {
+            tree: RunTree {
+                run_id: run_id.to_string(),
+                thread_id: thread_id.map(str::to_string),
+                started_at_ms,
+                duration_ms: None,
+                error: None,
+                children: Vec::new(),
+            },
+            started: Instant::now(),
+            spans: Vec::new(),
+        };
+        self.runs.lock().unwrap().insert(run_id.to_string(), record);
+    }
+
+    fn on_run_end(&self, run_id: &str, elapsed: Duration, error: Option<&Error>) {
+        let mut runs = self.runs.lock().unwrap();
+        if let Some(run) = runs.get_mut(run_id) {
+            run.tree.duration_ms = Some(millis(elapsed));
+            run.tree.error = error.map(|e| e.to_string());
+        }
+
+        let directory = match &self.directory {
+            Some(directory) => directory,
+            None => return,
+        };
+        let tree = runs.remove(run_id).map(|run| run.tree());
+        drop(runs);
+        let path = directory.join(format!("{}.json", file_stem(run_id)));
+        let written = std::fs::create_dir_all(directory)
+            .map_err(Error::from)
+            .and_then(|_| tree.map_or(Ok(()), |tree| tree.write_to(&path)));
+        if let Err(e) = written {
+            tracing::warn!(run_id, error = %e, "Failed to write run tree to {:?}", path);
+        }
+    }
+
+    fn on_node_start(&self, node: &str, step: usize, input: &dyn fmt::Debug) {
+        let input = json!(format!("{:?}", input));
+        self.update(|run, _| {
+            // Nodes of a subgraph are nested under the subgraph's node
+            let parent = node
+                .rsplit_once(PATH_SEPARATOR)
+                .and_then(|(parent, _)| run.node_span(Some(parent)));
+            run.start(parent, SpanKind::Node, node, input).step = Some(step);
+        });
+    }
+
+    fn on_node_end(&self, node: &str, output: &dyn fmt::Debug, _elapsed: Duration) {
+        let output = json!(format!("{:?}", output));
+        self.finish_node(node, output, None);
+    }
+
+    fn on_node_error(&self, node: &str, error: &Error) {
+        self.finish_node(node, Value::Null, Some(error));
+    }
+
+    fn on_llm_start(&self, model: &str, prompts: &[String]) {
+        self.start_call(SpanKind::Llm, model, json!(prompts));
+    }
+
+    fn on_chat_model_start(&self, model: &str, messages: &[Message]) {
+        let input = serde_json::to_value(messages).unwrap_or(Value::Null);
+        self.start_call(SpanKind::Llm, model, input);
+    }
+
+    fn on_llm_end(&self, model: &str, output: &str, usage: Option<&TokenUsage>) {
+        self.finish_call(SpanKind::Llm, model, json!(output), None, usage);
+    }
+
+    fn on_llm_error(&self, model: &str, error: &Error) {
+        self.finish_call(SpanKind::Llm, model, Value::Null, Some(error), None);
+    }
+
+    fn on_embedding_start(&self, model: &str, texts: &[String]) {
+        self.start_call(SpanKind::Embedding, model, json!(texts));
+    }
+
+    fn on_embedding_end(&self, model: &str, usage: Option<&TokenUsage>) {
+        self.finish_call(SpanKind::Embedding, model, Value::Null, None, usage);
+    }
+
+    fn on_embedding_error(&self, model: &str, error: &Error) {
+        self.finish_call(SpanKind::Embedding, model, Value::Null, Some(error), None);
+    }
+
+    fn on_retriever_start(&self, query: &str) {
+        self.start_call(SpanKind::Retriever, "retriever", json!(query));
+    }
+
+    fn on_retriever_end(&self, documents: &[(Document, f32)]) {
+        let output = documents
+            .iter()
+            .map(|(document, score)| json!({ "document": document, "score": score }))
+            .collect();
+        self.finish_call(SpanKind::Retriever, "retriever", output, None, None);
+    }
+
+    fn on_retriever_error(&self, error: &Error) {
+        self.finish_call(
+            SpanKind::Retriever,
+            "retriever",
+            Value::Null,
+            Some(error),
+            None,
+        );
+    }
+
+    fn on_tool_start(&self, tool: &str, input: &str) {
+        self.start_call(SpanKind::Tool, tool, json!(input));
+    }
+
+    fn on_tool_end(&self, tool: &str, output: &str) {
+        self.finish_call(SpanKind::Tool, tool, json!(output), None, None);
+    }
+
+    fn on_tool_error(&self, tool: &str, error: &Error) {
+        self.finish_call(SpanKind::Tool, tool, Value::Null, Some(error), None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::MockEmbeddings;
+    use crate::graph::{Command, Graph, GraphBuilder, NodeContext, NodeProcessor, RunConfig};
+    use crate::state::State;
+    use crate::traits::VectorStore;
+    use crate::vectorstores::MemoryVectorStore;
+    use async_trait::async_trait;
+    use std::sync::Arc;
+
+    /// Searches a store for the state, then looks the best match up with a tool
+    struct Research {
+        store: MemoryVectorStore,
+    }
+
+    #[async_trait]
+    impl NodeProcessor<String> for Research {
+        async fn process(&self, state: State<String>) -> Result<State<String>> {
+            Ok(state)
+        }
+
+        async fn process_command(
+            &self,
+            mut state: State<String>,
+            ctx: &NodeContext<String>,
+        ) -> Result<Command<String>> {
+            let found = self.store.search(&state.data, 1).await?;
+            let best = found[0].0.page_content.clone();
+            ctx.callbacks().on_tool_start("lookup", &best);
+            ctx.callbacks().on_tool_end("lookup", "found");
+            state.data = best;
+            Ok(Command::new(state))
+        }
+    }
+
+    struct Fail;
+
+    #[async_trait]
+    impl NodeProcessor<String> for Fail {
+        async fn process(&self, _state: State<String>) -> Result<State<String>> {
+            Err(Error::Other("no answer".to_string()))
+        }
+    }
+
+    async fn research_graph(recorder: &Arc<RunTreeRecorder>) -> Graph<String> {
+        let embeddings = MockEmbeddings::new(2)
+            .with_embedding("rust", vec![1.0, 0.0])
+            .with_embedding("crab", vec![1.0, 0.0]);
+        let mut store = MemoryVectorStore::new(embeddings).with_callback(recorder.clone());
+        store
+            .add_documents(vec![Document::new("rust")])
+            .await
+            .unwrap();
+
+        let inner = GraphBuilder::new()
+            .with_node("search", Research { store })
+            .unwrap()
+            .with_start_edge("search")
+            .unwrap()
+            .with_end_edge("search")
+            .unwrap()
+            .build();
+
+        GraphBuilder::new()
+            .with_node("research", inner)
+            .unwrap()
+            .with_node("answer", Fail)
+            .unwrap()
+            .with_start_edge("research")
+            .unwrap()
+            .with_edge("research", "answer", None)
+            .unwrap()
+            .with_end_edge("answer")
+            .unwrap()
+            .build()
+    }
+
+    /// Describe a span and its children in a compact form for assertions
+    fn describe(span: &TraceSpan, out: &mut Vec<String>, depth: usize) {
+        out.push(format!(
+            "{}{:?} {} {} -> {}{}",
+            "  ".repeat(depth),
+            span.kind,
+            span.name,
+            span.input,
+            span.output,
+            span.error
+                .as_ref()
+                .map(|e| format!(" ({})", e))
+                .unwrap_or_default()
+        ));
+        for child in &span.children {
+            describe(child, out, depth + 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_tree() {
+        let recorder = Arc::new(RunTreeRecorder::new());
+        let graph = research_graph(&recorder).await;
+        let config = RunConfig::new()
+            .with_run_id("run-1")
+            .with_callback(recorder.clone());
+
+        graph
+            .execute_with_config(State::new("crab".to_string()), &config)
+            .await
+            .unwrap_err();
+
+        let tree = recorder.run_tree("run-1").unwrap();
+        assert!(tree.duration_ms.is_some());
+        assert!(tree.error.unwrap().contains("no answer"));
+
+        let mut spans = Vec::new();
+        for span in &tree.children {
+            describe(span, &mut spans, 0);
+        }
+        assert_eq!(
+            spans,
+            vec![
+                r#"Node research "\"crab\"" -> "\"rust\"""#,
+                r#"  Node research/search "\"crab\"" -> "\"rust\"""#,
+                r#"    Retriever retriever "crab" -> [{"document":{"metadata":{},"page_content":"rust"},"score":1.0}]"#,
+                r#"    Tool lookup "rust" -> "found""#,
+                r#"Node answer "\"rust\"" -> null (Other error: no answer)"#,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_tree_directory() {
+        let directory = std::env::temp_dir().join(format!("glint-traces-{}", uuid::Uuid::new_v4()));
+        let recorder = Arc::new(RunTreeRecorder::new().with_directory(&directory));
+        let graph = research_graph(&recorder).await;
+        let config = RunConfig::new()
+            .with_run_id("run-2")
+            .with_callback(recorder.clone());
+
+        graph
+            .execute_with_config(State::new("crab".to_string()), &config)
+            .await
+            .unwrap_err();
+
+        // Written runs are no longer kept in memory
+        assert!(recorder.run_tree("run-2").is_none());
+        let json = std::fs::read_to_string(directory.join("run-2.json")).unwrap();
+        let tree: RunTree = serde_json::from_str(&json).unwrap();
+        assert_eq!(tree.run_id, "run-2");
+        assert_eq!(tree.children.len(), 2);
+
+        // Run IDs cannot write outside the directory
+        let config = RunConfig::new()
+            .with_run_id("../eval/run 3")
+            .with_callback(recorder.clone());
+        graph
+            .execute_with_config(State::new("crab".to_string()), &config)
+            .await
+            .unwrap_err();
+        assert!(directory.join(".._eval_run_3.json").exists());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -2,6 +2,7 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, RwLock};
 
+use crate::callbacks::{CallbackHandler, Callbacks};
 use crate::error::Error;
 use crate::schema::Document;
 use crate::traits::{EmbeddingModel, VectorStore};
@@ -37,6 +38,8 @@ pub struct MemoryVectorStore {
     embedding_model: Arc<dyn EmbeddingModel>,
     /// Similarity metric to use
     similarity_metric: SimilarityMetric,
+    /// Handlers called on every search
+    callbacks: Callbacks,
 }
 
 impl MemoryVectorStore {
@@ -46,6 +49,7 @@ impl MemoryVectorStore {
             documents: Arc::new(RwLock::new(Vec::new())),
             embedding_model: Arc::new(embedding_model),
             similarity_metric: SimilarityMetric::Cosine,
+            callbacks: Callbacks::new(),
         }
     }
 
@@ -55,6 +59,21 @@ impl MemoryVectorStore {
         self
     }
 
+    /// Register a handler called on every search by query
+    pub fn with_callback(mut self, handler: Arc<dyn CallbackHandler>) -> Self {
+        self.callbacks.add(handler);
+        self
+    }
+
+    /// Search for the documents most similar to a query
+    async fn search_query(&self, query: &str, limit: usize) -> Result<Vec<(Document, f32)>> {
+        // Get query embedding
+        let query_embedding = self.embedding_model.invoke(query.to_string()).await?;
+
+        // Search by vector
+        self.search_by_vector(&query_embedding, limit).await
+    }
+
     /// Calculate similarity between two vectors based on selected metric
     fn calculate_similarity(&self, a: &[f32], b: &[f32]) -> f32 {
         match self.similarity_metric {
@@ -100,11 +119,17 @@ impl VectorStore for MemoryVectorStore {
     }
 
     async fn search(&self, query: &str, limit: usize) -> Result<Vec<(Document, f32)>> {
-        // Get query embedding
-        let query_embedding = self.embedding_model.invoke(query.to_string()).await?;
-
-        // Search by vector
-        self.search_by_vector(&query_embedding, limit).await
+        self.callbacks.on_retriever_start(query);
+        match self.search_query(query, limit).await {
+            Ok(results) => {
+                self.callbacks.on_retriever_end(&results);
+                Ok(results)
+            }
+            Err(e) => {
+                self.callbacks.on_retriever_error(&e);
+                Err(e)
+            }
+        }
     }
 
     async fn search_by_vector(